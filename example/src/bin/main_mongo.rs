@@ -1,13 +1,13 @@
 use anyhow::Result;
 use bson::doc;
-use rustling_data::api::CrudRepository;
+use rustling_data::api::{CrudRepository, Patch};
 use rustling_data::bson::oid::ObjectId;
 use rustling_data::Client;
 use rustling_data::ClientOptions;
-use rustling_derive::MongoRepository;
+use rustling_derive::{Entity, MongoRepository};
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Entity)]
 pub struct User {
     #[serde(rename = "_id")]
     pub id: ObjectId,
@@ -70,6 +70,16 @@ async fn main() -> Result<()> {
         println!("Updated user: {:?}", updated);
     }
 
+    // Patch a user: only `name` is written, other fields are left untouched
+    if let Some(first_user) = users.first() {
+        let patch: Patch<User> = UserPatch {
+            name: Some("Alice Cooper".to_string()),
+            ..Default::default()
+        };
+        let patched = repo.patch_one(&first_user.id, &patch).await?;
+        println!("Patched user: {:?}", patched);
+    }
+
     // Delete a user
     if let Some(first_user) = users.first() {
         let deleted_count = repo.delete_one(&first_user.id).await?;
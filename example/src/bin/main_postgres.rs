@@ -26,6 +26,7 @@ async fn main() -> Result<()> {
         .await?;
 
     let repo = UserRepository { pool: pool.clone() };
+    repo.verify_columns().await?;
 
     // --- INSERT ONE ---
     let new_user = User { id: 0, username: "alice".into() }; // id may be ignored if auto-increment
@@ -43,10 +44,22 @@ async fn main() -> Result<()> {
     // --- UPDATE ONE ---
     if let Some(mut u) = user.clone() {
         u.username = "alice_updated".into();
-        let updated = repo.update_one(&inserted_id, &u).await?;
+        let updated = repo.update_one(&u.id, &u).await?;
         println!("Updated user: {:?}", updated);
     }
 
+    // --- PATCH ONE ---
+    let patch = UserPatch { username: Some("alice_patched".into()) };
+    let patched = repo.patch_one(&inserted_id, &patch).await?;
+    println!("Patched user: {:?}", patched);
+
+    // --- TRACKED SAVE ---
+    if let Some(mut tracked) = repo.find_one_tracked(&inserted_id).await? {
+        tracked.username = "alice_tracked".into();
+        let saved = repo.save(&inserted_id, &mut tracked).await?;
+        println!("Saved tracked user: {:?}", saved);
+    }
+
     // --- DELETE ONE ---
     let deleted_count = repo.delete_one(&inserted_id).await?;
     println!("Deleted {} user(s)", deleted_count);
@@ -0,0 +1,119 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use rustling_data::api::CrudRepository;
+use rustling_data::events::{self, EntityDeleted, EntityInserted, EntityUpdated, Subscription};
+use rustling_data::{InMemoryRepository, SqlitePool};
+use rustling_derive::{Entity, Repository};
+use sqlx::FromRow;
+use sqlx::sqlite::SqlitePoolOptions;
+
+#[derive(Debug, Clone, PartialEq, Entity, FromRow)]
+struct Ticket {
+    id: i32,
+    title: String,
+}
+
+#[derive(Repository)]
+#[backend(sqlite)]
+#[entity(Ticket)]
+#[id(i32)]
+#[table("tickets")]
+struct TicketRepository {
+    pool: SqlitePool,
+}
+
+#[derive(Repository)]
+#[backend(memory)]
+#[entity(Ticket)]
+#[id(i32)]
+struct MemoryTicketRepository {
+    store: InMemoryRepository<Ticket, i32>,
+}
+
+async fn start_sqlite() -> SqlitePool {
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await
+        .expect("Failed to open SQLite database");
+
+    sqlx::query("CREATE TABLE tickets (id INTEGER PRIMARY KEY AUTOINCREMENT, title TEXT NOT NULL)")
+        .execute(&pool)
+        .await
+        .expect("Failed to create table");
+
+    pool
+}
+
+fn ticket(title: &str) -> Ticket {
+    Ticket {
+        id: 0,
+        title: title.to_string(),
+    }
+}
+
+async fn next<E: Send + Sync + 'static>(subscription: &mut Subscription<E>) -> Arc<E> {
+    tokio::time::timeout(Duration::from_secs(1), subscription.recv())
+        .await
+        .expect("No event published")
+        .expect("Sink closed")
+}
+
+/// Writes through `repo` and checks the event each write publishes.
+async fn check_events<R>(repo: &R)
+where
+    R: CrudRepository<Ticket, i32, sqlx::Error>,
+{
+    let mut inserted = events::subscribe::<EntityInserted<Ticket>>();
+    let mut updated = events::subscribe::<EntityUpdated<Ticket>>();
+    let mut deleted = events::subscribe::<EntityDeleted<i32>>();
+
+    let id = repo
+        .insert_one(&ticket("open"))
+        .await
+        .expect("Insert failed");
+    let event = next(&mut inserted).await;
+    assert_eq!((event.entity.id, event.entity.title.as_str()), (id, "open"));
+
+    repo.update_one(&id, &ticket("triaged"))
+        .await
+        .expect("Update failed");
+    let event = next(&mut updated).await;
+    assert_eq!(
+        (event.before.title.as_str(), event.after.title.as_str()),
+        ("open", "triaged")
+    );
+
+    let patch = TicketPatch {
+        title: Some("closed".to_string()),
+    };
+    repo.patch_one(&id, &patch).await.expect("Patch failed");
+    let event = next(&mut updated).await;
+    assert_eq!(
+        (event.before.title.as_str(), event.after.title.as_str()),
+        ("triaged", "closed")
+    );
+
+    assert_eq!(repo.delete_one(&id).await.expect("Delete failed"), 1);
+    assert_eq!(next(&mut deleted).await.id, id);
+
+    // Nothing is deleted the second time, so nothing is published.
+    assert_eq!(repo.delete_one(&id).await.expect("Delete failed"), 0);
+    let none = tokio::time::timeout(Duration::from_millis(50), deleted.recv()).await;
+    assert!(none.is_err());
+}
+
+// One test, so the two backends do not publish into each other's subscriptions.
+#[tokio::test]
+async fn test_repositories_publish_events() {
+    let repo = TicketRepository {
+        pool: start_sqlite().await,
+    };
+    check_events(&repo).await;
+
+    let repo = MemoryTicketRepository {
+        store: InMemoryRepository::new(),
+    };
+    check_events(&repo).await;
+}
@@ -0,0 +1,181 @@
+use std::cell::RefCell;
+
+use rustling_data::api::{CrudRepository, Lifecycle, RepositoryError};
+use rustling_data::{InMemoryRepository, SqlitePool};
+use rustling_derive::{Entity, Repository};
+use sqlx::FromRow;
+use sqlx::sqlite::SqlitePoolOptions;
+
+thread_local! {
+    /// Hooks run by the current test; `#[tokio::test]` runs each test on its own thread.
+    static CALLS: RefCell<Vec<&'static str>> = const { RefCell::new(Vec::new()) };
+}
+
+fn record(hook: &'static str) {
+    CALLS.with(|calls| calls.borrow_mut().push(hook));
+}
+
+/// Hooks recorded since the last call, without `after_load`, which reloads also trigger.
+fn take_writes() -> Vec<&'static str> {
+    CALLS
+        .with(|calls| calls.take())
+        .into_iter()
+        .filter(|hook| *hook != "after_load")
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Entity, FromRow)]
+#[rustling(lifecycle)]
+struct Account {
+    id: i32,
+    email: String,
+    loaded: bool,
+}
+
+impl Lifecycle for Account {
+    fn before_insert(&mut self) -> Result<(), String> {
+        record("before_insert");
+        self.email = self.email.trim().to_lowercase();
+        Ok(())
+    }
+
+    fn after_insert(&self) {
+        record("after_insert");
+    }
+
+    fn before_update(&mut self) -> Result<(), String> {
+        record("before_update");
+        if self.email.is_empty() {
+            return Err("email is required".to_string());
+        }
+        Ok(())
+    }
+
+    fn after_update(&self) {
+        record("after_update");
+    }
+
+    fn before_delete(&self) -> Result<(), String> {
+        record("before_delete");
+        if self.email.ends_with("@admin.example.com") {
+            return Err("admin accounts cannot be deleted".to_string());
+        }
+        Ok(())
+    }
+
+    fn after_load(&mut self) {
+        record("after_load");
+        self.loaded = true;
+    }
+}
+
+#[derive(Repository)]
+#[backend(sqlite)]
+#[entity(Account)]
+#[id(i32)]
+#[table("accounts")]
+struct AccountRepository {
+    pool: SqlitePool,
+}
+
+#[derive(Repository)]
+#[backend(memory)]
+#[entity(Account)]
+#[id(i32)]
+struct MemoryAccountRepository {
+    store: InMemoryRepository<Account, i32>,
+}
+
+async fn start_sqlite() -> SqlitePool {
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await
+        .expect("Failed to open SQLite database");
+
+    sqlx::query(
+        r#"
+        CREATE TABLE accounts (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            email TEXT NOT NULL,
+            loaded BOOLEAN NOT NULL DEFAULT FALSE
+        )
+        "#,
+    )
+    .execute(&pool)
+    .await
+    .expect("Failed to create table");
+
+    pool
+}
+
+fn account(email: &str) -> Account {
+    Account {
+        id: 0,
+        email: email.to_string(),
+        loaded: false,
+    }
+}
+
+/// Runs every hook through `repo`, which must start empty.
+async fn check_hooks<R>(repo: &R)
+where
+    R: CrudRepository<Account, i32, sqlx::Error>,
+{
+    let id = repo
+        .insert_one(&account("  Alice@Example.com "))
+        .await
+        .expect("Insert failed");
+    assert_eq!(take_writes(), ["before_insert", "after_insert"]);
+
+    let found = repo
+        .find_one(&id)
+        .await
+        .expect("Find failed")
+        .expect("Account missing");
+    assert_eq!(found.email, "alice@example.com");
+    assert!(found.loaded);
+
+    let updated = repo
+        .update_one(&id, &account("bob@example.com"))
+        .await
+        .expect("Update failed");
+    assert_eq!(
+        updated.map(|a| a.email),
+        Some("bob@example.com".to_string())
+    );
+    assert_eq!(take_writes(), ["before_update", "after_update"]);
+
+    let rejected = repo.update_one(&id, &account("")).await;
+    assert!(matches!(rejected, Err(RepositoryError::Other(_))));
+    assert_eq!(take_writes(), ["before_update"]);
+
+    let admin = repo
+        .insert_one(&account("root@admin.example.com"))
+        .await
+        .expect("Insert failed");
+    take_writes();
+    let refused = repo.delete_one(&admin).await;
+    assert!(matches!(refused, Err(RepositoryError::Other(_))));
+    assert_eq!(take_writes(), ["before_delete"]);
+
+    assert_eq!(repo.delete_one(&id).await.expect("Delete failed"), 1);
+    assert_eq!(take_writes(), ["before_delete"]);
+    assert_eq!(repo.find_all().await.expect("Find all failed").len(), 1);
+}
+
+#[tokio::test]
+async fn test_sqlite_repository_runs_hooks() {
+    let repo = AccountRepository {
+        pool: start_sqlite().await,
+    };
+    check_hooks(&repo).await;
+}
+
+#[tokio::test]
+async fn test_memory_repository_runs_hooks() {
+    let repo = MemoryAccountRepository {
+        store: InMemoryRepository::new(),
+    };
+    check_hooks(&repo).await;
+}
@@ -0,0 +1,62 @@
+use rustling_data::InMemoryRepository;
+use rustling_data::api::{CrudRepository, RepositoryError};
+use rustling_derive::{Entity, Repository};
+use sqlx::FromRow;
+
+#[cfg(not(test))]
+use rustling_data::PgPool;
+
+#[derive(Debug, Clone, PartialEq, Entity, FromRow)]
+struct Task {
+    id: i32,
+    title: String,
+    #[rustling(soft_delete)]
+    deleted: bool,
+}
+
+/// Postgres in regular builds, in memory under `cfg(test)`, as shown in the README.
+#[derive(Repository)]
+#[cfg_attr(test, backend(memory))]
+#[entity(Task)]
+#[id(i32)]
+#[table("tasks")]
+struct TaskRepository {
+    #[cfg(not(test))]
+    pool: PgPool,
+    #[cfg(test)]
+    store: InMemoryRepository<Task, i32>,
+}
+
+impl TaskRepository {
+    /// Postgres-only methods are not generated for the memory backend, so their callers are
+    /// compiled out of tests too.
+    #[cfg(not(test))]
+    async fn prepare(&self) -> Result<(), RepositoryError<sqlx::Error>> {
+        self.create_table().await
+    }
+}
+
+fn task(title: &str) -> Task {
+    Task {
+        id: 0,
+        title: title.to_string(),
+        deleted: false,
+    }
+}
+
+#[tokio::test]
+async fn test_cfg_test_switches_to_memory() {
+    let repo = TaskRepository {
+        store: InMemoryRepository::new(),
+    };
+
+    // The error type stays the one of the replaced backend.
+    let id: Result<i32, RepositoryError<sqlx::Error>> = repo.insert_one(&task("write docs")).await;
+    let id = id.expect("Insert failed");
+    assert_eq!(repo.delete_one(&id).await.expect("Delete failed"), 1);
+    assert!(repo.find_all().await.expect("Find all failed").is_empty());
+
+    assert_eq!(repo.restore_one(&id).await.expect("Restore failed"), 1);
+    let found = repo.find_one(&id).await.expect("Find failed");
+    assert_eq!(found.map(|t| t.title), Some("write docs".to_string()));
+}
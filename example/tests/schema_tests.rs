@@ -0,0 +1,27 @@
+use rustling_derive::Entity;
+
+#[derive(Debug, Clone, PartialEq, Entity, sqlx::FromRow)]
+struct Reading {
+    id: i64,
+    level: i8,
+    offset: i16,
+    count: Option<i32>,
+}
+
+#[test]
+fn test_derived_column_types() {
+    let types: Vec<_> = Reading::table_schema()
+        .columns
+        .iter()
+        .map(|c| (c.name, c.sql_type))
+        .collect();
+    assert_eq!(
+        types,
+        vec![
+            ("id", Some("BIGINT")),
+            ("level", Some("\"char\"")),
+            ("offset", Some("SMALLINT")),
+            ("count", Some("INTEGER")),
+        ]
+    );
+}
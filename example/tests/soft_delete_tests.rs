@@ -0,0 +1,132 @@
+use rustling_data::api::CrudRepository;
+use rustling_data::{InMemoryRepository, SqlitePool};
+use rustling_derive::{Entity, Repository};
+use sqlx::FromRow;
+use sqlx::sqlite::SqlitePoolOptions;
+
+#[derive(Debug, Clone, PartialEq, Entity, FromRow)]
+struct Note {
+    id: i32,
+    title: String,
+    #[rustling(version)]
+    version: i64,
+    #[rustling(soft_delete)]
+    deleted: bool,
+}
+
+#[derive(Repository)]
+#[backend(sqlite)]
+#[entity(Note)]
+#[id(i32)]
+#[table("notes")]
+struct NoteRepository {
+    pool: SqlitePool,
+}
+
+#[derive(Repository)]
+#[backend(memory)]
+#[entity(Note)]
+#[id(i32)]
+struct MemoryNoteRepository {
+    store: InMemoryRepository<Note, i32>,
+}
+
+async fn start_sqlite() -> SqlitePool {
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await
+        .expect("Failed to open SQLite database");
+
+    sqlx::query(
+        r#"
+        CREATE TABLE notes (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            title TEXT NOT NULL,
+            version INTEGER NOT NULL DEFAULT 0,
+            deleted BOOLEAN NOT NULL DEFAULT FALSE
+        )
+        "#,
+    )
+    .execute(&pool)
+    .await
+    .expect("Failed to create table");
+
+    pool
+}
+
+fn note(title: &str) -> Note {
+    Note {
+        id: 0,
+        title: title.to_string(),
+        version: 0,
+        deleted: false,
+    }
+}
+
+fn patch(title: &str) -> NotePatch {
+    NotePatch {
+        title: Some(title.to_string()),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn test_sqlite_skips_deleted_rows_on_write() {
+    let repo = NoteRepository {
+        pool: start_sqlite().await,
+    };
+    let id = repo
+        .insert_one(&note("draft"))
+        .await
+        .expect("Insert failed");
+    assert_eq!(repo.delete_one(&id).await.expect("Delete failed"), 1);
+
+    let updated = repo
+        .update_one(&id, &note("overwritten"))
+        .await
+        .expect("Update failed");
+    assert!(updated.is_none());
+    let patched = repo
+        .patch_one(&id, &patch("patched"))
+        .await
+        .expect("Patch failed");
+    assert!(patched.is_none());
+
+    let stored = repo
+        .find_one_with_deleted(&id)
+        .await
+        .expect("Find failed")
+        .expect("Row missing");
+    assert_eq!((stored.title.as_str(), stored.version), ("draft", 0));
+}
+
+#[tokio::test]
+async fn test_memory_skips_deleted_rows_on_write() {
+    let repo = MemoryNoteRepository {
+        store: InMemoryRepository::new(),
+    };
+    let id = repo
+        .insert_one(&note("draft"))
+        .await
+        .expect("Insert failed");
+    assert_eq!(repo.delete_one(&id).await.expect("Delete failed"), 1);
+
+    let updated = repo
+        .update_one(&id, &note("overwritten"))
+        .await
+        .expect("Update failed");
+    assert!(updated.is_none());
+    let patched = repo
+        .patch_one(&id, &patch("patched"))
+        .await
+        .expect("Patch failed");
+    assert!(patched.is_none());
+
+    let stored = repo
+        .find_one_with_deleted(&id)
+        .await
+        .expect("Find failed")
+        .expect("Row missing");
+    assert_eq!((stored.title.as_str(), stored.version), ("draft", 0));
+}
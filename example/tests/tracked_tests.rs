@@ -0,0 +1,122 @@
+use std::convert::Infallible;
+use std::sync::Mutex;
+
+use rustling_data::api::{CrudRepository, Patch, RepositoryError};
+use rustling_derive::Entity;
+
+#[derive(Debug, Clone, PartialEq, Entity)]
+struct Note {
+    id: i32,
+    title: String,
+    done: bool,
+}
+
+/// Repository keeping notes in a `Vec` and recording every patch it receives.
+#[derive(Default)]
+struct Notes {
+    notes: Mutex<Vec<Note>>,
+    patches: Mutex<Vec<(Option<String>, Option<bool>)>>,
+}
+
+type Outcome<T> = Result<T, RepositoryError<Infallible>>;
+
+#[async_trait::async_trait]
+impl CrudRepository<Note, i32, Infallible> for Notes {
+    async fn find_all(&self) -> Outcome<Vec<Note>> {
+        Ok(self.notes.lock().unwrap().clone())
+    }
+
+    async fn find_one(&self, id: &i32) -> Outcome<Option<Note>> {
+        Ok(self
+            .notes
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|n| n.id == *id)
+            .cloned())
+    }
+
+    async fn insert_one(&self, entity: &Note) -> Outcome<i32> {
+        let mut notes = self.notes.lock().unwrap();
+        let id = notes.len() as i32 + 1;
+        notes.push(Note {
+            id,
+            ..entity.clone()
+        });
+        Ok(id)
+    }
+
+    async fn update_one(&self, _id: &i32, _entity: &Note) -> Outcome<Option<Note>> {
+        unimplemented!("save patches")
+    }
+
+    async fn patch_one(&self, id: &i32, patch: &Patch<Note>) -> Outcome<Option<Note>> {
+        self.patches
+            .lock()
+            .unwrap()
+            .push((patch.title.clone(), patch.done));
+        let mut notes = self.notes.lock().unwrap();
+        let Some(note) = notes.iter_mut().find(|n| n.id == *id) else {
+            return Ok(None);
+        };
+        if let Some(title) = &patch.title {
+            note.title = title.clone();
+        }
+        if let Some(done) = patch.done {
+            note.done = done;
+        }
+        Ok(Some(note.clone()))
+    }
+
+    async fn delete_one(&self, _id: &i32) -> Outcome<u64> {
+        unimplemented!("not needed")
+    }
+}
+
+fn note(title: &str) -> Note {
+    Note {
+        id: 0,
+        title: title.to_string(),
+        done: false,
+    }
+}
+
+#[tokio::test]
+async fn test_find_all_tracked_saves_changed_fields() {
+    let repo = Notes::default();
+    repo.insert_one(&note("first"))
+        .await
+        .expect("Insert failed");
+    repo.insert_one(&note("second"))
+        .await
+        .expect("Insert failed");
+
+    let mut tracked = repo.find_all_tracked().await.expect("Find all failed");
+    assert_eq!(tracked.len(), 2);
+    for entity in &mut tracked {
+        entity.done = true;
+        let id = entity.id;
+        repo.save(&id, entity).await.expect("Save failed");
+        assert_eq!(entity.original(), &**entity);
+    }
+    assert_eq!(
+        *repo.patches.lock().unwrap(),
+        vec![(None, Some(true)), (None, Some(true))]
+    );
+
+    let mut first = repo
+        .find_one_tracked(&1)
+        .await
+        .expect("Find failed")
+        .expect("Note missing");
+    first.title = "renamed".to_string();
+    let saved = repo.save(&1, &mut first).await.expect("Save failed");
+    assert_eq!(
+        saved.map(|n| (n.title, n.done)),
+        Some(("renamed".to_string(), true))
+    );
+    assert_eq!(
+        repo.patches.lock().unwrap().last(),
+        Some(&(Some("renamed".to_string()), None))
+    );
+}
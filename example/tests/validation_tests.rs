@@ -0,0 +1,84 @@
+use rustling_data::InMemoryRepository;
+use rustling_data::api::{CrudRepository, Entity, RepositoryError};
+use rustling_data::validate::FieldError;
+use rustling_derive::{Entity, Repository};
+
+fn not_reserved(name: &str) -> Result<(), String> {
+    match name {
+        "admin" | "root" => Err("is reserved".to_string()),
+        _ => Ok(()),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Entity, sqlx::FromRow)]
+struct Member {
+    id: i32,
+    #[rustling(
+        length(min = 3, max = 16),
+        regex = "^[a-z0-9_]+$",
+        custom = "not_reserved"
+    )]
+    name: String,
+    #[rustling(email)]
+    email: Option<String>,
+    #[rustling(range(min = 13, max = 150))]
+    age: i32,
+}
+
+#[derive(Repository)]
+#[backend(memory)]
+#[entity(Member)]
+#[id(i32)]
+struct MemberRepository {
+    store: InMemoryRepository<Member, i32>,
+}
+
+fn member(name: &str, email: Option<&str>, age: i32) -> Member {
+    Member {
+        id: 0,
+        name: name.to_string(),
+        email: email.map(str::to_string),
+        age,
+    }
+}
+
+#[test]
+fn test_derived_validate() {
+    assert_eq!(
+        member("alice_01", Some("alice@example.com"), 30).validate(),
+        Ok(())
+    );
+    assert_eq!(member("bob", None, 13).validate(), Ok(()));
+
+    let errors = member("Al", Some("not-an-email"), 200)
+        .validate()
+        .expect_err("Invalid member passed");
+    assert_eq!(
+        errors,
+        vec![
+            FieldError::new("name", "length must be at least 3"),
+            FieldError::new("name", "must match ^[a-z0-9_]+$"),
+            FieldError::new("email", "must be a valid email address"),
+            FieldError::new("age", "must be at most 150"),
+        ]
+    );
+
+    let errors = member("admin", None, 40)
+        .validate()
+        .expect_err("Reserved name passed");
+    assert_eq!(errors, vec![FieldError::new("name", "is reserved")]);
+}
+
+#[tokio::test]
+async fn test_repository_rejects_invalid_entity() {
+    let repo = MemberRepository {
+        store: InMemoryRepository::new(),
+    };
+
+    let rejected = repo.insert_one(&member("x", None, 30)).await;
+    assert!(matches!(
+        rejected,
+        Err(RepositoryError::Validation(errors)) if errors[0].field == "name"
+    ));
+    assert!(repo.find_all().await.expect("Find all failed").is_empty());
+}
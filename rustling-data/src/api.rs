@@ -1,7 +1,13 @@
+use crate::audit::AuditStamp;
 #[cfg(feature = "postgres")]
-use sqlx::{Encode, Postgres, Type};
+use crate::drivers::postgres::PgValues;
+use crate::schema::Index;
+use crate::validate::FieldError;
+use std::sync::{Arc, PoisonError, RwLock};
 #[cfg(feature = "mongo")]
 pub type MongoError = mongodb::error::Error;
+#[cfg(feature = "redis")]
+pub type RedisError = redis::RedisError;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -12,22 +18,516 @@ pub enum RepositoryError<DB> {
     ConnectionError(DB),
     #[error("constraint violation: {0}")]
     ConstraintViolation(String),
+    #[error("schema mismatch: {0}")]
+    SchemaMismatch(String),
+    #[error("optimistic lock conflict: entity was modified concurrently")]
+    OptimisticLockConflict,
+    #[error("validation failed: {}", display_field_errors(.0))]
+    Validation(Vec<FieldError>),
     #[error("unknown error: {0}")]
     Other(String),
 }
 
+/// Driver error of any backend, see [`ErasedRepository`].
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+impl<DB: std::error::Error + Send + Sync + 'static> RepositoryError<DB> {
+    /// The same error with the driver error boxed.
+    pub fn boxed(self) -> RepositoryError<BoxError> {
+        match self {
+            RepositoryError::NotFound => RepositoryError::NotFound,
+            RepositoryError::ConnectionError(e) => RepositoryError::ConnectionError(Box::new(e)),
+            RepositoryError::ConstraintViolation(msg) => RepositoryError::ConstraintViolation(msg),
+            RepositoryError::SchemaMismatch(msg) => RepositoryError::SchemaMismatch(msg),
+            RepositoryError::OptimisticLockConflict => RepositoryError::OptimisticLockConflict,
+            RepositoryError::Validation(errors) => RepositoryError::Validation(errors),
+            RepositoryError::Other(msg) => RepositoryError::Other(msg),
+        }
+    }
+}
+
+fn display_field_errors(errors: &[FieldError]) -> String {
+    errors
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Where an entity field is stored: its column in Postgres and its key in Mongo documents.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FieldName {
+    pub column: &'static str,
+    pub key: &'static str,
+}
+
+/// The `#[rustling(soft_delete)]` field of an entity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SoftDelete {
+    /// `Option<_>` timestamp: set to the deletion time, null while the entity is live.
+    Timestamp(FieldName),
+    /// `bool` flag: `true` once the entity is deleted.
+    Flag(FieldName),
+}
+
+impl SoftDelete {
+    pub fn field(&self) -> FieldName {
+        match self {
+            SoftDelete::Timestamp(field) | SoftDelete::Flag(field) => *field,
+        }
+    }
+}
+
+/// Hooks called by generated repositories around reads and writes.
+///
+/// `#[derive(Entity)]` provides an empty implementation unless the struct is marked
+/// `#[rustling(lifecycle)]`, in which case it is implemented by hand and only the needed
+/// hooks are overridden. An error returned by a `before_*` hook aborts the operation
+/// with [`RepositoryError::Other`].
+///
+/// `patch_one` writes the patch as given: only `after_load` and `after_update` run for it.
+pub trait Lifecycle {
+    fn before_insert(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn after_insert(&self) {}
+
+    fn before_update(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn after_update(&self) {}
+
+    fn before_delete(&self) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn after_load(&mut self) {}
+
+    /// `self` after [`Lifecycle::after_load`], for mapping over query results.
+    fn loaded(mut self) -> Self
+    where
+        Self: Sized,
+    {
+        self.after_load();
+        self
+    }
+}
+
+/// Metadata generated by `#[derive(Entity)]`.
+pub trait Entity: Lifecycle {
+    /// Companion struct with every non-id field wrapped in `Option`, see [`Patch`].
+    type Patch: Default + Send + Sync;
+
+    /// The `#[rustling(version)]` field used for optimistic locking, if any.
+    const VERSION_FIELD: Option<FieldName> = None;
+
+    /// Field marking the entity as deleted instead of removing it, if any.
+    const SOFT_DELETE: Option<SoftDelete> = None;
+
+    /// Indexes declared with `#[rustling(index)]` and `#[rustling(unique)]`.
+    const INDEXES: &'static [Index] = &[];
+
+    /// Current value of the version field.
+    fn version(&self) -> Option<i64> {
+        None
+    }
+
+    /// Whether the entity implements [`Lifecycle`] itself (`#[rustling(lifecycle)]`).
+    ///
+    /// Repositories only load an entity before deleting it when this is set.
+    const LIFECYCLE: bool = false;
+
+    /// Field-by-field copy of `self` that audit stamps and hooks modify before a write.
+    fn copy_for_write(&self) -> Self
+    where
+        Self: Sized;
+
+    /// Sets the audit fields for an insert.
+    fn stamp_insert(&mut self, _stamp: &AuditStamp) {}
+
+    /// Sets the `updated_*` audit fields.
+    fn stamp_update(&mut self, _stamp: &AuditStamp) {}
+
+    /// Runs the field validation attributes; see [`crate::validate`].
+    fn validate(&self) -> Result<(), Vec<FieldError>> {
+        Ok(())
+    }
+
+    /// Patch holding the fields of `self` that differ from `original`.
+    fn changes(&self, original: &Self) -> Self::Patch;
+}
+
+/// Partial update for `T`: only fields set to `Some` are written.
+///
+/// For fields that are already `Option<_>` in the entity, `Some(None)` clears the value
+/// (`NULL` in Postgres, `$unset` in Mongo).
+pub type Patch<T> = <T as Entity>::Patch;
+
+/// Entity loaded from a repository that remembers its original state.
+///
+/// Mutate it through `DerefMut`; [`CrudRepository::save`] then writes only the fields
+/// that changed since it was loaded, leaving concurrent edits to other fields intact.
+#[derive(Debug, Clone)]
+pub struct Tracked<T> {
+    original: T,
+    current: T,
+}
+
+impl<T: Entity + Clone> Tracked<T> {
+    pub fn new(entity: T) -> Self {
+        Self {
+            original: entity.clone(),
+            current: entity,
+        }
+    }
+
+    /// Fields changed since the entity was loaded or last saved.
+    pub fn changes(&self) -> Patch<T> {
+        self.current.changes(&self.original)
+    }
+
+    pub fn original(&self) -> &T {
+        &self.original
+    }
+
+    pub fn into_inner(self) -> T {
+        self.current
+    }
+
+    fn reset(&mut self, entity: T) {
+        self.original = entity.clone();
+        self.current = entity;
+    }
+}
+
+impl<T> std::ops::Deref for Tracked<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.current
+    }
+}
+
+impl<T> std::ops::DerefMut for Tracked<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.current
+    }
+}
+
 #[async_trait::async_trait]
 pub trait CrudRepository<T, ID, DB> {
     async fn find_all(&self) -> Result<Vec<T>, RepositoryError<DB>>;
     async fn find_one(&self, id: &ID) -> Result<Option<T>, RepositoryError<DB>>;
     async fn insert_one(&self, entity: &T) -> Result<ID, RepositoryError<DB>>;
     async fn update_one(&self, id: &ID, entity: &T) -> Result<Option<T>, RepositoryError<DB>>;
+    async fn patch_one(&self, id: &ID, patch: &Patch<T>) -> Result<Option<T>, RepositoryError<DB>>
+    where
+        T: Entity;
     async fn delete_one(&self, id: &ID) -> Result<u64, RepositoryError<DB>>;
+
+    async fn find_one_tracked(&self, id: &ID) -> Result<Option<Tracked<T>>, RepositoryError<DB>>
+    where
+        Self: Sync,
+        T: Entity + Clone,
+        ID: Sync,
+    {
+        Ok(self.find_one(id).await?.map(Tracked::new))
+    }
+
+    async fn find_all_tracked(&self) -> Result<Vec<Tracked<T>>, RepositoryError<DB>>
+    where
+        Self: Sync,
+        T: Entity + Clone,
+    {
+        Ok(self
+            .find_all()
+            .await?
+            .into_iter()
+            .map(Tracked::new)
+            .collect())
+    }
+
+    /// Writes the changed fields of `entity` and refreshes it with the stored state.
+    async fn save(&self, id: &ID, entity: &mut Tracked<T>) -> Result<Option<T>, RepositoryError<DB>>
+    where
+        Self: Sync,
+        T: Entity + Clone + Send + Sync,
+        ID: Sync,
+    {
+        let saved = self.patch_one(id, &entity.changes()).await?;
+        if let Some(stored) = &saved {
+            entity.reset(stored.clone());
+        }
+        Ok(saved)
+    }
+}
+
+/// Repository of `T` whose driver error is an associated type rather than a parameter, so
+/// service code can be written against it without naming a backend:
+///
+/// ```rust,ignore
+/// async fn rename<R: Repository<User, i32>>(users: &R, id: i32, name: &str) -> Result<(), RepositoryError<R::Error>> { ... }
+/// ```
+///
+/// Implemented by every derived repository and by [`crate::InMemoryRepository`]. Wrap them in
+/// [`ErasedRepository`], or call [`Repository::into_dyn`], to use repositories of different
+/// backends as one type.
+#[async_trait::async_trait]
+pub trait Repository<T, ID>: Send + Sync {
+    type Error;
+
+    async fn find_all(&self) -> Result<Vec<T>, RepositoryError<Self::Error>>;
+    async fn find_one(&self, id: &ID) -> Result<Option<T>, RepositoryError<Self::Error>>;
+    async fn insert_one(&self, entity: &T) -> Result<ID, RepositoryError<Self::Error>>;
+    async fn update_one(
+        &self,
+        id: &ID,
+        entity: &T,
+    ) -> Result<Option<T>, RepositoryError<Self::Error>>;
+    async fn patch_one(
+        &self,
+        id: &ID,
+        patch: &Patch<T>,
+    ) -> Result<Option<T>, RepositoryError<Self::Error>>
+    where
+        T: Entity;
+    async fn delete_one(&self, id: &ID) -> Result<u64, RepositoryError<Self::Error>>;
+
+    /// This repository as a shared trait object, e.g. for application state.
+    fn into_dyn(self) -> DynRepository<T, ID>
+    where
+        Self: Sized + 'static,
+        Self::Error: std::error::Error + Send + Sync + 'static,
+        T: Send + Sync + 'static,
+        ID: Sync + 'static,
+    {
+        Arc::new(ErasedRepository(self))
+    }
+}
+
+/// Repository of any backend, shared between tasks.
+pub type DynRepository<T, ID> = Arc<dyn Repository<T, ID, Error = BoxError>>;
+
+/// Repository with its driver error boxed into a [`BoxError`].
+///
+/// All wrapped repositories of `T` share the error type, so a Postgres, a Mongo and an in-memory
+/// repository can sit behind the same `dyn Repository<T, ID, Error = BoxError>`.
+pub struct ErasedRepository<R>(pub R);
+
+#[async_trait::async_trait]
+impl<T, ID, R> Repository<T, ID> for ErasedRepository<R>
+where
+    T: Send + Sync + 'static,
+    ID: Sync + 'static,
+    R: Repository<T, ID>,
+    R::Error: std::error::Error + Send + Sync + 'static,
+{
+    type Error = BoxError;
+
+    async fn find_all(&self) -> Result<Vec<T>, RepositoryError<BoxError>> {
+        self.0.find_all().await.map_err(RepositoryError::boxed)
+    }
+
+    async fn find_one(&self, id: &ID) -> Result<Option<T>, RepositoryError<BoxError>> {
+        self.0.find_one(id).await.map_err(RepositoryError::boxed)
+    }
+
+    async fn insert_one(&self, entity: &T) -> Result<ID, RepositoryError<BoxError>> {
+        self.0
+            .insert_one(entity)
+            .await
+            .map_err(RepositoryError::boxed)
+    }
+
+    async fn update_one(
+        &self,
+        id: &ID,
+        entity: &T,
+    ) -> Result<Option<T>, RepositoryError<BoxError>> {
+        self.0
+            .update_one(id, entity)
+            .await
+            .map_err(RepositoryError::boxed)
+    }
+
+    async fn patch_one(
+        &self,
+        id: &ID,
+        patch: &Patch<T>,
+    ) -> Result<Option<T>, RepositoryError<BoxError>>
+    where
+        T: Entity,
+    {
+        self.0
+            .patch_one(id, patch)
+            .await
+            .map_err(RepositoryError::boxed)
+    }
+
+    async fn delete_one(&self, id: &ID) -> Result<u64, RepositoryError<BoxError>> {
+        self.0.delete_one(id).await.map_err(RepositoryError::boxed)
+    }
+}
+
+/// Repository whose implementation can be replaced while it is in use, e.g. to move reads from
+/// Postgres to Mongo behind a feature flag.
+///
+/// Calls already running finish on the repository they started with.
+pub struct SwappableRepository<T, ID> {
+    current: RwLock<DynRepository<T, ID>>,
+}
+
+impl<T, ID> SwappableRepository<T, ID> {
+    pub fn new(repository: DynRepository<T, ID>) -> Self {
+        Self {
+            current: RwLock::new(repository),
+        }
+    }
+
+    /// The repository calls currently go to.
+    pub fn current(&self) -> DynRepository<T, ID> {
+        self.current
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Sends subsequent calls to `repository`; returns the previous one.
+    pub fn swap(&self, repository: DynRepository<T, ID>) -> DynRepository<T, ID> {
+        let mut current = self.current.write().unwrap_or_else(PoisonError::into_inner);
+        std::mem::replace(&mut current, repository)
+    }
 }
 
+#[async_trait::async_trait]
+impl<T, ID> Repository<T, ID> for SwappableRepository<T, ID>
+where
+    T: Send + Sync + 'static,
+    ID: Send + Sync + 'static,
+{
+    type Error = BoxError;
+
+    async fn find_all(&self) -> Result<Vec<T>, RepositoryError<BoxError>> {
+        self.current().find_all().await
+    }
+
+    async fn find_one(&self, id: &ID) -> Result<Option<T>, RepositoryError<BoxError>> {
+        self.current().find_one(id).await
+    }
+
+    async fn insert_one(&self, entity: &T) -> Result<ID, RepositoryError<BoxError>> {
+        self.current().insert_one(entity).await
+    }
+
+    async fn update_one(
+        &self,
+        id: &ID,
+        entity: &T,
+    ) -> Result<Option<T>, RepositoryError<BoxError>> {
+        self.current().update_one(id, entity).await
+    }
+
+    async fn patch_one(
+        &self,
+        id: &ID,
+        patch: &Patch<T>,
+    ) -> Result<Option<T>, RepositoryError<BoxError>>
+    where
+        T: Entity,
+    {
+        self.current().patch_one(id, patch).await
+    }
+
+    async fn delete_one(&self, id: &ID) -> Result<u64, RepositoryError<BoxError>> {
+        self.current().delete_one(id).await
+    }
+}
+
+/// Lets `Arc<dyn ...>` and `Box<dyn ...>` repositories be passed where a repository is expected.
+macro_rules! forward_repository {
+    ($($pointer:ident),*) => {$(
+        #[async_trait::async_trait]
+        impl<T, ID, DB, R> CrudRepository<T, ID, DB> for $pointer<R>
+        where
+            T: Send + Sync + 'static,
+            ID: Sync + 'static,
+            DB: 'static,
+            R: CrudRepository<T, ID, DB> + Send + Sync + ?Sized,
+        {
+            async fn find_all(&self) -> Result<Vec<T>, RepositoryError<DB>> {
+                (**self).find_all().await
+            }
+
+            async fn find_one(&self, id: &ID) -> Result<Option<T>, RepositoryError<DB>> {
+                (**self).find_one(id).await
+            }
+
+            async fn insert_one(&self, entity: &T) -> Result<ID, RepositoryError<DB>> {
+                (**self).insert_one(entity).await
+            }
+
+            async fn update_one(&self, id: &ID, entity: &T) -> Result<Option<T>, RepositoryError<DB>> {
+                (**self).update_one(id, entity).await
+            }
+
+            async fn patch_one(&self, id: &ID, patch: &Patch<T>) -> Result<Option<T>, RepositoryError<DB>>
+            where
+                T: Entity,
+            {
+                (**self).patch_one(id, patch).await
+            }
+
+            async fn delete_one(&self, id: &ID) -> Result<u64, RepositoryError<DB>> {
+                (**self).delete_one(id).await
+            }
+        }
+
+        #[async_trait::async_trait]
+        impl<T, ID, R> Repository<T, ID> for $pointer<R>
+        where
+            T: Send + Sync + 'static,
+            ID: Sync + 'static,
+            R: Repository<T, ID> + ?Sized,
+        {
+            type Error = R::Error;
+
+            async fn find_all(&self) -> Result<Vec<T>, RepositoryError<R::Error>> {
+                (**self).find_all().await
+            }
+
+            async fn find_one(&self, id: &ID) -> Result<Option<T>, RepositoryError<R::Error>> {
+                (**self).find_one(id).await
+            }
+
+            async fn insert_one(&self, entity: &T) -> Result<ID, RepositoryError<R::Error>> {
+                (**self).insert_one(entity).await
+            }
+
+            async fn update_one(&self, id: &ID, entity: &T) -> Result<Option<T>, RepositoryError<R::Error>> {
+                (**self).update_one(id, entity).await
+            }
+
+            async fn patch_one(&self, id: &ID, patch: &Patch<T>) -> Result<Option<T>, RepositoryError<R::Error>>
+            where
+                T: Entity,
+            {
+                (**self).patch_one(id, patch).await
+            }
+
+            async fn delete_one(&self, id: &ID) -> Result<u64, RepositoryError<R::Error>> {
+                (**self).delete_one(id).await
+            }
+        }
+    )*};
+}
+
+forward_repository!(Arc, Box);
+
 #[cfg(feature = "postgres")]
 pub trait PostgresEntity {
     type Id;
     fn columns() -> &'static [&'static str];
-    fn values<'e>(&'e self) -> Vec<&'e (impl Encode<'e, Postgres> + Type<Postgres>)>;
+    fn values(&self) -> PgValues;
 }
@@ -0,0 +1,117 @@
+//! Values for the `#[rustling(created_at)]`, `#[rustling(updated_at)]`,
+//! `#[rustling(created_by)]` and `#[rustling(updated_by)]` entity fields.
+//!
+//! Timestamps come from the global [`Clock`] (the system clock unless replaced with
+//! [`set_clock`]) and are converted with `From<SystemTime>`, which `chrono`, `time` and
+//! `bson` date types implement. The actor is whatever value the current task runs
+//! [`with_actor`] with; it is only written to fields of that exact type (or `Option` of it).
+
+use std::any::Any;
+use std::sync::{Arc, RwLock};
+use std::time::SystemTime;
+#[cfg(any(feature = "postgres", feature = "sqlite", feature = "mysql"))]
+use std::time::UNIX_EPOCH;
+
+/// Source of the audit timestamps.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> SystemTime;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+static CLOCK: RwLock<Option<Arc<dyn Clock>>> = RwLock::new(None);
+
+/// Replaces the clock used by all repositories, e.g. with a fixed clock in tests.
+pub fn set_clock(clock: impl Clock + 'static) {
+    *CLOCK.write().unwrap_or_else(|e| e.into_inner()) = Some(Arc::new(clock));
+}
+
+pub fn now() -> SystemTime {
+    match CLOCK.read().unwrap_or_else(|e| e.into_inner()).as_ref() {
+        Some(clock) => clock.now(),
+        None => SystemTime::now(),
+    }
+}
+
+/// [`now`] in seconds since the Unix epoch, for SQL drivers to bind and convert in the query.
+#[cfg(any(feature = "postgres", feature = "sqlite", feature = "mysql"))]
+pub(crate) fn unix_seconds() -> f64 {
+    match now().duration_since(UNIX_EPOCH) {
+        Ok(since) => since.as_secs_f64(),
+        Err(before) => -before.duration().as_secs_f64(),
+    }
+}
+
+type Actor = Arc<dyn Any + Send + Sync>;
+
+#[cfg(feature = "tokio")]
+tokio::task_local! {
+    static ACTOR: Actor;
+}
+
+/// Runs `fut` with `actor` as the current user for the audit fields it writes.
+#[cfg(feature = "tokio")]
+pub async fn with_actor<A, F>(actor: A, fut: F) -> F::Output
+where
+    A: Any + Send + Sync,
+    F: std::future::Future,
+{
+    ACTOR.scope(Arc::new(actor), fut).await
+}
+
+/// Actor set by [`with_actor`] for the current task, if it has type `A`.
+pub fn current_actor<A: Clone + 'static>() -> Option<A> {
+    current_actor_any().and_then(|actor| actor.downcast_ref::<A>().cloned())
+}
+
+#[cfg(feature = "tokio")]
+fn current_actor_any() -> Option<Actor> {
+    ACTOR.try_with(|actor| actor.clone()).ok()
+}
+
+#[cfg(not(feature = "tokio"))]
+fn current_actor_any() -> Option<Actor> {
+    None
+}
+
+/// Time and actor applied to the audit fields of one write.
+#[derive(Clone)]
+pub struct AuditStamp {
+    now: SystemTime,
+    actor: Option<Actor>,
+}
+
+impl AuditStamp {
+    /// Stamp from the global clock and the current task's actor.
+    pub fn current() -> Self {
+        Self {
+            now: now(),
+            actor: current_actor_any(),
+        }
+    }
+
+    pub fn new(now: SystemTime) -> Self {
+        Self { now, actor: None }
+    }
+
+    pub fn by<A: Any + Send + Sync>(mut self, actor: A) -> Self {
+        self.actor = Some(Arc::new(actor));
+        self
+    }
+
+    pub fn now(&self) -> SystemTime {
+        self.now
+    }
+
+    pub fn actor<A: Clone + 'static>(&self) -> Option<A> {
+        self.actor
+            .as_ref()
+            .and_then(|actor| actor.downcast_ref::<A>().cloned())
+    }
+}
@@ -1,6 +1,14 @@
 #[cfg(feature = "mongo")]
 pub mod mongo;
+#[cfg(feature = "mysql")]
+pub mod mysql;
+#[cfg(feature = "postgres")]
+pub mod outbox;
 #[cfg(feature = "postgres")]
 pub mod postgres;
+#[cfg(feature = "redis")]
+pub mod redis;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
 #[cfg(feature = "mongo")]
 pub use mongodb;
\ No newline at end of file
@@ -1,13 +1,211 @@
-use crate::api::RepositoryError;
+use std::time::Duration;
+
+use crate::api::{RepositoryError, SoftDelete};
+use crate::audit;
 use crate::bson::to_document;
-use futures::stream::TryStreamExt;
+use crate::schema::Index;
+use futures::future;
+use futures::stream::{BoxStream, StreamExt, TryStreamExt};
 use mongodb::{
-    Client, Collection, Database,
-    bson::{Document, doc, oid::ObjectId},
+    Client, Collection, Database, IndexModel,
+    bson::{Bson, DateTime, Document, doc, oid::ObjectId},
+    change_stream::event::{ChangeStreamEvent, OperationType, ResumeToken},
+    error::ErrorKind,
+    options::{FullDocumentType, IndexOptions, ReturnDocument, ValidationAction, ValidationLevel},
 };
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 
+/// Server error code of `create` for an existing collection.
+const NAMESPACE_EXISTS: i32 = 48;
+
+/// Kind of change reported by [`MongoDriver::watch`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeOperation {
+    Insert,
+    Update,
+    Replace,
+    Delete,
+}
+
+impl ChangeOperation {
+    const ALL: [ChangeOperation; 4] = [
+        ChangeOperation::Insert,
+        ChangeOperation::Update,
+        ChangeOperation::Replace,
+        ChangeOperation::Delete,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            ChangeOperation::Insert => "insert",
+            ChangeOperation::Update => "update",
+            ChangeOperation::Replace => "replace",
+            ChangeOperation::Delete => "delete",
+        }
+    }
+}
+
+/// A change to one document of a watched collection.
+#[derive(Debug, Clone)]
+pub struct ChangeEvent<T> {
+    pub operation: ChangeOperation,
+    /// `_id` of the changed document.
+    pub id: Option<Bson>,
+    /// The document after the change: set for inserts and replaces, and for updates when
+    /// [`WatchOptions::full_document`] is enabled and the document still exists.
+    pub document: Option<T>,
+    /// Position of this event; persist it to resume after it, see [`ResumeTokenStore`].
+    pub resume_token: ResumeToken,
+}
+
+impl<T> TryFrom<ChangeStreamEvent<T>> for ChangeEvent<T> {
+    type Error = RepositoryError<mongodb::error::Error>;
+
+    fn try_from(event: ChangeStreamEvent<T>) -> Result<Self, Self::Error> {
+        let operation = match event.operation_type {
+            OperationType::Insert => ChangeOperation::Insert,
+            OperationType::Update => ChangeOperation::Update,
+            OperationType::Replace => ChangeOperation::Replace,
+            OperationType::Delete => ChangeOperation::Delete,
+            // Drops and renames invalidate the stream; it ends after this event.
+            other => {
+                return Err(RepositoryError::Other(format!(
+                    "change stream invalidated by {:?}",
+                    other
+                )));
+            }
+        };
+        Ok(ChangeEvent {
+            operation,
+            id: event.document_key.and_then(|mut key| key.remove("_id")),
+            document: event.full_document,
+            resume_token: event.id,
+        })
+    }
+}
+
+/// Stream returned by [`MongoDriver::watch`].
+pub type ChangeEvents<T> = BoxStream<'static, Result<ChangeEvent<T>, RepositoryError<mongodb::error::Error>>>;
+
+/// Options of [`MongoDriver::watch`]; by default every operation is reported, without update lookups.
+#[derive(Debug, Clone, Default)]
+pub struct WatchOptions {
+    operations: Vec<ChangeOperation>,
+    full_document: bool,
+    resume_after: Option<ResumeToken>,
+}
+
+impl WatchOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only report these operations.
+    pub fn operations(mut self, operations: impl IntoIterator<Item = ChangeOperation>) -> Self {
+        self.operations = operations.into_iter().collect();
+        self
+    }
+
+    /// Look up the current document for update events.
+    pub fn full_document(mut self, full_document: bool) -> Self {
+        self.full_document = full_document;
+        self
+    }
+
+    /// Start after the event with this token instead of at the current time.
+    pub fn resume_after(mut self, token: impl Into<Option<ResumeToken>>) -> Self {
+        self.resume_after = token.into();
+        self
+    }
+}
+
+/// Options of [`MongoDriver::apply_json_schema`]; unset options keep the server's defaults,
+/// `strict` and `error`, or the collection's current setting.
+#[derive(Debug, Clone, Default)]
+pub struct ValidatorOptions {
+    level: Option<ValidationLevel>,
+    action: Option<ValidationAction>,
+}
+
+impl ValidatorOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Which writes are validated; `Moderate` skips updates to documents that are already invalid.
+    pub fn level(mut self, level: ValidationLevel) -> Self {
+        self.level = Some(level);
+        self
+    }
+
+    /// Whether invalid writes are rejected or only logged.
+    pub fn action(mut self, action: ValidationAction) -> Self {
+        self.action = Some(action);
+        self
+    }
+}
+
+/// Where a consumer keeps the resume token of the last event it handled.
+#[async_trait::async_trait]
+pub trait ResumeTokenStore: Send + Sync {
+    async fn load(&self) -> Result<Option<ResumeToken>, RepositoryError<mongodb::error::Error>>;
+
+    async fn save(&self, token: &ResumeToken) -> Result<(), RepositoryError<mongodb::error::Error>>;
+}
+
+/// [`ResumeTokenStore`] keeping tokens in a collection, one document per consumer name.
+///
+/// ```rust,ignore
+/// let tokens = MongoResumeTokenStore::new(&driver, "resume_tokens", "search-indexer");
+/// let options = WatchOptions::new().resume_after(tokens.load().await?);
+/// let mut events = driver.watch::<User>("users", options).await?;
+/// while let Some(event) = events.try_next().await? {
+///     index(&event).await;
+///     tokens.save(&event.resume_token).await?;
+/// }
+/// ```
+pub struct MongoResumeTokenStore {
+    collection: Collection<Document>,
+    name: String,
+}
+
+impl MongoResumeTokenStore {
+    pub fn new(driver: &MongoDriver, collection: &str, name: impl Into<String>) -> Self {
+        Self {
+            collection: driver.collection(collection),
+            name: name.into(),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl ResumeTokenStore for MongoResumeTokenStore {
+    async fn load(&self) -> Result<Option<ResumeToken>, RepositoryError<mongodb::error::Error>> {
+        let stored = self
+            .collection
+            .find_one(doc! { "_id": &self.name })
+            .await
+            .map_err(RepositoryError::ConnectionError)?;
+        match stored.and_then(|mut doc| doc.remove("token")) {
+            Some(token) => mongodb::bson::from_bson(token)
+                .map(Some)
+                .map_err(|e| RepositoryError::Other(e.to_string())),
+            None => Ok(None),
+        }
+    }
+
+    async fn save(&self, token: &ResumeToken) -> Result<(), RepositoryError<mongodb::error::Error>> {
+        let token = mongodb::bson::to_bson(token).map_err(|e| RepositoryError::Other(e.to_string()))?;
+        self.collection
+            .update_one(doc! { "_id": &self.name }, doc! { "$set": { "token": token } })
+            .upsert(true)
+            .await
+            .map_err(RepositoryError::ConnectionError)?;
+        Ok(())
+    }
+}
+
 pub struct MongoDriver {
     client: Client,
     db_name: String,
@@ -25,6 +223,11 @@ impl MongoDriver {
         self.client.database(&self.db_name)
     }
 
+    /// The driver's database, for commands without a driver method.
+    pub fn database(&self) -> Database {
+        self.db()
+    }
+
     pub fn collection<T>(&self, name: &str) -> Collection<T>
     where
         T: Send + Sync + Unpin + 'static,
@@ -48,11 +251,18 @@ impl MongoDriver {
     }
 
     pub async fn find_all<T>(&self, collection: &str) -> Result<Vec<T>, RepositoryError<mongodb::error::Error>>
+    where
+        T: for<'de> Deserialize<'de> + Unpin + Send + Sync,
+    {
+        self.find_where(collection, doc! {}).await
+    }
+
+    pub async fn find_where<T>(&self, collection: &str, filter: Document) -> Result<Vec<T>, RepositoryError<mongodb::error::Error>>
     where
         T: for<'de> Deserialize<'de> + Unpin + Send + Sync,
     {
         let coll = self.db().collection::<T>(collection);
-        let mut cursor = coll.find(doc! {}).await.map_err(RepositoryError::ConnectionError)?;
+        let mut cursor = coll.find(filter).await.map_err(RepositoryError::ConnectionError)?;
         let mut results = Vec::new();
         while let Some(doc) = cursor.try_next().await.map_err(RepositoryError::ConnectionError)? {
             results.push(doc);
@@ -87,9 +297,206 @@ impl MongoDriver {
         Ok(result)
     }
 
+    /// Applies an update document (`$set`/`$unset`, ...) as-is and returns the updated document.
+    ///
+    /// Unlike [`MongoDriver::update_one`], fields not mentioned in `update` are left untouched.
+    pub async fn patch_one<T>(&self, collection: &str, filter: Document, update: Document) -> Result<Option<T>, RepositoryError<mongodb::error::Error>>
+    where
+        T: DeserializeOwned + Unpin + Send + Sync,
+    {
+        let coll = self.db().collection::<T>(collection);
+        coll.find_one_and_update(filter, update)
+            .return_document(ReturnDocument::After)
+            .await
+            .map_err(RepositoryError::ConnectionError)
+    }
+
+    /// Like [`MongoDriver::update_one`], but increments `version_key` and, when
+    /// `expected_version` is given, fails with [`RepositoryError::OptimisticLockConflict`]
+    /// if the stored document has another version.
+    pub async fn update_one_versioned<T>(&self, collection: &str, filter: Document, doc: &T, version_key: &str, expected_version: Option<i64>) -> Result<Option<T>, RepositoryError<mongodb::error::Error>>
+    where
+        T: Serialize + DeserializeOwned + Unpin + Send + Sync,
+    {
+        let mut set_doc = to_document(doc).map_err(|e| RepositoryError::Other(e.to_string()))?;
+        set_doc.remove("_id");
+        set_doc.remove(version_key);
+        self.patch_one_versioned(collection, filter, doc! { "$set": set_doc }, version_key, expected_version)
+            .await
+    }
+
+    /// Versioned counterpart of [`MongoDriver::patch_one`], see [`MongoDriver::update_one_versioned`].
+    pub async fn patch_one_versioned<T>(&self, collection: &str, filter: Document, mut update: Document, version_key: &str, expected_version: Option<i64>) -> Result<Option<T>, RepositoryError<mongodb::error::Error>>
+    where
+        T: DeserializeOwned + Unpin + Send + Sync,
+    {
+        update.insert("$inc", doc! { version_key: 1 });
+        let mut versioned_filter = filter.clone();
+        if let Some(version) = expected_version {
+            versioned_filter.insert(version_key, version);
+        }
+
+        match self.patch_one(collection, versioned_filter, update).await? {
+            Some(updated) => Ok(Some(updated)),
+            None if expected_version.is_some() => {
+                let existing = self.find_one::<Document>(collection, filter).await?;
+                match existing {
+                    Some(_) => Err(RepositoryError::OptimisticLockConflict),
+                    None => Ok(None),
+                }
+            }
+            None => Ok(None),
+        }
+    }
+
     pub async fn delete_one(&self, collection: &str, filter: Document) -> Result<u64, RepositoryError<mongodb::error::Error>> {
         let coll = self.db().collection::<Document>(collection);
         let result = coll.delete_one(filter).await.map_err(RepositoryError::ConnectionError)?;
         Ok(result.deleted_count)
     }
+
+    /// Streams changes to `collection` using a change stream (requires a replica set).
+    pub async fn watch<T>(&self, collection: &str, options: WatchOptions) -> Result<ChangeEvents<T>, RepositoryError<mongodb::error::Error>>
+    where
+        T: DeserializeOwned + Unpin + Send + Sync + 'static,
+    {
+        let operations = if options.operations.is_empty() {
+            &ChangeOperation::ALL[..]
+        } else {
+            &options.operations[..]
+        };
+        let names: Vec<&str> = operations.iter().map(ChangeOperation::name).collect();
+        let pipeline = [doc! { "$match": { "operationType": { "$in": names } } }];
+
+        let coll = self.collection::<T>(collection);
+        let mut watch = coll
+            .watch()
+            .pipeline(pipeline)
+            .resume_after(options.resume_after);
+        if options.full_document {
+            watch = watch.full_document(FullDocumentType::UpdateLookup);
+        }
+        let stream = watch.await.map_err(RepositoryError::ConnectionError)?;
+        Ok(stream
+            .map_err(RepositoryError::ConnectionError)
+            .and_then(|event| future::ready(ChangeEvent::try_from(event)))
+            .boxed())
+    }
+
+    /// Creates the indexes missing on `collection`.
+    ///
+    /// Postgres-only partial predicates are ignored; an existing index with the same name or
+    /// keys but different options is reported as an error by the server.
+    pub async fn ensure_indexes(&self, collection: &str, indexes: &[Index]) -> Result<(), RepositoryError<mongodb::error::Error>> {
+        if indexes.is_empty() {
+            return Ok(());
+        }
+        let models = indexes
+            .iter()
+            .map(index_model)
+            .collect::<Result<Vec<_>, _>>()?;
+        self.db()
+            .collection::<Document>(collection)
+            .create_indexes(models)
+            .await
+            .map_err(RepositoryError::ConnectionError)?;
+        Ok(())
+    }
+
+    /// Makes the server validate writes to `collection` against `schema`, a `$jsonSchema`
+    /// document such as the one generated by `#[derive(Entity)]`.
+    ///
+    /// Creates the collection if needed, otherwise replaces its validator with `collMod`.
+    pub async fn apply_json_schema(&self, collection: &str, schema: Document, options: ValidatorOptions) -> Result<(), RepositoryError<mongodb::error::Error>> {
+        let mut settings = doc! { "validator": { "$jsonSchema": schema } };
+        if let Some(level) = options.level {
+            settings.insert("validationLevel", mongodb::bson::to_bson(&level).map_err(|e| RepositoryError::Other(e.to_string()))?);
+        }
+        if let Some(action) = options.action {
+            settings.insert("validationAction", mongodb::bson::to_bson(&action).map_err(|e| RepositoryError::Other(e.to_string()))?);
+        }
+        let command = |verb: &str| {
+            let mut command = doc! { verb: collection };
+            command.extend(settings.clone());
+            command
+        };
+
+        let db = self.db();
+        let existing = db
+            .list_collection_names()
+            .filter(doc! { "name": collection })
+            .await
+            .map_err(RepositoryError::ConnectionError)?;
+        if existing.is_empty() {
+            match db.run_command(command("create")).await {
+                Ok(_) => return Ok(()),
+                // Created concurrently by someone else: update it instead.
+                Err(e) if matches!(&*e.kind, ErrorKind::Command(c) if c.code == NAMESPACE_EXISTS) => {}
+                Err(e) => return Err(RepositoryError::ConnectionError(e)),
+            }
+        }
+        db.run_command(command("collMod")).await.map_err(RepositoryError::ConnectionError)?;
+        Ok(())
+    }
+
+    /// Filter matching the documents that are not soft-deleted.
+    ///
+    /// A missing field counts as live, so documents written before the field existed stay visible.
+    pub fn not_deleted(soft_delete: &SoftDelete) -> Document {
+        match soft_delete {
+            SoftDelete::Timestamp(field) => doc! { field.key: null },
+            SoftDelete::Flag(field) => doc! { field.key: { "$ne": true } },
+        }
+    }
+
+    /// Marks a live document as deleted: sets the timestamp to [`audit::now`] or the flag to `true`.
+    pub async fn soft_delete_one(&self, collection: &str, mut filter: Document, soft_delete: &SoftDelete) -> Result<u64, RepositoryError<mongodb::error::Error>> {
+        filter.extend(Self::not_deleted(soft_delete));
+        let update = match soft_delete {
+            SoftDelete::Timestamp(field) => doc! { "$set": { field.key: DateTime::from_system_time(audit::now()) } },
+            SoftDelete::Flag(field) => doc! { "$set": { field.key: true } },
+        };
+        let coll = self.db().collection::<Document>(collection);
+        let result = coll.update_one(filter, update).await.map_err(RepositoryError::ConnectionError)?;
+        Ok(result.modified_count)
+    }
+
+    /// Brings a soft-deleted document back: clears the timestamp or resets the flag.
+    pub async fn restore_one(&self, collection: &str, mut filter: Document, soft_delete: &SoftDelete) -> Result<u64, RepositoryError<mongodb::error::Error>> {
+        let (deleted, update) = match soft_delete {
+            SoftDelete::Timestamp(field) => (doc! { field.key: { "$ne": null } }, doc! { "$set": { field.key: null } }),
+            SoftDelete::Flag(field) => (doc! { field.key: true }, doc! { "$set": { field.key: false } }),
+        };
+        filter.extend(deleted);
+        let coll = self.db().collection::<Document>(collection);
+        let result = coll.update_one(filter, update).await.map_err(RepositoryError::ConnectionError)?;
+        Ok(result.modified_count)
+    }
+}
+
+fn index_model(index: &Index) -> Result<IndexModel, RepositoryError<mongodb::error::Error>> {
+    let mut keys = Document::new();
+    for field in index.fields {
+        if index.text {
+            keys.insert(field.key, "text");
+        } else {
+            keys.insert(field.key, 1);
+        }
+    }
+    let partial = match index.partial {
+        Some(json) => {
+            let invalid = |e: String| RepositoryError::Other(format!("invalid partial index filter {}: {}", json, e));
+            let value: serde_json::Map<String, serde_json::Value> = serde_json::from_str(json).map_err(|e| invalid(e.to_string()))?;
+            Some(Document::try_from(value).map_err(|e| invalid(e.to_string()))?)
+        }
+        None => None,
+    };
+    let options = IndexOptions::builder()
+        .name(index.name.map(str::to_string))
+        .unique(index.unique.then_some(true))
+        .sparse(index.sparse.then_some(true))
+        .expire_after(index.ttl.map(Duration::from_secs))
+        .partial_filter_expression(partial)
+        .build();
+    Ok(IndexModel::builder().keys(keys).options(options).build())
 }
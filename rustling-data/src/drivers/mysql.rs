@@ -0,0 +1,412 @@
+use crate::api::{RepositoryError, SoftDelete};
+use crate::audit;
+use sqlx::error::BoxDynError;
+use sqlx::mysql::{MySqlArguments, MySqlRow};
+use sqlx::{Arguments, Encode, Executor, FromRow, MySql, Type};
+
+/// Ordered values bound to the `?` placeholders of a statement; see [`crate::PgValues`].
+#[derive(Default)]
+pub struct MySqlValues {
+    args: MySqlArguments,
+    error: Option<BoxDynError>,
+}
+
+impl MySqlValues {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push<'q, T>(&mut self, value: T) -> &mut Self
+    where
+        T: Encode<'q, MySql> + Type<MySql> + 'q,
+    {
+        if let Err(e) = self.args.add(value) {
+            self.error.get_or_insert(e);
+        }
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.args.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn into_arguments(self) -> Result<MySqlArguments, RepositoryError<sqlx::Error>> {
+        match self.error {
+            Some(e) => Err(RepositoryError::Other(e.to_string())),
+            None => Ok(self.args),
+        }
+    }
+}
+
+impl<'q, T> From<Vec<&'q T>> for MySqlValues
+where
+    T: Encode<'q, MySql> + Type<MySql>,
+{
+    fn from(values: Vec<&'q T>) -> Self {
+        let mut mysql_values = MySqlValues::new();
+        for v in values {
+            mysql_values.push(v);
+        }
+        mysql_values
+    }
+}
+
+/// MySQL and MariaDB counterpart of [`crate::PostgresDriver`].
+///
+/// Table and column names are quoted with backticks, so reserved words such as `order` or
+/// `key` can be used; a `schema.table` name is quoted part by part.
+pub struct MySqlDriver;
+
+impl MySqlDriver {
+    pub async fn find_all<'e, T, E>(
+        executor: E,
+        table: &str,
+        columns: &[&str],
+    ) -> Result<Vec<T>, RepositoryError<sqlx::Error>>
+    where
+        E: Executor<'e, Database = MySql>,
+        T: for<'r> FromRow<'r, MySqlRow> + Send + Unpin,
+    {
+        let query = format!("SELECT {} FROM {}", quote_all(columns), quote(table));
+        sqlx::query_as::<_, T>(&query)
+            .fetch_all(executor)
+            .await
+            .map_err(RepositoryError::ConnectionError)
+    }
+
+    /// Selects the rows matching `filter`, a SQL condition whose `?` placeholders are
+    /// bound to `values`.
+    pub async fn find_where<'e, T, E>(
+        executor: E,
+        table: &str,
+        columns: &[&str],
+        filter: &str,
+        values: impl Into<MySqlValues>,
+    ) -> Result<Vec<T>, RepositoryError<sqlx::Error>>
+    where
+        E: Executor<'e, Database = MySql>,
+        T: for<'r> FromRow<'r, MySqlRow> + Send + Unpin,
+    {
+        let query = format!(
+            "SELECT {} FROM {} WHERE {}",
+            quote_all(columns),
+            quote(table),
+            filter
+        );
+        let args = values.into().into_arguments()?;
+        sqlx::query_as_with::<_, T, _>(&query, args)
+            .fetch_all(executor)
+            .await
+            .map_err(RepositoryError::ConnectionError)
+    }
+
+    /// Inserts a row and returns its `AUTO_INCREMENT` id, i.e. `LAST_INSERT_ID()`.
+    pub async fn insert<'e, E>(
+        executor: E,
+        table: &str,
+        columns: &[&str],
+        values: impl Into<MySqlValues>,
+    ) -> Result<i32, RepositoryError<sqlx::Error>>
+    where
+        E: Executor<'e, Database = MySql>,
+    {
+        let query_string = format!(
+            "INSERT INTO {} ({}) VALUES ({})",
+            quote(table),
+            quote_all(columns),
+            placeholders(columns.len())
+        );
+
+        let args = values.into().into_arguments()?;
+        let result = sqlx::query_with(&query_string, args)
+            .execute(executor)
+            .await
+            .map_err(map_write_error)?;
+        i32::try_from(result.last_insert_id()).map_err(|e| RepositoryError::Other(e.to_string()))
+    }
+
+    /// Inserts a row or, when it collides with a primary or unique key, updates
+    /// `update_columns` of the existing row to the new values (`ON DUPLICATE KEY UPDATE`).
+    ///
+    /// Returns the affected rows as MySQL counts them: 2 when an existing row was changed, and 1
+    /// both for an insert and for a collision that left the row as it was, since sqlx connects
+    /// with `CLIENT_FOUND_ROWS`. The count therefore cannot tell an insert from a no-op update.
+    pub async fn upsert<'e, E>(
+        executor: E,
+        table: &str,
+        columns: &[&str],
+        values: impl Into<MySqlValues>,
+        update_columns: &[&str],
+    ) -> Result<u64, RepositoryError<sqlx::Error>>
+    where
+        E: Executor<'e, Database = MySql>,
+    {
+        // `VALUES(col)` rather than a row alias, which MariaDB does not support.
+        let assignments: Vec<String> = update_columns
+            .iter()
+            .map(|c| format!("{0} = VALUES({0})", quote(c)))
+            .collect();
+        let query_string = format!(
+            "INSERT INTO {} ({}) VALUES ({}) ON DUPLICATE KEY UPDATE {}",
+            quote(table),
+            quote_all(columns),
+            placeholders(columns.len()),
+            assignments.join(", ")
+        );
+
+        let args = values.into().into_arguments()?;
+        sqlx::query_with(&query_string, args)
+            .execute(executor)
+            .await
+            .map_err(map_write_error)
+            .map(|res| res.rows_affected())
+    }
+
+    pub async fn find_one<'e, T, E>(
+        executor: E,
+        table: &str,
+        columns: &[&str],
+        id_column: &str,
+        id_value: i32,
+    ) -> Result<Option<T>, RepositoryError<sqlx::Error>>
+    where
+        E: Executor<'e, Database = MySql>,
+        T: for<'r> FromRow<'r, MySqlRow> + Send + Unpin,
+    {
+        let query = format!(
+            "SELECT {} FROM {} WHERE {} = ?",
+            quote_all(columns),
+            quote(table),
+            quote(id_column)
+        );
+        sqlx::query_as::<_, T>(&query)
+            .bind(id_value)
+            .fetch_optional(executor)
+            .await
+            .map_err(RepositoryError::ConnectionError)
+    }
+
+    /// Sets `columns` on the row with `id_value`; given `soft_delete`, only while it is not
+    /// soft-deleted.
+    pub async fn update<'e, E>(
+        executor: E,
+        table: &str,
+        id_column: &str,
+        id_value: i32,
+        columns: &[&str],
+        values: impl Into<MySqlValues>,
+        soft_delete: Option<&SoftDelete>,
+    ) -> Result<u64, RepositoryError<sqlx::Error>>
+    where
+        E: Executor<'e, Database = MySql>,
+    {
+        let mut query_string = format!(
+            "UPDATE {} SET {} WHERE {} = ?",
+            quote(table),
+            assignments(columns).join(", "),
+            quote(id_column)
+        );
+
+        if let Some(soft_delete) = soft_delete {
+            query_string.push_str(&format!(" AND {}", Self::not_deleted(soft_delete)));
+        }
+
+        let mut values = values.into();
+        values.push(id_value);
+        let args = values.into_arguments()?;
+
+        sqlx::query_with(&query_string, args)
+            .execute(executor)
+            .await
+            .map_err(map_write_error)
+            .map(|res| res.rows_affected())
+    }
+
+    /// Like [`MySqlDriver::update`], but also increments `version_column` and, when
+    /// `expected_version` is given, only matches the row while it still has that version.
+    ///
+    /// Zero affected rows then means either a missing row or a concurrent modification.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn update_versioned<'e, E>(
+        executor: E,
+        table: &str,
+        id_column: &str,
+        id_value: i32,
+        columns: &[&str],
+        values: impl Into<MySqlValues>,
+        version_column: &str,
+        expected_version: Option<i64>,
+        soft_delete: Option<&SoftDelete>,
+    ) -> Result<u64, RepositoryError<sqlx::Error>>
+    where
+        E: Executor<'e, Database = MySql>,
+    {
+        let mut set_clause = assignments(columns);
+        set_clause.push(format!("{0} = {0} + 1", quote(version_column)));
+
+        let mut query_string = format!(
+            "UPDATE {} SET {} WHERE {} = ?",
+            quote(table),
+            set_clause.join(", "),
+            quote(id_column)
+        );
+
+        let mut values = values.into();
+        values.push(id_value);
+        if let Some(version) = expected_version {
+            query_string.push_str(&format!(" AND {} = ?", quote(version_column)));
+            values.push(version);
+        }
+        if let Some(soft_delete) = soft_delete {
+            query_string.push_str(&format!(" AND {}", Self::not_deleted(soft_delete)));
+        }
+        let args = values.into_arguments()?;
+
+        sqlx::query_with(&query_string, args)
+            .execute(executor)
+            .await
+            .map_err(map_write_error)
+            .map(|res| res.rows_affected())
+    }
+
+    pub async fn delete<'e, E>(
+        executor: E,
+        table: &str,
+        id_column: &str,
+        id_value: i32,
+    ) -> Result<u64, RepositoryError<sqlx::Error>>
+    where
+        E: Executor<'e, Database = MySql>,
+    {
+        let query = format!(
+            "DELETE FROM {} WHERE {} = ?",
+            quote(table),
+            quote(id_column)
+        );
+        let result = sqlx::query(&query)
+            .bind(id_value)
+            .execute(executor)
+            .await
+            .map_err(RepositoryError::ConnectionError)?;
+        Ok(result.rows_affected())
+    }
+
+    /// SQL condition matching the rows that are not soft-deleted.
+    pub fn not_deleted(soft_delete: &SoftDelete) -> String {
+        match soft_delete {
+            SoftDelete::Timestamp(field) => format!("{} IS NULL", quote(field.column)),
+            SoftDelete::Flag(field) => format!("{} IS NOT TRUE", quote(field.column)),
+        }
+    }
+
+    /// Marks a live row as deleted: sets the timestamp to [`audit::now`] or the flag to `TRUE`.
+    pub async fn soft_delete<'e, E>(
+        executor: E,
+        table: &str,
+        id_column: &str,
+        id_value: i32,
+        soft_delete: &SoftDelete,
+    ) -> Result<u64, RepositoryError<sqlx::Error>>
+    where
+        E: Executor<'e, Database = MySql>,
+    {
+        let set_clause = match soft_delete {
+            SoftDelete::Timestamp(field) => format!("{} = FROM_UNIXTIME(?)", quote(field.column)),
+            SoftDelete::Flag(field) => format!("{} = TRUE", quote(field.column)),
+        };
+        let query = format!(
+            "UPDATE {} SET {} WHERE {} = ? AND {}",
+            quote(table),
+            set_clause,
+            quote(id_column),
+            Self::not_deleted(soft_delete)
+        );
+        let mut query = sqlx::query(&query);
+        if let SoftDelete::Timestamp(_) = soft_delete {
+            query = query.bind(audit::unix_seconds());
+        }
+        let result = query
+            .bind(id_value)
+            .execute(executor)
+            .await
+            .map_err(RepositoryError::ConnectionError)?;
+        Ok(result.rows_affected())
+    }
+
+    /// Brings a soft-deleted row back: clears the timestamp or resets the flag.
+    pub async fn restore<'e, E>(
+        executor: E,
+        table: &str,
+        id_column: &str,
+        id_value: i32,
+        soft_delete: &SoftDelete,
+    ) -> Result<u64, RepositoryError<sqlx::Error>>
+    where
+        E: Executor<'e, Database = MySql>,
+    {
+        let (set_clause, deleted) = match soft_delete {
+            SoftDelete::Timestamp(field) => (
+                format!("{} = NULL", quote(field.column)),
+                format!("{} IS NOT NULL", quote(field.column)),
+            ),
+            SoftDelete::Flag(field) => (
+                format!("{} = FALSE", quote(field.column)),
+                format!("{} IS TRUE", quote(field.column)),
+            ),
+        };
+        let query = format!(
+            "UPDATE {} SET {} WHERE {} = ? AND {}",
+            quote(table),
+            set_clause,
+            quote(id_column),
+            deleted
+        );
+        let result = sqlx::query(&query)
+            .bind(id_value)
+            .execute(executor)
+            .await
+            .map_err(RepositoryError::ConnectionError)?;
+        Ok(result.rows_affected())
+    }
+}
+
+/// Backtick-quotes an identifier, part by part for `schema.table`.
+pub fn quote(identifier: &str) -> String {
+    identifier
+        .split('.')
+        .map(|part| format!("`{}`", part.replace('`', "``")))
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+fn quote_all(columns: &[&str]) -> String {
+    columns
+        .iter()
+        .map(|c| quote(c))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn placeholders(count: usize) -> String {
+    vec!["?"; count].join(", ")
+}
+
+fn assignments(columns: &[&str]) -> Vec<String> {
+    columns
+        .iter()
+        .map(|c| format!("{} = ?", quote(c)))
+        .collect()
+}
+
+fn map_write_error(e: sqlx::Error) -> RepositoryError<sqlx::Error> {
+    match e {
+        sqlx::Error::Database(db_err) => {
+            RepositoryError::ConstraintViolation(db_err.message().to_string())
+        }
+        other => RepositoryError::ConnectionError(other),
+    }
+}
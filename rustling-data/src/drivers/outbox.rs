@@ -0,0 +1,288 @@
+use std::time::Duration;
+
+use crate::api::RepositoryError;
+use serde::Serialize;
+use sqlx::types::{Json, JsonValue};
+use sqlx::{Executor, FromRow, PgConnection, PgPool, Postgres};
+
+/// Event waiting in the outbox table to be published.
+#[derive(Debug, Clone, FromRow)]
+pub struct OutboxMessage {
+    pub id: i64,
+    pub topic: String,
+    pub payload: JsonValue,
+    /// Failed publish attempts so far.
+    pub attempts: i32,
+}
+
+/// Outbox table holding events written in the same transaction as the entity change.
+///
+/// ```rust,ignore
+/// let mut tx = pool.begin().await?;
+/// let id = PostgresDriver::insert(&mut *tx, "users", User::columns(), user.values()).await?;
+/// outbox.enqueue(&mut *tx, "user.created", &UserCreated { id }).await?;
+/// tx.commit().await?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct Outbox {
+    table: String,
+}
+
+impl Outbox {
+    pub fn new(table: impl Into<String>) -> Self {
+        Self {
+            table: table.into(),
+        }
+    }
+
+    pub fn table(&self) -> &str {
+        &self.table
+    }
+
+    /// Creates the outbox table if it does not exist yet.
+    pub async fn create_table<'e, E>(&self, executor: E) -> Result<(), RepositoryError<sqlx::Error>>
+    where
+        E: Executor<'e, Database = Postgres>,
+    {
+        let query = format!(
+            "CREATE TABLE IF NOT EXISTS {} (\
+             id BIGSERIAL PRIMARY KEY, \
+             topic TEXT NOT NULL, \
+             payload JSONB NOT NULL, \
+             created_at TIMESTAMPTZ NOT NULL DEFAULT now(), \
+             attempts INT NOT NULL DEFAULT 0, \
+             last_error TEXT, \
+             status TEXT NOT NULL DEFAULT 'pending', \
+             next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT now())",
+            self.table
+        );
+        sqlx::query(&query)
+            .execute(executor)
+            .await
+            .map_err(RepositoryError::ConnectionError)?;
+        Ok(())
+    }
+
+    /// Adds an event; pass the transaction of the entity change as `executor`.
+    pub async fn enqueue<'e, E, P>(
+        &self,
+        executor: E,
+        topic: &str,
+        payload: &P,
+    ) -> Result<i64, RepositoryError<sqlx::Error>>
+    where
+        E: Executor<'e, Database = Postgres>,
+        P: Serialize + Sync,
+    {
+        let query = format!(
+            "INSERT INTO {} (topic, payload) VALUES ($1, $2) RETURNING id",
+            self.table
+        );
+        sqlx::query_scalar(&query)
+            .bind(topic)
+            .bind(Json(payload))
+            .fetch_one(executor)
+            .await
+            .map_err(RepositoryError::ConnectionError)
+    }
+
+    /// Oldest pending messages due for an attempt, locked `FOR UPDATE SKIP LOCKED` so concurrent
+    /// relays claim different batches. `connection` must be inside a transaction.
+    pub async fn claim(
+        &self,
+        connection: &mut PgConnection,
+        limit: i64,
+    ) -> Result<Vec<OutboxMessage>, RepositoryError<sqlx::Error>> {
+        let query = format!(
+            "SELECT id, topic, payload, attempts FROM {} \
+             WHERE status = 'pending' AND next_attempt_at <= now() \
+             ORDER BY id LIMIT $1 FOR UPDATE SKIP LOCKED",
+            self.table
+        );
+        sqlx::query_as(&query)
+            .bind(limit)
+            .fetch_all(connection)
+            .await
+            .map_err(RepositoryError::ConnectionError)
+    }
+
+    /// Removes published messages.
+    pub async fn complete<'e, E>(
+        &self,
+        executor: E,
+        ids: &[i64],
+    ) -> Result<u64, RepositoryError<sqlx::Error>>
+    where
+        E: Executor<'e, Database = Postgres>,
+    {
+        let query = format!("DELETE FROM {} WHERE id = ANY($1)", self.table);
+        sqlx::query(&query)
+            .bind(ids)
+            .execute(executor)
+            .await
+            .map(|r| r.rows_affected())
+            .map_err(RepositoryError::ConnectionError)
+    }
+
+    /// Records a failed publish attempt. The message stays in the outbox and is claimed again
+    /// after `retry_in`; with `None` it is marked `failed` and no longer claimed.
+    pub async fn fail<'e, E>(
+        &self,
+        executor: E,
+        id: i64,
+        error: &str,
+        retry_in: Option<Duration>,
+    ) -> Result<(), RepositoryError<sqlx::Error>>
+    where
+        E: Executor<'e, Database = Postgres>,
+    {
+        let query = format!(
+            "UPDATE {} SET attempts = attempts + 1, last_error = $2, \
+             status = CASE WHEN $3::float8 IS NULL THEN 'failed' ELSE status END, \
+             next_attempt_at = now() + make_interval(secs => COALESCE($3::float8, 0)) \
+             WHERE id = $1",
+            self.table
+        );
+        sqlx::query(&query)
+            .bind(id)
+            .bind(error)
+            .bind(retry_in.map(|delay| delay.as_secs_f64()))
+            .execute(executor)
+            .await
+            .map_err(RepositoryError::ConnectionError)?;
+        Ok(())
+    }
+}
+
+impl Default for Outbox {
+    fn default() -> Self {
+        Self::new("rustling_outbox")
+    }
+}
+
+/// Destination the [`OutboxRelay`] hands messages to, e.g. a message broker.
+#[async_trait::async_trait]
+pub trait OutboxPublisher: Send + Sync {
+    async fn publish(&self, message: &OutboxMessage) -> Result<(), String>;
+}
+
+/// Worker moving messages from the outbox to an [`OutboxPublisher`].
+///
+/// Delivery is at least once: a message is removed only after `publish` succeeds, so a crash
+/// between the two publishes it again and consumers should deduplicate by [`OutboxMessage::id`].
+/// A failed message is retried with exponential backoff until it reaches `max_attempts`, then
+/// marked `failed`; it does not hold up the rest of its batch. Messages are claimed in id order,
+/// but retries and concurrent relays mean they are not necessarily published in that order.
+pub struct OutboxRelay<P> {
+    pool: PgPool,
+    outbox: Outbox,
+    publisher: P,
+    batch_size: i64,
+    poll_interval: Duration,
+    max_attempts: i32,
+    retry_backoff: Duration,
+    max_backoff: Duration,
+}
+
+impl<P: OutboxPublisher> OutboxRelay<P> {
+    pub fn new(pool: PgPool, outbox: Outbox, publisher: P) -> Self {
+        Self {
+            pool,
+            outbox,
+            publisher,
+            batch_size: 100,
+            poll_interval: Duration::from_secs(1),
+            max_attempts: 10,
+            retry_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(300),
+        }
+    }
+
+    /// Maximum number of messages claimed per transaction.
+    pub fn batch_size(mut self, batch_size: i64) -> Self {
+        self.batch_size = batch_size;
+        self
+    }
+
+    /// Pause between polls once the outbox is empty.
+    pub fn poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    /// Publish attempts after which a message is marked `failed`.
+    pub fn max_attempts(mut self, max_attempts: i32) -> Self {
+        self.max_attempts = max_attempts;
+        self
+    }
+
+    /// First retry delay, doubled after every further failure up to `max_backoff`.
+    pub fn retry_backoff(mut self, retry_backoff: Duration) -> Self {
+        self.retry_backoff = retry_backoff;
+        self
+    }
+
+    /// Upper bound for retry delays.
+    pub fn max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    fn backoff(&self, failures: u32) -> Duration {
+        self.retry_backoff
+            .saturating_mul(2_u32.saturating_pow(failures))
+            .min(self.max_backoff)
+    }
+
+    /// Publishes one batch; returns the number of published messages.
+    pub async fn relay_once(&self) -> Result<usize, RepositoryError<sqlx::Error>> {
+        let mut tx = self
+            .pool
+            .begin()
+            .await
+            .map_err(RepositoryError::ConnectionError)?;
+        let messages = self.outbox.claim(&mut tx, self.batch_size).await?;
+
+        let mut published = Vec::with_capacity(messages.len());
+        for message in &messages {
+            match self.publisher.publish(message).await {
+                Ok(()) => published.push(message.id),
+                Err(e) => {
+                    let attempts = message.attempts + 1;
+                    let retry_in = (attempts < self.max_attempts)
+                        .then(|| self.backoff(message.attempts.max(0) as u32));
+                    self.outbox.fail(&mut *tx, message.id, &e, retry_in).await?;
+                }
+            }
+        }
+        if !published.is_empty() {
+            self.outbox.complete(&mut *tx, &published).await?;
+        }
+
+        tx.commit()
+            .await
+            .map_err(RepositoryError::ConnectionError)?;
+        Ok(published.len())
+    }
+
+    /// Relays messages forever, sleeping `poll_interval` whenever a poll finds nothing.
+    ///
+    /// Database errors are logged and retried with the same backoff as failed messages.
+    pub async fn run(&self) {
+        let mut errors = 0;
+        loop {
+            match self.relay_once().await {
+                Ok(0) => {
+                    errors = 0;
+                    tokio::time::sleep(self.poll_interval).await;
+                }
+                Ok(_) => errors = 0,
+                Err(e) => {
+                    log::warn!("outbox relay for {} failed: {}", self.outbox.table(), e);
+                    tokio::time::sleep(self.backoff(errors)).await;
+                    errors = errors.saturating_add(1);
+                }
+            }
+        }
+    }
+}
@@ -1,29 +1,213 @@
-use crate::api::RepositoryError;
-use sqlx::{Encode, Executor, FromRow, Postgres, Row, Type, postgres::PgRow};
+use crate::api::{RepositoryError, SoftDelete};
+use crate::audit;
+use crate::schema::{DbColumn, Index, TableSchema};
+use futures::future;
+use futures::stream::{BoxStream, StreamExt, TryStreamExt};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use sqlx::error::BoxDynError;
+use sqlx::postgres::{PgArguments, PgListener, PgRow};
+use sqlx::{Acquire, Arguments, Encode, Executor, FromRow, PgPool, Postgres, Row, Type};
+
+/// Ordered values bound to the `$n` placeholders of a statement.
+///
+/// Unlike a `Vec<&T>`, values of different types can be mixed, which is what entity
+/// columns usually need. Encoding errors are kept and reported when the statement runs.
+#[derive(Default)]
+pub struct PgValues {
+    args: PgArguments,
+    error: Option<BoxDynError>,
+}
+
+impl PgValues {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push<'q, T>(&mut self, value: T) -> &mut Self
+    where
+        T: Encode<'q, Postgres> + Type<Postgres> + 'q,
+    {
+        if let Err(e) = self.args.add(value) {
+            self.error.get_or_insert(e);
+        }
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.args.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn into_arguments(self) -> Result<PgArguments, RepositoryError<sqlx::Error>> {
+        match self.error {
+            Some(e) => Err(RepositoryError::Other(e.to_string())),
+            None => Ok(self.args),
+        }
+    }
+}
+
+impl<'q, T> From<Vec<&'q T>> for PgValues
+where
+    T: Encode<'q, Postgres> + Type<Postgres>,
+{
+    fn from(values: Vec<&'q T>) -> Self {
+        let mut pg_values = PgValues::new();
+        for v in values {
+            pg_values.push(v);
+        }
+        pg_values
+    }
+}
+
+/// Row lock taken by the `*_for_update` finders; only meaningful inside a transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LockMode {
+    strength: LockStrength,
+    wait: LockWait,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LockStrength {
+    Update,
+    Share,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LockWait {
+    Block,
+    NoWait,
+    SkipLocked,
+}
+
+impl LockMode {
+    /// `FOR UPDATE`: exclusive lock, blocks other writers and lockers.
+    pub const fn for_update() -> Self {
+        Self {
+            strength: LockStrength::Update,
+            wait: LockWait::Block,
+        }
+    }
+
+    /// `FOR SHARE`: shared lock, blocks writers but not other `FOR SHARE` readers.
+    pub const fn for_share() -> Self {
+        Self {
+            strength: LockStrength::Share,
+            wait: LockWait::Block,
+        }
+    }
+
+    /// `NOWAIT`: fail immediately instead of waiting for a locked row.
+    pub const fn nowait(self) -> Self {
+        Self {
+            wait: LockWait::NoWait,
+            ..self
+        }
+    }
+
+    /// `SKIP LOCKED`: leave out rows that are already locked.
+    pub const fn skip_locked(self) -> Self {
+        Self {
+            wait: LockWait::SkipLocked,
+            ..self
+        }
+    }
+
+    fn sql(&self) -> &'static str {
+        match (self.strength, self.wait) {
+            (LockStrength::Update, LockWait::Block) => "FOR UPDATE",
+            (LockStrength::Update, LockWait::NoWait) => "FOR UPDATE NOWAIT",
+            (LockStrength::Update, LockWait::SkipLocked) => "FOR UPDATE SKIP LOCKED",
+            (LockStrength::Share, LockWait::Block) => "FOR SHARE",
+            (LockStrength::Share, LockWait::NoWait) => "FOR SHARE NOWAIT",
+            (LockStrength::Share, LockWait::SkipLocked) => "FOR SHARE SKIP LOCKED",
+        }
+    }
+}
+
+impl Default for LockMode {
+    fn default() -> Self {
+        Self::for_update()
+    }
+}
+
+/// Notification received on a channel by [`PostgresDriver::listen`].
+#[derive(Debug, Clone)]
+pub struct Notification<T> {
+    pub channel: String,
+    pub payload: T,
+    /// Backend process that sent the notification.
+    pub process_id: u32,
+}
+
+/// Stream returned by [`PostgresDriver::listen`].
+pub type Notifications<T> = BoxStream<'static, Result<Notification<T>, RepositoryError<sqlx::Error>>>;
+
+/// Kind of row change reported by a trigger from [`PostgresDriver::create_notify_trigger`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum RowOperation {
+    Insert,
+    Update,
+    Delete,
+}
+
+/// Payload sent by a trigger from [`PostgresDriver::create_notify_trigger`].
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+pub struct RowChange<ID> {
+    pub operation: RowOperation,
+    pub table: String,
+    pub id: ID,
+}
 
 pub struct PostgresDriver;
 
 impl PostgresDriver {
     pub async fn find_all<'e, T, E>(
         executor: E,
-        table: &str
+        table: &str,
+        columns: &[&str],
     ) -> Result<Vec<T>, RepositoryError<sqlx::Error>>
     where
         E: Executor<'e, Database = Postgres>,
         T: for<'r> FromRow<'r, PgRow> + Send + Unpin,
     {
-        let query = format!("SELECT * FROM {}", table);
+        let query = format!("SELECT {} FROM {}", columns.join(", "), table);
         sqlx::query_as::<_, T>(&query)
             .fetch_all(executor)
             .await
             .map_err(RepositoryError::ConnectionError)
     }
 
+    /// Selects the rows matching `filter`, a SQL condition whose `$n` placeholders are
+    /// bound to `values`.
+    pub async fn find_where<'e, T, E>(
+        executor: E,
+        table: &str,
+        columns: &[&str],
+        filter: &str,
+        values: impl Into<PgValues>,
+    ) -> Result<Vec<T>, RepositoryError<sqlx::Error>>
+    where
+        E: Executor<'e, Database = Postgres>,
+        T: for<'r> FromRow<'r, PgRow> + Send + Unpin,
+    {
+        let query = format!("SELECT {} FROM {} WHERE {}", columns.join(", "), table, filter);
+        let args = values.into().into_arguments()?;
+        sqlx::query_as_with::<_, T, _>(&query, args)
+            .fetch_all(executor)
+            .await
+            .map_err(RepositoryError::ConnectionError)
+    }
+
     pub async fn insert<'e, E>(
         executor: E,
         table: &str,
         columns: &[&str],
-        values: Vec<&'e (impl Encode<'e, Postgres> + Type<Postgres>)>,
+        values: impl Into<PgValues>,
     ) -> Result<i32, RepositoryError<sqlx::Error>>
     where
         E: Executor<'e, Database = Postgres>,
@@ -36,14 +220,9 @@ impl PostgresDriver {
             placeholders.join(", ")
         );
 
-        let box_str = query_string.into_boxed_str();
-        let static_str: &'static str = Box::leak(box_str);
-        let mut q = sqlx::query(static_str);
-        for v in values {
-            q = q.bind(v);
-        }
-
-        q.fetch_one(executor)
+        let args = values.into().into_arguments()?;
+        sqlx::query_with(&query_string, args)
+            .fetch_one(executor)
             .await
             .map_err(|e| match e {
                 sqlx::Error::Database(db_err) => {
@@ -60,6 +239,33 @@ impl PostgresDriver {
     pub async fn find_one<'e, T, E>(
         executor: E,
         table: &str,
+        columns: &[&str],
+        id_column: &str,
+        id_value: i32,
+    ) -> Result<Option<T>, RepositoryError<sqlx::Error>>
+    where
+        E: Executor<'e, Database = Postgres>,
+        T: for<'r> FromRow<'r, PgRow> + Send + Unpin,
+    {
+        let query = format!(
+            "SELECT {} FROM {} WHERE {} = $1",
+            columns.join(", "),
+            table,
+            id_column
+        );
+        sqlx::query_as::<_, T>(&query)
+            .bind(id_value)
+            .fetch_optional(executor)
+            .await
+            .map_err(RepositoryError::ConnectionError)
+    }
+
+    /// Like [`PostgresDriver::find_one`], but locks the row `FOR UPDATE` until the
+    /// surrounding transaction ends.
+    pub async fn find_one_for_update<'e, T, E>(
+        executor: E,
+        table: &str,
+        columns: &[&str],
         id_column: &str,
         id_value: i32,
     ) -> Result<Option<T>, RepositoryError<sqlx::Error>>
@@ -67,7 +273,13 @@ impl PostgresDriver {
         E: Executor<'e, Database = Postgres>,
         T: for<'r> FromRow<'r, PgRow> + Send + Unpin,
     {
-        let query = format!("SELECT * FROM {} WHERE {} = $1", table, id_column);
+        let query = format!(
+            "SELECT {} FROM {} WHERE {} = $1 {}",
+            columns.join(", "),
+            table,
+            id_column,
+            LockMode::for_update().sql()
+        );
         sqlx::query_as::<_, T>(&query)
             .bind(id_value)
             .fetch_optional(executor)
@@ -75,13 +287,44 @@ impl PostgresDriver {
             .map_err(RepositoryError::ConnectionError)
     }
 
+    /// Selects and locks the rows matching `filter`, a SQL condition whose `$n`
+    /// placeholders are bound to `values`.
+    pub async fn find_where_for_update<'e, T, E>(
+        executor: E,
+        table: &str,
+        columns: &[&str],
+        filter: &str,
+        values: impl Into<PgValues>,
+        lock: LockMode,
+    ) -> Result<Vec<T>, RepositoryError<sqlx::Error>>
+    where
+        E: Executor<'e, Database = Postgres>,
+        T: for<'r> FromRow<'r, PgRow> + Send + Unpin,
+    {
+        let query = format!(
+            "SELECT {} FROM {} WHERE {} {}",
+            columns.join(", "),
+            table,
+            filter,
+            lock.sql()
+        );
+        let args = values.into().into_arguments()?;
+        sqlx::query_as_with::<_, T, _>(&query, args)
+            .fetch_all(executor)
+            .await
+            .map_err(RepositoryError::ConnectionError)
+    }
+
+    /// Sets `columns` on the row with `id_value`; given `soft_delete`, only while it is not
+    /// soft-deleted.
     pub async fn update<'e, E>(
         executor: E,
         table: &str,
         id_column: &str,
         id_value: i32,
         columns: &[&str],
-        values: Vec<&'e (impl Encode<'e, Postgres> + Type<Postgres>)>,
+        values: impl Into<PgValues>,
+        soft_delete: Option<&SoftDelete>,
     ) -> Result<u64, RepositoryError<sqlx::Error>>
     where
         E: Executor<'e, Database = Postgres>,
@@ -93,7 +336,7 @@ impl PostgresDriver {
             .collect();
         let id_placeholder = columns.len() + 1;
 
-        let query_string = format!(
+        let mut query_string = format!(
             "UPDATE {} SET {} WHERE {} = ${}",
             table,
             set_clause.join(", "),
@@ -101,15 +344,73 @@ impl PostgresDriver {
             id_placeholder
         );
 
-        let box_str = query_string.into_boxed_str();
-        let static_str: &'static str = Box::leak(box_str);
-        let mut q = sqlx::query(static_str);
-        for v in values {
-            q = q.bind(v);
+        if let Some(soft_delete) = soft_delete {
+            query_string.push_str(&format!(" AND {}", Self::not_deleted(soft_delete)));
         }
-        q = q.bind(id_value);
 
-        q.execute(executor)
+        let mut values = values.into();
+        values.push(id_value);
+        let args = values.into_arguments()?;
+
+        sqlx::query_with(&query_string, args)
+            .execute(executor)
+            .await
+            .map_err(|e| match e {
+                sqlx::Error::Database(db_err) => {
+                    RepositoryError::ConstraintViolation(db_err.message().to_string())
+                }
+                other => RepositoryError::ConnectionError(other),
+            })
+            .map(|res| res.rows_affected())
+    }
+
+    /// Like [`PostgresDriver::update`], but also increments `version_column` and, when
+    /// `expected_version` is given, only matches the row while it still has that version.
+    ///
+    /// Zero affected rows then means either a missing row or a concurrent modification.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn update_versioned<'e, E>(
+        executor: E,
+        table: &str,
+        id_column: &str,
+        id_value: i32,
+        columns: &[&str],
+        values: impl Into<PgValues>,
+        version_column: &str,
+        expected_version: Option<i64>,
+        soft_delete: Option<&SoftDelete>,
+    ) -> Result<u64, RepositoryError<sqlx::Error>>
+    where
+        E: Executor<'e, Database = Postgres>,
+    {
+        let mut set_clause: Vec<String> = columns
+            .iter()
+            .enumerate()
+            .map(|(i, c)| format!("{} = ${}", c, i + 1))
+            .collect();
+        set_clause.push(format!("{0} = {0} + 1", version_column));
+
+        let mut query_string = format!(
+            "UPDATE {} SET {} WHERE {} = ${}",
+            table,
+            set_clause.join(", "),
+            id_column,
+            columns.len() + 1
+        );
+
+        let mut values = values.into();
+        values.push(id_value);
+        if let Some(version) = expected_version {
+            query_string.push_str(&format!(" AND {} = ${}", version_column, columns.len() + 2));
+            values.push(version);
+        }
+        if let Some(soft_delete) = soft_delete {
+            query_string.push_str(&format!(" AND {}", Self::not_deleted(soft_delete)));
+        }
+        let args = values.into_arguments()?;
+
+        sqlx::query_with(&query_string, args)
+            .execute(executor)
             .await
             .map_err(|e| match e {
                 sqlx::Error::Database(db_err) => {
@@ -137,4 +438,317 @@ impl PostgresDriver {
             .map_err(RepositoryError::ConnectionError)?;
         Ok(result.rows_affected())
     }
+
+    /// SQL condition matching the rows that are not soft-deleted.
+    pub fn not_deleted(soft_delete: &SoftDelete) -> String {
+        match soft_delete {
+            SoftDelete::Timestamp(field) => format!("{} IS NULL", field.column),
+            SoftDelete::Flag(field) => format!("{} IS NOT TRUE", field.column),
+        }
+    }
+
+    /// Marks a live row as deleted: sets the timestamp to [`audit::now`] or the flag to `TRUE`.
+    pub async fn soft_delete<'e, E>(
+        executor: E,
+        table: &str,
+        id_column: &str,
+        id_value: i32,
+        soft_delete: &SoftDelete,
+    ) -> Result<u64, RepositoryError<sqlx::Error>>
+    where
+        E: Executor<'e, Database = Postgres>,
+    {
+        let set_clause = match soft_delete {
+            SoftDelete::Timestamp(field) => format!("{} = to_timestamp($2)", field.column),
+            SoftDelete::Flag(field) => format!("{} = TRUE", field.column),
+        };
+        let query = format!(
+            "UPDATE {} SET {} WHERE {} = $1 AND {}",
+            table,
+            set_clause,
+            id_column,
+            Self::not_deleted(soft_delete)
+        );
+        let mut query = sqlx::query(&query).bind(id_value);
+        if let SoftDelete::Timestamp(_) = soft_delete {
+            query = query.bind(audit::unix_seconds());
+        }
+        let result = query
+            .execute(executor)
+            .await
+            .map_err(RepositoryError::ConnectionError)?;
+        Ok(result.rows_affected())
+    }
+
+    /// Brings a soft-deleted row back: clears the timestamp or resets the flag.
+    pub async fn restore<'e, E>(
+        executor: E,
+        table: &str,
+        id_column: &str,
+        id_value: i32,
+        soft_delete: &SoftDelete,
+    ) -> Result<u64, RepositoryError<sqlx::Error>>
+    where
+        E: Executor<'e, Database = Postgres>,
+    {
+        let (set_clause, deleted) = match soft_delete {
+            SoftDelete::Timestamp(field) => (
+                format!("{} = NULL", field.column),
+                format!("{} IS NOT NULL", field.column),
+            ),
+            SoftDelete::Flag(field) => (
+                format!("{} = FALSE", field.column),
+                format!("{} IS TRUE", field.column),
+            ),
+        };
+        let query = format!(
+            "UPDATE {} SET {} WHERE {} = $1 AND {}",
+            table, set_clause, id_column, deleted
+        );
+        let result = sqlx::query(&query)
+            .bind(id_value)
+            .execute(executor)
+            .await
+            .map_err(RepositoryError::ConnectionError)?;
+        Ok(result.rows_affected())
+    }
+
+    /// Subscribes to `channel` on a dedicated connection from `pool`.
+    ///
+    /// Payloads are JSON, deserialized into `T`; a payload that does not match yields an
+    /// error item without ending the stream. The connection is re-established when lost,
+    /// but notifications sent in between are missed.
+    pub async fn listen<T>(pool: &PgPool, channel: &str) -> Result<Notifications<T>, RepositoryError<sqlx::Error>>
+    where
+        T: DeserializeOwned + Send + 'static,
+    {
+        let mut listener = PgListener::connect_with(pool)
+            .await
+            .map_err(RepositoryError::ConnectionError)?;
+        listener
+            .listen(channel)
+            .await
+            .map_err(RepositoryError::ConnectionError)?;
+
+        Ok(listener
+            .into_stream()
+            .map_err(RepositoryError::ConnectionError)
+            .and_then(|notification| {
+                let payload = serde_json::from_str(notification.payload())
+                    .map_err(|e| RepositoryError::Other(format!("invalid notification payload: {}", e)));
+                future::ready(payload.map(|payload| Notification {
+                    channel: notification.channel().to_string(),
+                    payload,
+                    process_id: notification.process_id(),
+                }))
+            })
+            .boxed())
+    }
+
+    /// Sends `payload` as JSON to the listeners of `channel`.
+    ///
+    /// Inside a transaction the notification is only delivered on commit.
+    pub async fn notify<'e, E, T>(executor: E, channel: &str, payload: &T) -> Result<(), RepositoryError<sqlx::Error>>
+    where
+        E: Executor<'e, Database = Postgres>,
+        T: Serialize,
+    {
+        let payload = serde_json::to_string(payload).map_err(|e| RepositoryError::Other(e.to_string()))?;
+        sqlx::query("SELECT pg_notify($1, $2)")
+            .bind(channel)
+            .bind(payload)
+            .execute(executor)
+            .await
+            .map_err(RepositoryError::ConnectionError)?;
+        Ok(())
+    }
+
+    /// Installs a trigger sending a [`RowChange`] to `channel` after every insert, update
+    /// and delete on `table`. Replaces the trigger if it already exists.
+    pub async fn create_notify_trigger<'e, E>(
+        executor: E,
+        table: &str,
+        id_column: &str,
+        channel: &str,
+    ) -> Result<(), RepositoryError<sqlx::Error>>
+    where
+        E: Executor<'e, Database = Postgres>,
+    {
+        let function = format!("rustling_notify_{}", table.replace('.', "_"));
+        let channel = channel.replace('\'', "''");
+        let query = format!(
+            "DO $do$ BEGIN \
+             CREATE OR REPLACE FUNCTION {function}() RETURNS trigger AS $fn$ \
+             DECLARE row_id JSON; \
+             BEGIN \
+               IF TG_OP = 'DELETE' THEN row_id := to_json(OLD.{id_column}); ELSE row_id := to_json(NEW.{id_column}); END IF; \
+               PERFORM pg_notify('{channel}', json_build_object('operation', TG_OP, 'table', TG_TABLE_NAME, 'id', row_id)::text); \
+               RETURN NULL; \
+             END $fn$ LANGUAGE plpgsql; \
+             DROP TRIGGER IF EXISTS rustling_notify ON {table}; \
+             CREATE TRIGGER rustling_notify AFTER INSERT OR UPDATE OR DELETE ON {table} \
+               FOR EACH ROW EXECUTE FUNCTION {function}(); \
+             END $do$"
+        );
+        sqlx::query(&query)
+            .execute(executor)
+            .await
+            .map_err(RepositoryError::ConnectionError)?;
+        Ok(())
+    }
+
+    /// Creates `table` with the columns and indexes of `schema`, unless it already exists.
+    ///
+    /// Takes a pool, connection or transaction, as the statements run one after another.
+    pub async fn create_table<'c, A>(
+        connection: A,
+        table: &str,
+        schema: &TableSchema,
+    ) -> Result<(), RepositoryError<sqlx::Error>>
+    where
+        A: Acquire<'c, Database = Postgres>,
+    {
+        let mut connection = connection
+            .acquire()
+            .await
+            .map_err(RepositoryError::ConnectionError)?;
+        let statements = schema
+            .create_table_sql(table)
+            .map_err(RepositoryError::SchemaMismatch)?;
+        for statement in statements {
+            sqlx::query(&statement)
+                .execute(&mut *connection)
+                .await
+                .map_err(RepositoryError::ConnectionError)?;
+        }
+        Ok(())
+    }
+
+    /// Creates the indexes missing on `table`; existing ones are left as they are.
+    ///
+    /// Mongo-only text indexes are skipped.
+    pub async fn ensure_indexes<'c, A>(
+        connection: A,
+        table: &str,
+        indexes: &[Index],
+    ) -> Result<(), RepositoryError<sqlx::Error>>
+    where
+        A: Acquire<'c, Database = Postgres>,
+    {
+        let mut connection = connection
+            .acquire()
+            .await
+            .map_err(RepositoryError::ConnectionError)?;
+        for statement in indexes.iter().filter_map(|i| i.create_index_sql(table)) {
+            sqlx::query(&statement)
+                .execute(&mut *connection)
+                .await
+                .map_err(RepositoryError::ConnectionError)?;
+        }
+        Ok(())
+    }
+
+    pub async fn drop_table<'e, E>(
+        executor: E,
+        table: &str,
+    ) -> Result<(), RepositoryError<sqlx::Error>>
+    where
+        E: Executor<'e, Database = Postgres>,
+    {
+        sqlx::query(&format!("DROP TABLE IF EXISTS {}", table))
+            .execute(executor)
+            .await
+            .map_err(RepositoryError::ConnectionError)?;
+        Ok(())
+    }
+
+    /// Compares `table` in `information_schema` with `schema`: missing columns, incompatible
+    /// types, nullable columns for non-`Option` fields and a missing primary key.
+    ///
+    /// Like [`Self::verify_columns`], meant to run at startup; all differences are reported
+    /// together in one [`RepositoryError::SchemaMismatch`].
+    pub async fn verify_schema<'e, E>(
+        executor: E,
+        table: &str,
+        schema: &TableSchema,
+    ) -> Result<(), RepositoryError<sqlx::Error>>
+    where
+        E: Executor<'e, Database = Postgres>,
+    {
+        let (namespace, name) = match table.split_once('.') {
+            Some((namespace, name)) => (Some(namespace), name),
+            None => (None, table),
+        };
+        let existing: Vec<DbColumn> = sqlx::query_as(
+            "SELECT c.column_name::text AS name, c.udt_name::text AS udt_name, \
+             c.is_nullable = 'YES' AS nullable, \
+             EXISTS (SELECT 1 FROM information_schema.table_constraints t \
+               JOIN information_schema.key_column_usage k \
+                 ON k.constraint_schema = t.constraint_schema AND k.constraint_name = t.constraint_name \
+               WHERE t.constraint_type = 'PRIMARY KEY' AND t.table_schema = c.table_schema \
+                 AND t.table_name = c.table_name AND k.column_name = c.column_name) AS primary_key \
+             FROM information_schema.columns c \
+             WHERE c.table_schema = COALESCE($1, current_schema()) AND c.table_name = $2",
+        )
+        .bind(namespace)
+        .bind(name)
+        .fetch_all(executor)
+        .await
+        .map_err(RepositoryError::ConnectionError)?;
+
+        if existing.is_empty() {
+            return Err(RepositoryError::SchemaMismatch(format!(
+                "table {} does not exist",
+                table
+            )));
+        }
+        let differences = schema.differences(&existing);
+        if differences.is_empty() {
+            Ok(())
+        } else {
+            Err(RepositoryError::SchemaMismatch(format!(
+                "table {}: {}",
+                table,
+                differences.join("; ")
+            )))
+        }
+    }
+
+    /// Checks that every mapped column exists on `table`.
+    ///
+    /// Intended to be called once at startup so that a renamed or dropped column is
+    /// reported up front instead of as a decode error on the first query.
+    pub async fn verify_columns<'e, E>(
+        executor: E,
+        table: &str,
+        columns: &[&str],
+    ) -> Result<(), RepositoryError<sqlx::Error>>
+    where
+        E: Executor<'e, Database = Postgres>,
+    {
+        let existing: Vec<String> = sqlx::query_scalar(
+            "SELECT attname::text FROM pg_attribute \
+             WHERE attrelid = $1::regclass AND attnum > 0 AND NOT attisdropped",
+        )
+        .bind(table)
+        .fetch_all(executor)
+        .await
+        .map_err(RepositoryError::ConnectionError)?;
+
+        let missing: Vec<&str> = columns
+            .iter()
+            .copied()
+            .filter(|c| !existing.iter().any(|e| e == c))
+            .collect();
+
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(RepositoryError::SchemaMismatch(format!(
+                "table {} is missing mapped column(s): {}",
+                table,
+                missing.join(", ")
+            )))
+        }
+    }
 }
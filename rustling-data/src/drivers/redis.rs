@@ -0,0 +1,440 @@
+use std::collections::BTreeSet;
+use std::fmt::Display;
+
+use redis::aio::MultiplexedConnection;
+use redis::{FromRedisValue, RedisError, Script};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+use crate::api::RepositoryError;
+
+/// How entities are serialized into Redis values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Codec {
+    #[default]
+    Json,
+    /// MessagePack with field names, so `#[serde(...)]` attributes behave as with JSON.
+    MessagePack,
+}
+
+impl Codec {
+    pub fn encode<T: Serialize>(&self, value: &T) -> Result<Vec<u8>, RepositoryError<RedisError>> {
+        match self {
+            Codec::Json => {
+                serde_json::to_vec(value).map_err(|e| RepositoryError::Other(e.to_string()))
+            }
+            Codec::MessagePack => {
+                rmp_serde::to_vec_named(value).map_err(|e| RepositoryError::Other(e.to_string()))
+            }
+        }
+    }
+
+    pub fn decode<T: DeserializeOwned>(
+        &self,
+        bytes: &[u8],
+    ) -> Result<T, RepositoryError<RedisError>> {
+        match self {
+            Codec::Json => {
+                serde_json::from_slice(bytes).map_err(|e| RepositoryError::Other(e.to_string()))
+            }
+            Codec::MessagePack => {
+                rmp_serde::from_slice(bytes).map_err(|e| RepositoryError::Other(e.to_string()))
+            }
+        }
+    }
+}
+
+/// Where and how a repository keeps its entities.
+///
+/// An entity is stored under `{prefix}:id`. Ids come from the counter `{prefix}#seq`, and each
+/// field listed in `indexes` gets one set `{prefix}#field:value` of the ids having that value.
+/// Keeping these outside `{prefix}:*` lets `find_all` scan the entity keys only. The braces make
+/// the prefix a hash tag, so on Redis Cluster all keys of a key space share one slot and the
+/// scripts writing several of them at once do not fail with `CROSSSLOT`.
+#[derive(Debug, Clone, Copy)]
+pub struct KeySpace<'a> {
+    pub prefix: &'a str,
+    pub codec: Codec,
+    /// Expiry in seconds, renewed on every write.
+    pub ttl: Option<u64>,
+    /// Serialized names of the fields with a secondary-index set.
+    pub indexes: &'a [&'a str],
+}
+
+impl<'a> KeySpace<'a> {
+    pub const fn new(prefix: &'a str) -> Self {
+        Self {
+            prefix,
+            codec: Codec::Json,
+            ttl: None,
+            indexes: &[],
+        }
+    }
+
+    pub fn key(&self, id: &impl Display) -> String {
+        format!("{{{}}}:{}", self.prefix, id)
+    }
+
+    fn sequence_key(&self) -> String {
+        format!("{{{}}}#seq", self.prefix)
+    }
+
+    fn index_key(&self, field: &str, value: &serde_json::Value) -> String {
+        let value = match value {
+            serde_json::Value::String(s) => s.clone(),
+            other => other.to_string(),
+        };
+        format!("{{{}}}#{}:{}", self.prefix, field, value)
+    }
+
+    /// Index sets `entity` belongs to; fields that are missing or null are not indexed.
+    fn index_keys<T: Serialize>(
+        &self,
+        entity: &T,
+    ) -> Result<Vec<String>, RepositoryError<RedisError>> {
+        if self.indexes.is_empty() {
+            return Ok(Vec::new());
+        }
+        let document =
+            serde_json::to_value(entity).map_err(|e| RepositoryError::Other(e.to_string()))?;
+        Ok(self
+            .indexes
+            .iter()
+            .filter_map(|field| match document.get(field) {
+                None | Some(serde_json::Value::Null) => None,
+                Some(value) => Some(self.index_key(field, value)),
+            })
+            .collect())
+    }
+}
+
+/// Sets `KEYS[1]` to `ARGV[1]` with the expiry `ARGV[2]` unless it exists, then adds the id
+/// `ARGV[3]` to the index sets that follow.
+const INSERT: &str = r#"
+if redis.call('EXISTS', KEYS[1]) == 1 then
+    return 0
+end
+if ARGV[2] == '' then
+    redis.call('SET', KEYS[1], ARGV[1])
+else
+    redis.call('SET', KEYS[1], ARGV[1], 'EX', ARGV[2])
+end
+for i = 2, #KEYS do
+    redis.call('SADD', KEYS[i], ARGV[3])
+end
+return 1
+"#;
+
+/// Writes `ARGV[2]` over `KEYS[1]` only while it still holds `ARGV[1]`, then moves the id
+/// `ARGV[4]` from the `ARGV[5]` old index sets that follow to the remaining new ones.
+const COMPARE_AND_SET: &str = r#"
+if redis.call('GET', KEYS[1]) ~= ARGV[1] then
+    return 0
+end
+if ARGV[3] == '' then
+    redis.call('SET', KEYS[1], ARGV[2], 'KEEPTTL')
+else
+    redis.call('SET', KEYS[1], ARGV[2], 'EX', ARGV[3])
+end
+local old = tonumber(ARGV[5])
+for i = 2, #KEYS do
+    if i <= old + 1 then
+        redis.call('SREM', KEYS[i], ARGV[4])
+    else
+        redis.call('SADD', KEYS[i], ARGV[4])
+    end
+end
+return 1
+"#;
+
+/// Deletes `KEYS[1]` only while it still holds `ARGV[1]` and removes the id `ARGV[2]` from
+/// the index sets that follow.
+const COMPARE_AND_DELETE: &str = r#"
+if redis.call('GET', KEYS[1]) ~= ARGV[1] then
+    return 0
+end
+redis.call('DEL', KEYS[1])
+for i = 2, #KEYS do
+    redis.call('SREM', KEYS[i], ARGV[2])
+end
+return 1
+"#;
+
+/// Keys requested per `SCAN` step and per `MGET`.
+const SCAN_COUNT: usize = 100;
+
+/// Attempts of a read-modify-write before giving up on a key that keeps changing.
+const MAX_ATTEMPTS: usize = 16;
+
+/// Key-value driver storing serialized entities, see [`KeySpace`] for the key layout.
+///
+/// Updates are read-modify-write cycles committed with a compare-and-set script, so a
+/// concurrent write makes them re-read the entity instead of overwriting it. The script keeps
+/// the expiry with `SET ... KEEPTTL`, which needs Redis 6.0 or later.
+#[derive(Clone)]
+pub struct RedisDriver {
+    connection: MultiplexedConnection,
+}
+
+impl RedisDriver {
+    pub fn new(connection: MultiplexedConnection) -> Self {
+        Self { connection }
+    }
+
+    /// The driver's connection, for commands without a driver method.
+    pub fn connection(&self) -> MultiplexedConnection {
+        self.connection.clone()
+    }
+
+    /// Next value of the key space's id counter, starting at 1.
+    pub async fn next_id<ID: FromRedisValue>(
+        &self,
+        space: &KeySpace<'_>,
+    ) -> Result<ID, RepositoryError<RedisError>> {
+        redis::cmd("INCR")
+            .arg(space.sequence_key())
+            .query_async(&mut self.connection())
+            .await
+            .map_err(RepositoryError::ConnectionError)
+    }
+
+    pub async fn find_one<T: DeserializeOwned>(
+        &self,
+        space: &KeySpace<'_>,
+        id: &impl Display,
+    ) -> Result<Option<T>, RepositoryError<RedisError>> {
+        Ok(self
+            .get_raw(space, &space.key(id))
+            .await?
+            .map(|(entity, _)| entity))
+    }
+
+    /// Loads every entity of the key space, walking it with `SCAN`.
+    pub async fn find_all<T: DeserializeOwned>(
+        &self,
+        space: &KeySpace<'_>,
+    ) -> Result<Vec<T>, RepositoryError<RedisError>> {
+        let pattern = format!("{{{}}}:*", space.prefix);
+        let mut connection = self.connection();
+        let mut cursor = 0_u64;
+        // `SCAN` may return a key more than once.
+        let mut keys = BTreeSet::new();
+        loop {
+            let (next, batch): (u64, Vec<String>) = redis::cmd("SCAN")
+                .arg(cursor)
+                .arg("MATCH")
+                .arg(&pattern)
+                .arg("COUNT")
+                .arg(SCAN_COUNT)
+                .query_async(&mut connection)
+                .await
+                .map_err(RepositoryError::ConnectionError)?;
+            keys.extend(batch);
+            if next == 0 {
+                break;
+            }
+            cursor = next;
+        }
+
+        let keys: Vec<String> = keys.into_iter().collect();
+        let mut found = Vec::with_capacity(keys.len());
+        for chunk in keys.chunks(SCAN_COUNT) {
+            found.extend(self.get_many(space, chunk).await?.into_iter().flatten());
+        }
+        Ok(found)
+    }
+
+    /// Loads the entities whose indexed `field` serializes to `value`.
+    pub async fn find_by_index<T: DeserializeOwned>(
+        &self,
+        space: &KeySpace<'_>,
+        field: &str,
+        value: &impl Serialize,
+    ) -> Result<Vec<T>, RepositoryError<RedisError>> {
+        if !space.indexes.contains(&field) {
+            return Err(RepositoryError::Other(format!(
+                "`{}` is not an indexed field of `{}`",
+                field, space.prefix
+            )));
+        }
+        let value =
+            serde_json::to_value(value).map_err(|e| RepositoryError::Other(e.to_string()))?;
+        let index_key = space.index_key(field, &value);
+        let mut connection = self.connection();
+        let ids: Vec<String> = redis::cmd("SMEMBERS")
+            .arg(&index_key)
+            .query_async(&mut connection)
+            .await
+            .map_err(RepositoryError::ConnectionError)?;
+        let keys: Vec<String> = ids.iter().map(|id| space.key(id)).collect();
+        let entities = self.get_many(space, &keys).await?;
+
+        // Entities that expired leave their id behind in the index set.
+        let expired: Vec<&String> = ids
+            .iter()
+            .zip(&entities)
+            .filter(|(_, e)| e.is_none())
+            .map(|(id, _)| id)
+            .collect();
+        if !expired.is_empty() {
+            redis::cmd("SREM")
+                .arg(&index_key)
+                .arg(expired)
+                .query_async::<()>(&mut connection)
+                .await
+                .map_err(RepositoryError::ConnectionError)?;
+        }
+        Ok(entities.into_iter().flatten().collect())
+    }
+
+    /// Stores a new entity along with its index entries; fails with
+    /// [`RepositoryError::ConstraintViolation`] if the key exists.
+    pub async fn insert<T: Serialize>(
+        &self,
+        space: &KeySpace<'_>,
+        id: &impl Display,
+        entity: &T,
+    ) -> Result<(), RepositoryError<RedisError>> {
+        let key = space.key(id);
+        let script = Script::new(INSERT);
+        let mut invocation = script.key(&key);
+        invocation
+            .arg(space.codec.encode(entity)?)
+            .arg(space.ttl.map(|ttl| ttl.to_string()).unwrap_or_default())
+            .arg(id.to_string());
+        for index_key in space.index_keys(entity)? {
+            invocation.key(index_key);
+        }
+        let mut connection = self.connection();
+        let stored: i64 = invocation
+            .invoke_async(&mut connection)
+            .await
+            .map_err(RepositoryError::ConnectionError)?;
+        if stored == 0 {
+            return Err(RepositoryError::ConstraintViolation(format!(
+                "key `{}` already exists",
+                key
+            )));
+        }
+        Ok(())
+    }
+
+    /// Applies `write` to the stored entity and saves the result, retrying with the new
+    /// state if the entity changes in between.
+    ///
+    /// `write` returns `Ok(false)` to leave the entity unchanged. Returns the entity before
+    /// and after the write, or `None` if it is missing or was left unchanged.
+    pub async fn modify<T, F>(
+        &self,
+        space: &KeySpace<'_>,
+        id: &impl Display,
+        mut write: F,
+    ) -> Result<Option<(T, T)>, RepositoryError<RedisError>>
+    where
+        T: Serialize + DeserializeOwned + Clone,
+        F: FnMut(&mut T) -> Result<bool, RepositoryError<RedisError>>,
+    {
+        let key = space.key(id);
+        let script = Script::new(COMPARE_AND_SET);
+        let mut connection = self.connection();
+        for _ in 0..MAX_ATTEMPTS {
+            let Some((before, raw)) = self.get_raw::<T>(space, &key).await? else {
+                return Ok(None);
+            };
+            let mut after = before.clone();
+            if !write(&mut after)? {
+                return Ok(None);
+            }
+            let old_indexes = space.index_keys(&before)?;
+            let new_indexes = space.index_keys(&after)?;
+            let mut invocation = script.key(&key);
+            invocation
+                .arg(raw)
+                .arg(space.codec.encode(&after)?)
+                .arg(space.ttl.map(|ttl| ttl.to_string()).unwrap_or_default())
+                .arg(id.to_string())
+                .arg(old_indexes.len());
+            for index_key in old_indexes.iter().chain(&new_indexes) {
+                invocation.key(index_key);
+            }
+            let written: i64 = invocation
+                .invoke_async(&mut connection)
+                .await
+                .map_err(RepositoryError::ConnectionError)?;
+            if written == 1 {
+                return Ok(Some((before, after)));
+            }
+        }
+        Err(RepositoryError::Other(format!(
+            "key `{}` kept changing during the update",
+            key
+        )))
+    }
+
+    /// Deletes the entity and its index entries; returns the number of deleted entities.
+    pub async fn delete<T: Serialize + DeserializeOwned>(
+        &self,
+        space: &KeySpace<'_>,
+        id: &impl Display,
+    ) -> Result<u64, RepositoryError<RedisError>> {
+        let key = space.key(id);
+        let script = Script::new(COMPARE_AND_DELETE);
+        let mut connection = self.connection();
+        for _ in 0..MAX_ATTEMPTS {
+            let Some((entity, raw)) = self.get_raw::<T>(space, &key).await? else {
+                return Ok(0);
+            };
+            let mut invocation = script.key(&key);
+            invocation.arg(raw).arg(id.to_string());
+            for index_key in space.index_keys(&entity)? {
+                invocation.key(index_key);
+            }
+            let deleted: u64 = invocation
+                .invoke_async(&mut connection)
+                .await
+                .map_err(RepositoryError::ConnectionError)?;
+            if deleted == 1 {
+                return Ok(1);
+            }
+        }
+        Err(RepositoryError::Other(format!(
+            "key `{}` kept changing during the delete",
+            key
+        )))
+    }
+
+    /// The decoded entity under `key` along with its stored bytes.
+    async fn get_raw<T: DeserializeOwned>(
+        &self,
+        space: &KeySpace<'_>,
+        key: &str,
+    ) -> Result<Option<(T, Vec<u8>)>, RepositoryError<RedisError>> {
+        let raw: Option<Vec<u8>> = redis::cmd("GET")
+            .arg(key)
+            .query_async(&mut self.connection())
+            .await
+            .map_err(RepositoryError::ConnectionError)?;
+        raw.map(|raw| Ok((space.codec.decode(&raw)?, raw)))
+            .transpose()
+    }
+
+    /// Entities under `keys`, `None` for keys that no longer exist.
+    async fn get_many<T: DeserializeOwned>(
+        &self,
+        space: &KeySpace<'_>,
+        keys: &[String],
+    ) -> Result<Vec<Option<T>>, RepositoryError<RedisError>> {
+        if keys.is_empty() {
+            return Ok(Vec::new());
+        }
+        let values: Vec<Option<Vec<u8>>> = redis::cmd("MGET")
+            .arg(keys)
+            .query_async(&mut self.connection())
+            .await
+            .map_err(RepositoryError::ConnectionError)?;
+        values
+            .into_iter()
+            .map(|raw| raw.map(|raw| space.codec.decode(&raw)).transpose())
+            .collect()
+    }
+}
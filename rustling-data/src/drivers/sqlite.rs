@@ -0,0 +1,351 @@
+use crate::api::{RepositoryError, SoftDelete};
+use crate::audit;
+use sqlx::error::BoxDynError;
+use sqlx::sqlite::{SqliteArguments, SqliteRow};
+use sqlx::{Arguments, Encode, Executor, FromRow, Row, Sqlite, Type};
+
+/// Ordered values bound to the `?n` placeholders of a statement; see [`crate::PgValues`].
+#[derive(Default)]
+pub struct SqliteValues<'q> {
+    args: SqliteArguments<'q>,
+    error: Option<BoxDynError>,
+}
+
+impl<'q> SqliteValues<'q> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push<T>(&mut self, value: T) -> &mut Self
+    where
+        T: Encode<'q, Sqlite> + Type<Sqlite> + 'q,
+    {
+        if let Err(e) = self.args.add(value) {
+            self.error.get_or_insert(e);
+        }
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.args.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn into_arguments(self) -> Result<SqliteArguments<'q>, RepositoryError<sqlx::Error>> {
+        match self.error {
+            Some(e) => Err(RepositoryError::Other(e.to_string())),
+            None => Ok(self.args),
+        }
+    }
+}
+
+impl<'q, T> From<Vec<&'q T>> for SqliteValues<'q>
+where
+    T: Encode<'q, Sqlite> + Type<Sqlite>,
+{
+    fn from(values: Vec<&'q T>) -> Self {
+        let mut sqlite_values = SqliteValues::new();
+        for v in values {
+            sqlite_values.push(v);
+        }
+        sqlite_values
+    }
+}
+
+/// SQLite counterpart of [`crate::PostgresDriver`], for embedded databases and in-process tests.
+pub struct SqliteDriver;
+
+impl SqliteDriver {
+    pub async fn find_all<'e, T, E>(
+        executor: E,
+        table: &str,
+        columns: &[&str],
+    ) -> Result<Vec<T>, RepositoryError<sqlx::Error>>
+    where
+        E: Executor<'e, Database = Sqlite>,
+        T: for<'r> FromRow<'r, SqliteRow> + Send + Unpin,
+    {
+        let query = format!("SELECT {} FROM {}", columns.join(", "), table);
+        sqlx::query_as::<_, T>(&query)
+            .fetch_all(executor)
+            .await
+            .map_err(RepositoryError::ConnectionError)
+    }
+
+    /// Selects the rows matching `filter`, a SQL condition whose `?n` placeholders are
+    /// bound to `values`.
+    pub async fn find_where<'e, 'q, T, E>(
+        executor: E,
+        table: &str,
+        columns: &[&str],
+        filter: &str,
+        values: impl Into<SqliteValues<'q>>,
+    ) -> Result<Vec<T>, RepositoryError<sqlx::Error>>
+    where
+        E: Executor<'e, Database = Sqlite>,
+        T: for<'r> FromRow<'r, SqliteRow> + Send + Unpin,
+    {
+        let query = format!(
+            "SELECT {} FROM {} WHERE {}",
+            columns.join(", "),
+            table,
+            filter
+        );
+        let args = values.into().into_arguments()?;
+        sqlx::query_as_with::<_, T, _>(&query, args)
+            .fetch_all(executor)
+            .await
+            .map_err(RepositoryError::ConnectionError)
+    }
+
+    pub async fn insert<'e, 'q, E>(
+        executor: E,
+        table: &str,
+        columns: &[&str],
+        values: impl Into<SqliteValues<'q>>,
+    ) -> Result<i32, RepositoryError<sqlx::Error>>
+    where
+        E: Executor<'e, Database = Sqlite>,
+    {
+        let placeholders: Vec<String> = (1..=columns.len()).map(|i| format!("?{}", i)).collect();
+        let query_string = format!(
+            "INSERT INTO {} ({}) VALUES ({}) RETURNING id",
+            table,
+            columns.join(", "),
+            placeholders.join(", ")
+        );
+
+        let args = values.into().into_arguments()?;
+        sqlx::query_with(&query_string, args)
+            .fetch_one(executor)
+            .await
+            .map_err(map_write_error)
+            .and_then(|row| {
+                row.try_get::<i32, _>("id")
+                    .map_err(|e| RepositoryError::Other(e.to_string()))
+            })
+    }
+
+    pub async fn find_one<'e, T, E>(
+        executor: E,
+        table: &str,
+        columns: &[&str],
+        id_column: &str,
+        id_value: i32,
+    ) -> Result<Option<T>, RepositoryError<sqlx::Error>>
+    where
+        E: Executor<'e, Database = Sqlite>,
+        T: for<'r> FromRow<'r, SqliteRow> + Send + Unpin,
+    {
+        let query = format!(
+            "SELECT {} FROM {} WHERE {} = ?1",
+            columns.join(", "),
+            table,
+            id_column
+        );
+        sqlx::query_as::<_, T>(&query)
+            .bind(id_value)
+            .fetch_optional(executor)
+            .await
+            .map_err(RepositoryError::ConnectionError)
+    }
+
+    /// Sets `columns` on the row with `id_value`; given `soft_delete`, only while it is not
+    /// soft-deleted.
+    pub async fn update<'e, 'q, E>(
+        executor: E,
+        table: &str,
+        id_column: &str,
+        id_value: i32,
+        columns: &[&str],
+        values: impl Into<SqliteValues<'q>>,
+        soft_delete: Option<&SoftDelete>,
+    ) -> Result<u64, RepositoryError<sqlx::Error>>
+    where
+        E: Executor<'e, Database = Sqlite>,
+    {
+        let set_clause: Vec<String> = columns
+            .iter()
+            .enumerate()
+            .map(|(i, c)| format!("{} = ?{}", c, i + 1))
+            .collect();
+        let mut query_string = format!(
+            "UPDATE {} SET {} WHERE {} = ?{}",
+            table,
+            set_clause.join(", "),
+            id_column,
+            columns.len() + 1
+        );
+
+        if let Some(soft_delete) = soft_delete {
+            query_string.push_str(&format!(" AND {}", Self::not_deleted(soft_delete)));
+        }
+
+        let mut values = values.into();
+        values.push(id_value);
+        let args = values.into_arguments()?;
+
+        sqlx::query_with(&query_string, args)
+            .execute(executor)
+            .await
+            .map_err(map_write_error)
+            .map(|res| res.rows_affected())
+    }
+
+    /// Like [`SqliteDriver::update`], but also increments `version_column` and, when
+    /// `expected_version` is given, only matches the row while it still has that version.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn update_versioned<'e, 'q, E>(
+        executor: E,
+        table: &str,
+        id_column: &str,
+        id_value: i32,
+        columns: &[&str],
+        values: impl Into<SqliteValues<'q>>,
+        version_column: &str,
+        expected_version: Option<i64>,
+        soft_delete: Option<&SoftDelete>,
+    ) -> Result<u64, RepositoryError<sqlx::Error>>
+    where
+        E: Executor<'e, Database = Sqlite>,
+    {
+        let mut set_clause: Vec<String> = columns
+            .iter()
+            .enumerate()
+            .map(|(i, c)| format!("{} = ?{}", c, i + 1))
+            .collect();
+        set_clause.push(format!("{0} = {0} + 1", version_column));
+
+        let mut query_string = format!(
+            "UPDATE {} SET {} WHERE {} = ?{}",
+            table,
+            set_clause.join(", "),
+            id_column,
+            columns.len() + 1
+        );
+
+        let mut values = values.into();
+        values.push(id_value);
+        if let Some(version) = expected_version {
+            query_string.push_str(&format!(" AND {} = ?{}", version_column, columns.len() + 2));
+            values.push(version);
+        }
+        if let Some(soft_delete) = soft_delete {
+            query_string.push_str(&format!(" AND {}", Self::not_deleted(soft_delete)));
+        }
+        let args = values.into_arguments()?;
+
+        sqlx::query_with(&query_string, args)
+            .execute(executor)
+            .await
+            .map_err(map_write_error)
+            .map(|res| res.rows_affected())
+    }
+
+    pub async fn delete<'e, E>(
+        executor: E,
+        table: &str,
+        id_column: &str,
+        id_value: i32,
+    ) -> Result<u64, RepositoryError<sqlx::Error>>
+    where
+        E: Executor<'e, Database = Sqlite>,
+    {
+        let query = format!("DELETE FROM {} WHERE {} = ?1", table, id_column);
+        let result = sqlx::query(&query)
+            .bind(id_value)
+            .execute(executor)
+            .await
+            .map_err(RepositoryError::ConnectionError)?;
+        Ok(result.rows_affected())
+    }
+
+    /// SQL condition matching the rows that are not soft-deleted.
+    pub fn not_deleted(soft_delete: &SoftDelete) -> String {
+        match soft_delete {
+            SoftDelete::Timestamp(field) => format!("{} IS NULL", field.column),
+            SoftDelete::Flag(field) => format!("{} IS NOT TRUE", field.column),
+        }
+    }
+
+    /// Marks a live row as deleted: sets the timestamp to [`audit::now`] or the flag to `TRUE`.
+    pub async fn soft_delete<'e, E>(
+        executor: E,
+        table: &str,
+        id_column: &str,
+        id_value: i32,
+        soft_delete: &SoftDelete,
+    ) -> Result<u64, RepositoryError<sqlx::Error>>
+    where
+        E: Executor<'e, Database = Sqlite>,
+    {
+        let set_clause = match soft_delete {
+            SoftDelete::Timestamp(field) => format!(
+                "{} = strftime('%Y-%m-%d %H:%M:%f', ?2, 'unixepoch')",
+                field.column
+            ),
+            SoftDelete::Flag(field) => format!("{} = TRUE", field.column),
+        };
+        let query = format!(
+            "UPDATE {} SET {} WHERE {} = ?1 AND {}",
+            table,
+            set_clause,
+            id_column,
+            Self::not_deleted(soft_delete)
+        );
+        let mut query = sqlx::query(&query).bind(id_value);
+        if let SoftDelete::Timestamp(_) = soft_delete {
+            query = query.bind(audit::unix_seconds());
+        }
+        let result = query
+            .execute(executor)
+            .await
+            .map_err(RepositoryError::ConnectionError)?;
+        Ok(result.rows_affected())
+    }
+
+    /// Brings a soft-deleted row back: clears the timestamp or resets the flag.
+    pub async fn restore<'e, E>(
+        executor: E,
+        table: &str,
+        id_column: &str,
+        id_value: i32,
+        soft_delete: &SoftDelete,
+    ) -> Result<u64, RepositoryError<sqlx::Error>>
+    where
+        E: Executor<'e, Database = Sqlite>,
+    {
+        let (set_clause, deleted) = match soft_delete {
+            SoftDelete::Timestamp(field) => (
+                format!("{} = NULL", field.column),
+                format!("{} IS NOT NULL", field.column),
+            ),
+            SoftDelete::Flag(field) => (
+                format!("{} = FALSE", field.column),
+                format!("{} IS TRUE", field.column),
+            ),
+        };
+        let query = format!(
+            "UPDATE {} SET {} WHERE {} = ?1 AND {}",
+            table, set_clause, id_column, deleted
+        );
+        let result = sqlx::query(&query)
+            .bind(id_value)
+            .execute(executor)
+            .await
+            .map_err(RepositoryError::ConnectionError)?;
+        Ok(result.rows_affected())
+    }
+}
+
+fn map_write_error(e: sqlx::Error) -> RepositoryError<sqlx::Error> {
+    match e {
+        sqlx::Error::Database(db_err) => {
+            RepositoryError::ConstraintViolation(db_err.message().to_string())
+        }
+        other => RepositoryError::ConnectionError(other),
+    }
+}
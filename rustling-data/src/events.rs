@@ -0,0 +1,172 @@
+//! Domain events published by generated repositories after successful writes.
+//!
+//! Events go to the global [`EventSink`], an in-process [`BroadcastSink`] unless replaced
+//! with [`set_sink`]. Repositories only do the extra reads an event needs (the stored entity
+//! after an insert, the previous state before an update) while the sink [is active](EventSink::is_active).
+//!
+//! ```rust,ignore
+//! let mut inserted = events::subscribe::<EntityInserted<User>>();
+//! tokio::spawn(async move {
+//!     while let Some(event) = inserted.recv().await {
+//!         search_index.add(&event.entity).await;
+//!     }
+//! });
+//! ```
+
+use std::any::Any;
+use std::sync::{Arc, RwLock};
+
+/// An entity was inserted; `entity` is the stored state, including its generated id.
+#[derive(Debug, Clone)]
+pub struct EntityInserted<T> {
+    pub entity: T,
+}
+
+/// An entity was updated or patched.
+///
+/// `before` is read separately ahead of the write, so a concurrent writer in between can make it
+/// differ from the state this write replaced; treat it as best-effort. `after` is read back after
+/// the write.
+#[derive(Debug, Clone)]
+pub struct EntityUpdated<T> {
+    pub before: T,
+    pub after: T,
+}
+
+/// An entity was deleted (or soft-deleted).
+#[derive(Debug, Clone)]
+pub struct EntityDeleted<ID> {
+    pub id: ID,
+}
+
+pub type Event = Arc<dyn Any + Send + Sync>;
+
+/// Destination of repository events.
+pub trait EventSink: Send + Sync {
+    fn publish(&self, event: Event);
+
+    /// Whether anyone receives the events; repositories skip building them otherwise.
+    fn is_active(&self) -> bool {
+        true
+    }
+}
+
+static SINK: RwLock<Option<Arc<dyn EventSink>>> = RwLock::new(None);
+
+/// Replaces the sink used by all repositories.
+pub fn set_sink(sink: impl EventSink + 'static) {
+    *SINK.write().unwrap_or_else(|e| e.into_inner()) = Some(Arc::new(sink));
+}
+
+fn sink() -> Option<Arc<dyn EventSink>> {
+    let installed = SINK.read().unwrap_or_else(|e| e.into_inner()).clone();
+    installed.or_else(default_sink)
+}
+
+pub fn is_active() -> bool {
+    sink().is_some_and(|sink| sink.is_active())
+}
+
+pub fn publish<E: Any + Send + Sync>(event: E) {
+    if let Some(sink) = sink() {
+        sink.publish(Arc::new(event));
+    }
+}
+
+#[cfg(feature = "tokio")]
+pub use broadcast::{BroadcastSink, Subscription, subscribe};
+
+#[cfg(feature = "tokio")]
+fn default_sink() -> Option<Arc<dyn EventSink>> {
+    Some(broadcast::default_sink())
+}
+
+#[cfg(not(feature = "tokio"))]
+fn default_sink() -> Option<Arc<dyn EventSink>> {
+    None
+}
+
+#[cfg(feature = "tokio")]
+mod broadcast {
+    use std::any::Any;
+    use std::marker::PhantomData;
+    use std::sync::{Arc, OnceLock};
+
+    use tokio::sync::broadcast::{self, error::RecvError};
+
+    use super::{Event, EventSink};
+
+    /// Sink fanning events out to every [`Subscription`] in the process.
+    #[derive(Clone)]
+    pub struct BroadcastSink {
+        sender: broadcast::Sender<Event>,
+    }
+
+    impl BroadcastSink {
+        /// Sink buffering up to `capacity` events for slow subscribers.
+        pub fn new(capacity: usize) -> Self {
+            let (sender, _) = broadcast::channel(capacity);
+            Self { sender }
+        }
+
+        pub fn subscribe<E: Any + Send + Sync>(&self) -> Subscription<E> {
+            Subscription {
+                receiver: self.sender.subscribe(),
+                event: PhantomData,
+            }
+        }
+    }
+
+    impl Default for BroadcastSink {
+        fn default() -> Self {
+            Self::new(1024)
+        }
+    }
+
+    impl EventSink for BroadcastSink {
+        fn publish(&self, event: Event) {
+            // Fails only when nobody is subscribed.
+            let _ = self.sender.send(event);
+        }
+
+        fn is_active(&self) -> bool {
+            self.sender.receiver_count() > 0
+        }
+    }
+
+    static DEFAULT_SINK: OnceLock<Arc<BroadcastSink>> = OnceLock::new();
+
+    pub(super) fn default_sink() -> Arc<dyn EventSink> {
+        DEFAULT_SINK.get_or_init(Default::default).clone()
+    }
+
+    /// Subscribes to events of type `E` on the default broadcast sink.
+    pub fn subscribe<E: Any + Send + Sync>() -> Subscription<E> {
+        DEFAULT_SINK.get_or_init(Default::default).subscribe()
+    }
+
+    /// Receiver of the events of type `E`; other events are skipped.
+    pub struct Subscription<E> {
+        receiver: broadcast::Receiver<Event>,
+        event: PhantomData<fn() -> E>,
+    }
+
+    impl<E: Any + Send + Sync> Subscription<E> {
+        /// Next event of type `E`, or `None` once the sink is gone.
+        ///
+        /// A subscriber that falls more than the sink capacity behind misses the oldest events.
+        pub async fn recv(&mut self) -> Option<Arc<E>> {
+            loop {
+                match self.receiver.recv().await {
+                    Ok(event) => {
+                        if let Ok(event) = event.downcast::<E>() {
+                            return Some(event);
+                        }
+                    }
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        }
+    }
+}
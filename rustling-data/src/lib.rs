@@ -8,6 +8,9 @@
 //!
 //! - `mongo`: MongoDB driver
 //! - `postgres`: PostgreSQL driver
+//! - `sqlite`: SQLite driver
+//! - `mysql`: MySQL / MariaDB driver
+//! - `redis`: Redis key-value driver
 //!
 //! ## Example
 //! ```rust,no_run
@@ -20,22 +23,60 @@
 #![doc(html_logo_url = "https://www.rust-lang.org/logos/rust-logo-512x512.png")]
 
 pub mod api;
+pub mod audit;
 pub mod drivers;
+pub mod events;
+pub mod memory;
+#[cfg(any(feature = "postgres", feature = "mongo"))]
+pub mod migrate;
+pub mod schema;
+pub mod validate;
+
+pub use memory::InMemoryRepository;
+pub use regex;
+pub use serde;
 
 #[cfg(feature = "postgres")]
-pub use drivers::postgres::PostgresDriver;
+pub use drivers::outbox::{Outbox, OutboxMessage, OutboxPublisher, OutboxRelay};
 #[cfg(feature = "postgres")]
+pub use drivers::postgres::{
+    LockMode, Notification, Notifications, PgValues, PostgresDriver, RowChange, RowOperation,
+};
+#[cfg(any(feature = "postgres", feature = "sqlite", feature = "mysql"))]
+pub use sqlx;
+#[cfg(any(feature = "postgres", feature = "sqlite", feature = "mysql"))]
 pub use sqlx::FromRow;
 #[cfg(feature = "postgres")]
 pub use sqlx::PgPool;
 #[cfg(feature = "postgres")]
 pub use sqlx::postgres::PgPoolOptions;
 
+#[cfg(feature = "sqlite")]
+pub use drivers::sqlite::{SqliteDriver, SqliteValues};
+#[cfg(feature = "sqlite")]
+pub use sqlx::SqlitePool;
+#[cfg(feature = "sqlite")]
+pub use sqlx::sqlite::SqlitePoolOptions;
+
+#[cfg(feature = "mysql")]
+pub use drivers::mysql::{MySqlDriver, MySqlValues};
+#[cfg(feature = "mysql")]
+pub use sqlx::MySqlPool;
+#[cfg(feature = "mysql")]
+pub use sqlx::mysql::MySqlPoolOptions;
+
 #[cfg(feature = "mongo")]
-pub use drivers::mongo::MongoDriver;
+pub use drivers::mongo::{
+    ChangeEvent, ChangeEvents, ChangeOperation, MongoDriver, MongoResumeTokenStore, ResumeTokenStore, ValidatorOptions, WatchOptions,
+};
 #[cfg(feature = "mongo")]
 pub use mongodb;
 #[cfg(feature = "mongo")]
 pub use mongodb::options::ClientOptions;
 #[cfg(feature = "mongo")]
 pub use mongodb::{Client, bson};
+
+#[cfg(feature = "redis")]
+pub use drivers::redis::{Codec, KeySpace, RedisDriver};
+#[cfg(feature = "redis")]
+pub use redis;
@@ -0,0 +1,405 @@
+//! [`InMemoryRepository`], a `CrudRepository` kept in a map, for unit tests that should not
+//! need a database.
+//!
+//! It runs the same steps as the generated repositories: audit stamps, lifecycle hooks,
+//! validation, optimistic locking, soft delete and events. It implements
+//! `CrudRepository<T, ID, DB>` for every `DB`, so it can stand in for a Postgres or Mongo
+//! repository in code written against either:
+//!
+//! ```rust,ignore
+//! async fn register<R: CrudRepository<User, i32, sqlx::Error>>(users: &R, name: &str) { ... }
+//!
+//! let users = InMemoryRepository::<User, i32>::new();
+//! register(&users, "alice").await;
+//! ```
+//!
+//! Derived repositories can be switched to it with `#[backend(memory)]`, e.g.
+//! `#[cfg_attr(test, backend(memory))]`, in which case the struct holds it in a `store` field.
+//!
+//! It also implements [`Repository`] with `Error = Infallible`.
+
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use crate::api::{CrudRepository, Entity, Lifecycle, Patch, Repository, RepositoryError};
+use crate::audit::AuditStamp;
+use crate::events::{self, EntityDeleted, EntityInserted, EntityUpdated};
+use crate::validate::FieldError;
+
+/// What [`InMemoryRepository`] needs to write an entity; implemented by `#[derive(Entity)]`
+/// for every `ID` its `id` field can be built from.
+///
+/// Redis repositories use it too, since they also modify entities in Rust before storing them.
+pub trait MemoryEntity<ID>: Entity {
+    /// Stores the generated id in the `id` field.
+    fn set_id(&mut self, id: ID);
+
+    /// Copies the fields `update_one` writes from `entity`.
+    fn apply_update(&mut self, entity: &Self);
+
+    /// Writes the fields set in `patch`, with the `updated_*` audit fields from `stamp`.
+    fn apply_patch(&mut self, patch: &Self::Patch, stamp: &AuditStamp);
+
+    /// Whether `patch` sets no field.
+    fn is_empty_patch(patch: &Self::Patch) -> bool;
+
+    /// Version the stored entity must have for `patch` to apply, if versioned.
+    fn expected_version(patch: &Self::Patch) -> Option<i64>;
+
+    /// Runs the validation attributes on the fields set in `patch`.
+    fn validate_patch(patch: &Self::Patch) -> Result<(), Vec<FieldError>>;
+
+    /// Increments the `#[rustling(version)]` field, if any.
+    fn bump_version(&mut self) {}
+
+    /// Whether the `#[rustling(soft_delete)]` field marks the entity as deleted.
+    fn is_deleted(&self) -> bool {
+        false
+    }
+
+    /// Sets or clears the `#[rustling(soft_delete)]` field.
+    fn set_deleted(&mut self, _deleted: bool) {}
+}
+
+/// Ids the repository can generate from its sequence, which starts at 1.
+pub trait GenerateId: Sized {
+    fn generate(sequence: u64) -> Self;
+
+    /// Sequence value `generate` makes this id from, if any.
+    fn sequence(&self) -> Option<u64> {
+        None
+    }
+}
+
+macro_rules! sequence_ids {
+    ($($ty:ty),*) => {
+        $(impl GenerateId for $ty {
+            fn generate(sequence: u64) -> Self {
+                sequence as $ty
+            }
+
+            fn sequence(&self) -> Option<u64> {
+                u64::try_from(*self).ok()
+            }
+        })*
+    };
+}
+
+sequence_ids!(i32, i64, u32, u64);
+
+impl GenerateId for String {
+    fn generate(sequence: u64) -> Self {
+        sequence.to_string()
+    }
+
+    fn sequence(&self) -> Option<u64> {
+        self.parse().ok()
+    }
+}
+
+#[cfg(feature = "mongo")]
+impl GenerateId for mongodb::bson::oid::ObjectId {
+    fn generate(_sequence: u64) -> Self {
+        mongodb::bson::oid::ObjectId::new()
+    }
+}
+
+type IdGenerator<ID> = Box<dyn Fn(u64) -> ID + Send + Sync>;
+
+/// `CrudRepository` over a map from id to entity, ordered by id.
+pub struct InMemoryRepository<T, ID> {
+    entities: RwLock<BTreeMap<ID, T>>,
+    sequence: AtomicU64,
+    generate_id: IdGenerator<ID>,
+    sequence_of: fn(&ID) -> Option<u64>,
+}
+
+impl<T, ID: GenerateId + 'static> InMemoryRepository<T, ID> {
+    pub fn new() -> Self {
+        Self {
+            sequence_of: ID::sequence,
+            ..Self::with_id_generator(ID::generate)
+        }
+    }
+}
+
+impl<T, ID: GenerateId + 'static> Default for InMemoryRepository<T, ID> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, ID> InMemoryRepository<T, ID> {
+    /// Repository generating ids with `generate`, given the next value of the sequence.
+    ///
+    /// Unlike with [`Self::new`], ids stored with `put` do not advance the sequence, so an insert
+    /// generating such an id fails with a constraint violation.
+    pub fn with_id_generator(generate: impl Fn(u64) -> ID + Send + Sync + 'static) -> Self {
+        Self {
+            entities: RwLock::new(BTreeMap::new()),
+            sequence: AtomicU64::new(0),
+            generate_id: Box::new(generate),
+            sequence_of: |_| None,
+        }
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, BTreeMap<ID, T>> {
+        self.entities.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, BTreeMap<ID, T>> {
+        self.entities.write().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Number of stored entities, including soft-deleted ones.
+    pub fn len(&self) -> usize {
+        self.read().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Removes every entity; the id sequence keeps counting.
+    pub fn clear(&self) {
+        self.write().clear();
+    }
+}
+
+impl<T, ID> InMemoryRepository<T, ID>
+where
+    T: MemoryEntity<ID> + Clone,
+    ID: Ord + Clone,
+{
+    /// Stores `entity` under `id` as is, replacing any entity with that id.
+    ///
+    /// The sequence moves past `id`, so later inserts do not generate it again.
+    pub fn put(&self, id: ID, mut entity: T) {
+        if let Some(sequence) = (self.sequence_of)(&id) {
+            self.sequence.fetch_max(sequence, Ordering::SeqCst);
+        }
+        entity.set_id(id.clone());
+        self.write().insert(id, entity);
+    }
+
+    /// Loads all entities, including soft-deleted ones.
+    pub fn find_with_deleted(&self) -> Vec<T> {
+        self.read()
+            .values()
+            .cloned()
+            .map(Lifecycle::loaded)
+            .collect()
+    }
+
+    /// Loads an entity even if it is soft-deleted.
+    pub fn find_one_with_deleted(&self, id: &ID) -> Option<T> {
+        self.read().get(id).cloned().map(Lifecycle::loaded)
+    }
+
+    /// Undoes a soft delete; returns the number of restored entities.
+    pub fn restore_one(&self, id: &ID) -> u64 {
+        match self.write().get_mut(id) {
+            Some(entity) if entity.is_deleted() => {
+                entity.set_deleted(false);
+                1
+            }
+            _ => 0,
+        }
+    }
+
+    /// Permanently removes an entity, whether or not it is soft-deleted.
+    pub fn purge_one(&self, id: &ID) -> u64 {
+        self.write().remove(id).map_or(0, |_| 1)
+    }
+
+    fn live(&self, id: &ID) -> Option<T> {
+        self.read()
+            .get(id)
+            .filter(|entity| !entity.is_deleted())
+            .cloned()
+            .map(Lifecycle::loaded)
+    }
+
+    /// Applies `write` to the live entity under `id`, checking `expected_version` first.
+    fn modify<DB>(
+        &self,
+        id: &ID,
+        expected_version: Option<i64>,
+        write: impl FnOnce(&mut T),
+    ) -> Result<Option<T>, RepositoryError<DB>> {
+        let mut entities = self.write();
+        let Some(stored) = entities.get_mut(id).filter(|entity| !entity.is_deleted()) else {
+            return Ok(None);
+        };
+        if T::VERSION_FIELD.is_some()
+            && expected_version.is_some()
+            && stored.version() != expected_version
+        {
+            return Err(RepositoryError::OptimisticLockConflict);
+        }
+        write(stored);
+        stored.bump_version();
+        Ok(Some(stored.clone().loaded()))
+    }
+}
+
+#[async_trait::async_trait]
+impl<T, ID, DB> CrudRepository<T, ID, DB> for InMemoryRepository<T, ID>
+where
+    T: MemoryEntity<ID> + Clone + Send + Sync + 'static,
+    ID: Ord + Clone + Send + Sync + 'static,
+    DB: Send,
+{
+    async fn find_all(&self) -> Result<Vec<T>, RepositoryError<DB>> {
+        Ok(self
+            .read()
+            .values()
+            .filter(|entity| !entity.is_deleted())
+            .cloned()
+            .map(Lifecycle::loaded)
+            .collect())
+    }
+
+    async fn find_one(&self, id: &ID) -> Result<Option<T>, RepositoryError<DB>> {
+        Ok(self.live(id))
+    }
+
+    async fn insert_one(&self, entity: &T) -> Result<ID, RepositoryError<DB>> {
+        let mut entity = entity.copy_for_write();
+        entity.stamp_insert(&AuditStamp::current());
+        entity.before_insert().map_err(RepositoryError::Other)?;
+        entity.validate().map_err(RepositoryError::Validation)?;
+        let id = (self.generate_id)(self.sequence.fetch_add(1, Ordering::SeqCst) + 1);
+        entity.set_id(id.clone());
+        {
+            let mut entities = self.write();
+            if entities.contains_key(&id) {
+                return Err(RepositoryError::ConstraintViolation(
+                    "duplicate id".to_string(),
+                ));
+            }
+            entities.insert(id.clone(), entity.copy_for_write());
+        }
+        entity.after_insert();
+        if let Some(entity) = self.live(&id).filter(|_| events::is_active()) {
+            events::publish(EntityInserted { entity });
+        }
+        Ok(id)
+    }
+
+    async fn update_one(&self, id: &ID, entity: &T) -> Result<Option<T>, RepositoryError<DB>> {
+        let mut entity = entity.copy_for_write();
+        entity.stamp_update(&AuditStamp::current());
+        entity.before_update().map_err(RepositoryError::Other)?;
+        entity.validate().map_err(RepositoryError::Validation)?;
+        let before = self.live(id);
+        let updated = self.modify(id, entity.version(), |stored| stored.apply_update(&entity))?;
+        if let Some(updated) = &updated {
+            updated.after_update();
+            if let Some(before) = before.filter(|_| events::is_active()) {
+                events::publish(EntityUpdated {
+                    before,
+                    after: updated.copy_for_write(),
+                });
+            }
+        }
+        Ok(updated)
+    }
+
+    async fn patch_one(&self, id: &ID, patch: &Patch<T>) -> Result<Option<T>, RepositoryError<DB>>
+    where
+        T: Entity,
+    {
+        if T::is_empty_patch(patch) {
+            return Ok(self.live(id));
+        }
+        T::validate_patch(patch).map_err(RepositoryError::Validation)?;
+        let stamp = AuditStamp::current();
+        let before = self.live(id);
+        let updated = self.modify(id, T::expected_version(patch), |stored| {
+            stored.apply_patch(patch, &stamp)
+        })?;
+        if let Some(updated) = &updated {
+            updated.after_update();
+            if let Some(before) = before.filter(|_| events::is_active()) {
+                events::publish(EntityUpdated {
+                    before,
+                    after: updated.copy_for_write(),
+                });
+            }
+        }
+        Ok(updated)
+    }
+
+    async fn delete_one(&self, id: &ID) -> Result<u64, RepositoryError<DB>> {
+        if let Some(entity) = self.live(id).filter(|_| T::LIFECYCLE) {
+            entity.before_delete().map_err(RepositoryError::Other)?;
+        }
+        let affected = {
+            let mut entities = self.write();
+            match entities.get_mut(id) {
+                Some(entity) if entity.is_deleted() => 0,
+                Some(entity) if T::SOFT_DELETE.is_some() => {
+                    entity.set_deleted(true);
+                    1
+                }
+                Some(_) => {
+                    entities.remove(id);
+                    1
+                }
+                None => 0,
+            }
+        };
+        if affected > 0 {
+            events::publish(EntityDeleted { id: id.clone() });
+        }
+        Ok(affected)
+    }
+}
+
+/// Never fails with a driver error; [`RepositoryError::ConnectionError`] is uninhabited.
+#[async_trait::async_trait]
+impl<T, ID> Repository<T, ID> for InMemoryRepository<T, ID>
+where
+    T: MemoryEntity<ID> + Clone + Send + Sync + 'static,
+    ID: Ord + Clone + Send + Sync + 'static,
+{
+    type Error = Infallible;
+
+    async fn find_all(&self) -> Result<Vec<T>, RepositoryError<Infallible>> {
+        CrudRepository::<T, ID, Infallible>::find_all(self).await
+    }
+
+    async fn find_one(&self, id: &ID) -> Result<Option<T>, RepositoryError<Infallible>> {
+        CrudRepository::<T, ID, Infallible>::find_one(self, id).await
+    }
+
+    async fn insert_one(&self, entity: &T) -> Result<ID, RepositoryError<Infallible>> {
+        CrudRepository::<T, ID, Infallible>::insert_one(self, entity).await
+    }
+
+    async fn update_one(
+        &self,
+        id: &ID,
+        entity: &T,
+    ) -> Result<Option<T>, RepositoryError<Infallible>> {
+        CrudRepository::<T, ID, Infallible>::update_one(self, id, entity).await
+    }
+
+    async fn patch_one(
+        &self,
+        id: &ID,
+        patch: &Patch<T>,
+    ) -> Result<Option<T>, RepositoryError<Infallible>>
+    where
+        T: Entity,
+    {
+        CrudRepository::<T, ID, Infallible>::patch_one(self, id, patch).await
+    }
+
+    async fn delete_one(&self, id: &ID) -> Result<u64, RepositoryError<Infallible>> {
+        CrudRepository::<T, ID, Infallible>::delete_one(self, id).await
+    }
+}
@@ -0,0 +1,97 @@
+//! Versioned schema migrations for Postgres and Mongo.
+//!
+//! Migrations are applied in version order and recorded with a checksum in a
+//! `_rustling_migrations` table (Postgres) or collection (Mongo). A recorded migration whose
+//! source has changed since it ran is reported instead of being silently skipped. Runners take
+//! a lock first, so service instances starting side by side apply each migration once.
+//!
+//! Migration files are named `<version>_<name>.<ext>`, e.g. `0002_add_email.sql`.
+
+#[cfg(feature = "mongo")]
+pub mod mongo;
+#[cfg(feature = "postgres")]
+pub mod postgres;
+
+use std::path::Path;
+
+use sha2::{Digest, Sha256};
+
+/// Default name of the table or collection recording applied migrations.
+pub const MIGRATIONS_TABLE: &str = "_rustling_migrations";
+
+/// Migration recorded as applied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "postgres", derive(sqlx::FromRow))]
+pub struct AppliedMigration {
+    pub version: i64,
+    pub name: String,
+    pub checksum: String,
+}
+
+/// Hex-encoded SHA-256 of a migration's source.
+pub fn checksum(source: &[u8]) -> String {
+    Sha256::digest(source)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Version and name of a migration file, from `<version>_<name>.<ext>`.
+pub fn parse_file_name(path: &Path) -> Option<(i64, String)> {
+    let stem = path.file_stem()?.to_str()?;
+    let (version, name) = stem.split_once('_')?;
+    Some((version.parse().ok()?, name.to_string()))
+}
+
+/// `(version, name, contents)` of the files in `dir` with the given extension, by version.
+pub(crate) fn read_dir(dir: &Path, extension: &str) -> Result<Vec<(i64, String, String)>, String> {
+    let entries = std::fs::read_dir(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    let mut files = Vec::new();
+    for entry in entries {
+        let path = entry.map_err(|e| e.to_string())?.path();
+        if path.extension().is_none_or(|e| e != extension) {
+            continue;
+        }
+        let (version, name) = parse_file_name(&path).ok_or_else(|| {
+            format!(
+                "{}: migration files must be named <version>_<name>.{}",
+                path.display(),
+                extension
+            )
+        })?;
+        let contents =
+            std::fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        files.push((version, name, contents));
+    }
+    files.sort_by_key(|(version, _, _)| *version);
+    Ok(files)
+}
+
+/// Indexes of the migrations still to apply, given `(version, name, checksum)` in version order.
+///
+/// Fails on duplicate versions and on applied migrations whose checksum changed.
+pub(crate) fn pending(
+    migrations: &[(i64, &str, &str)],
+    applied: &[AppliedMigration],
+) -> Result<Vec<usize>, String> {
+    if let Some(pair) = migrations.windows(2).find(|w| w[0].0 == w[1].0) {
+        return Err(format!(
+            "migrations {} and {} share version {}",
+            pair[0].1, pair[1].1, pair[0].0
+        ));
+    }
+    let mut pending = Vec::new();
+    for (index, (version, name, checksum)) in migrations.iter().enumerate() {
+        match applied.iter().find(|a| a.version == *version) {
+            Some(a) if a.checksum != *checksum => {
+                return Err(format!(
+                    "migration {} ({}) was changed after it was applied",
+                    version, name
+                ));
+            }
+            Some(_) => {}
+            None => pending.push(index),
+        }
+    }
+    Ok(pending)
+}
@@ -0,0 +1,320 @@
+use std::future::Future;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::TryStreamExt;
+use futures::future::BoxFuture;
+use mongodb::bson::{DateTime, Document, doc, oid::ObjectId};
+use mongodb::error::{ErrorKind, WriteFailure};
+use mongodb::{Collection, Database};
+
+use super::{AppliedMigration, MIGRATIONS_TABLE, checksum, pending, read_dir};
+use crate::api::RepositoryError;
+use crate::drivers::mongo::MongoDriver;
+
+type MigrationFn =
+    Arc<dyn Fn(Database) -> BoxFuture<'static, Result<(), mongodb::error::Error>> + Send + Sync>;
+
+/// `_id` of the lock document in the migrations collection.
+const LOCK_ID: &str = "lock";
+
+#[derive(Clone)]
+enum Action {
+    Commands(Vec<Document>),
+    Function(MigrationFn),
+}
+
+/// Mongo migration: database commands, or a Rust function given the database.
+#[derive(Clone)]
+pub struct MongoMigration {
+    pub version: i64,
+    pub name: String,
+    pub checksum: String,
+    action: Action,
+}
+
+impl MongoMigration {
+    /// Runs each command with `runCommand`, in order.
+    pub fn commands(version: i64, name: impl Into<String>, commands: Vec<Document>) -> Self {
+        let source: String = commands.iter().map(ToString::to_string).collect();
+        Self {
+            version,
+            name: name.into(),
+            checksum: checksum(source.as_bytes()),
+            action: Action::Commands(commands),
+        }
+    }
+
+    /// Commands from a JSON array of (extended JSON) command documents.
+    pub fn from_json(
+        version: i64,
+        name: impl Into<String>,
+        json: &str,
+    ) -> Result<Self, RepositoryError<mongodb::error::Error>> {
+        let name = name.into();
+        let invalid =
+            |e: String| RepositoryError::Other(format!("migration {} ({}): {}", version, name, e));
+        let values: Vec<serde_json::Map<String, serde_json::Value>> =
+            serde_json::from_str(json).map_err(|e| invalid(e.to_string()))?;
+        let commands = values
+            .into_iter()
+            .map(Document::try_from)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| invalid(e.to_string()))?;
+        Ok(Self {
+            version,
+            checksum: checksum(json.as_bytes()),
+            name,
+            action: Action::Commands(commands),
+        })
+    }
+
+    /// Runs `f` with the database.
+    ///
+    /// The checksum covers the version and name only, so changes to `f` are not detected.
+    pub fn function<F, Fut>(version: i64, name: impl Into<String>, f: F) -> Self
+    where
+        F: Fn(Database) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), mongodb::error::Error>> + Send + 'static,
+    {
+        let name = name.into();
+        Self {
+            version,
+            checksum: checksum(format!("{}_{}", version, name).as_bytes()),
+            name,
+            action: Action::Function(Arc::new(move |db| Box::pin(f(db)))),
+        }
+    }
+
+    async fn apply(&self, db: &Database) -> Result<(), mongodb::error::Error> {
+        match &self.action {
+            Action::Commands(commands) => {
+                for command in commands {
+                    db.run_command(command.clone()).await?;
+                }
+                Ok(())
+            }
+            Action::Function(f) => f(db.clone()).await,
+        }
+    }
+}
+
+/// Applies [`MongoMigration`]s and records them in the `_rustling_migrations` collection.
+///
+/// ```rust,ignore
+/// let applied = MongoMigrator::from_dir("migrations/mongo")?
+///     .migration(MongoMigration::function(3, "backfill_slugs", backfill_slugs))
+///     .run(&driver)
+///     .await?;
+/// ```
+///
+/// A lock document in the same collection keeps concurrent runners apart; a lock left by a
+/// runner that died is taken over once it expires. The runner renews the lock around every
+/// migration, so only a single migration has to finish within `lock_expiry`; a run that lost its
+/// lock in the meantime fails instead of recording the migration.
+#[derive(Clone)]
+pub struct MongoMigrator {
+    migrations: Vec<MongoMigration>,
+    collection: String,
+    lock_expiry: Duration,
+}
+
+impl MongoMigrator {
+    pub fn new() -> Self {
+        Self {
+            migrations: Vec::new(),
+            collection: MIGRATIONS_TABLE.to_string(),
+            lock_expiry: Duration::from_secs(600),
+        }
+    }
+
+    /// Migrator with the `<version>_<name>.json` command files of `dir`.
+    pub fn from_dir(dir: impl AsRef<Path>) -> Result<Self, RepositoryError<mongodb::error::Error>> {
+        let files = read_dir(dir.as_ref(), "json").map_err(RepositoryError::Other)?;
+        let mut migrator = Self::new();
+        for (version, name, json) in files {
+            migrator = migrator.migration(MongoMigration::from_json(version, name, &json)?);
+        }
+        Ok(migrator)
+    }
+
+    pub fn migration(mut self, migration: MongoMigration) -> Self {
+        self.migrations.push(migration);
+        self.migrations.sort_by_key(|m| m.version);
+        self
+    }
+
+    /// Collection recording applied migrations, `_rustling_migrations` by default.
+    pub fn collection(mut self, collection: impl Into<String>) -> Self {
+        self.collection = collection.into();
+        self
+    }
+
+    /// How long a runner may hold the lock before others take it over; 10 minutes by default.
+    pub fn lock_expiry(mut self, lock_expiry: Duration) -> Self {
+        self.lock_expiry = lock_expiry;
+        self
+    }
+
+    /// Applies the pending migrations in version order and returns them.
+    ///
+    /// Fails before applying anything if an applied migration was changed since.
+    pub async fn run(
+        &self,
+        driver: &MongoDriver,
+    ) -> Result<Vec<AppliedMigration>, RepositoryError<mongodb::error::Error>> {
+        let collection = driver.collection::<Document>(&self.collection);
+        let owner = self.lock(&collection).await?;
+
+        let result = self
+            .run_locked(&driver.database(), &collection, owner)
+            .await;
+
+        let unlocked = collection
+            .delete_one(doc! { "_id": LOCK_ID, "owner": owner })
+            .await
+            .map_err(RepositoryError::ConnectionError);
+        let applied = result?;
+        unlocked?;
+        Ok(applied)
+    }
+
+    /// Migrations recorded as applied, by version.
+    pub async fn applied(
+        &self,
+        driver: &MongoDriver,
+    ) -> Result<Vec<AppliedMigration>, RepositoryError<mongodb::error::Error>> {
+        self.applied_in(&driver.collection(&self.collection)).await
+    }
+
+    async fn applied_in(
+        &self,
+        collection: &Collection<Document>,
+    ) -> Result<Vec<AppliedMigration>, RepositoryError<mongodb::error::Error>> {
+        let records: Vec<Document> = collection
+            .find(doc! { "_id": { "$type": "long" } })
+            .sort(doc! { "_id": 1 })
+            .await
+            .map_err(RepositoryError::ConnectionError)?
+            .try_collect()
+            .await
+            .map_err(RepositoryError::ConnectionError)?;
+        let invalid =
+            |e: mongodb::bson::document::ValueAccessError| RepositoryError::Other(e.to_string());
+        records
+            .iter()
+            .map(|record| {
+                Ok(AppliedMigration {
+                    version: record.get_i64("_id").map_err(invalid)?,
+                    name: record.get_str("name").map_err(invalid)?.to_string(),
+                    checksum: record.get_str("checksum").map_err(invalid)?.to_string(),
+                })
+            })
+            .collect()
+    }
+
+    async fn run_locked(
+        &self,
+        db: &Database,
+        collection: &Collection<Document>,
+        owner: ObjectId,
+    ) -> Result<Vec<AppliedMigration>, RepositoryError<mongodb::error::Error>> {
+        let applied = self.applied_in(collection).await?;
+        let known: Vec<_> = self
+            .migrations
+            .iter()
+            .map(|m| (m.version, m.name.as_str(), m.checksum.as_str()))
+            .collect();
+        let pending = pending(&known, &applied).map_err(RepositoryError::SchemaMismatch)?;
+
+        let mut newly_applied = Vec::new();
+        for migration in pending.into_iter().map(|i| &self.migrations[i]) {
+            self.renew(collection, owner).await?;
+            migration.apply(db).await.map_err(|e| {
+                RepositoryError::Other(format!(
+                    "migration {} ({}) failed: {}",
+                    migration.version, migration.name, e
+                ))
+            })?;
+            self.renew(collection, owner).await?;
+            collection
+                .insert_one(doc! {
+                    "_id": migration.version,
+                    "name": &migration.name,
+                    "checksum": &migration.checksum,
+                    "applied_at": DateTime::now(),
+                })
+                .await
+                .map_err(RepositoryError::ConnectionError)?;
+            newly_applied.push(AppliedMigration {
+                version: migration.version,
+                name: migration.name.clone(),
+                checksum: migration.checksum.clone(),
+            });
+        }
+        Ok(newly_applied)
+    }
+
+    /// Pushes the expiry of the lock held by `owner` forward; fails if another runner took it over.
+    async fn renew(
+        &self,
+        collection: &Collection<Document>,
+        owner: ObjectId,
+    ) -> Result<(), RepositoryError<mongodb::error::Error>> {
+        let renewed = collection
+            .update_one(
+                doc! { "_id": LOCK_ID, "owner": owner },
+                doc! { "$set": { "expires_at": self.expires_at(DateTime::now()) } },
+            )
+            .await
+            .map_err(RepositoryError::ConnectionError)?;
+        if renewed.matched_count == 0 {
+            return Err(RepositoryError::Other(
+                "migration lock expired and was taken over by another runner".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn expires_at(&self, now: DateTime) -> DateTime {
+        DateTime::from_millis(now.timestamp_millis() + self.lock_expiry.as_millis() as i64)
+    }
+
+    /// Takes the lock, waiting while another runner holds it; returns the owner token.
+    async fn lock(
+        &self,
+        collection: &Collection<Document>,
+    ) -> Result<ObjectId, RepositoryError<mongodb::error::Error>> {
+        let owner = ObjectId::new();
+        loop {
+            let now = DateTime::now();
+            let expires_at = self.expires_at(now);
+            // Matches a missing or expired lock; a live one makes the upsert hit the `_id` index.
+            let taken = collection
+                .update_one(
+                    doc! { "_id": LOCK_ID, "expires_at": { "$lt": now } },
+                    doc! { "$set": { "owner": owner, "expires_at": expires_at } },
+                )
+                .upsert(true)
+                .await;
+            match taken {
+                Ok(_) => return Ok(owner),
+                Err(e) if is_duplicate_key(&e) => {
+                    tokio::time::sleep(Duration::from_millis(500)).await
+                }
+                Err(e) => return Err(RepositoryError::ConnectionError(e)),
+            }
+        }
+    }
+}
+
+impl Default for MongoMigrator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn is_duplicate_key(error: &mongodb::error::Error) -> bool {
+    matches!(&*error.kind, ErrorKind::Write(WriteFailure::WriteError(e)) if e.code == 11000)
+}
@@ -0,0 +1,204 @@
+use std::path::Path;
+
+use sqlx::{Acquire, PgConnection, PgPool};
+
+use super::{AppliedMigration, MIGRATIONS_TABLE, checksum, pending, read_dir};
+use crate::api::RepositoryError;
+
+/// SQL migration; its statements run in one transaction.
+#[derive(Debug, Clone)]
+pub struct SqlMigration {
+    pub version: i64,
+    pub name: String,
+    pub sql: String,
+    pub checksum: String,
+}
+
+impl SqlMigration {
+    pub fn new(version: i64, name: impl Into<String>, sql: impl Into<String>) -> Self {
+        let sql = sql.into();
+        Self {
+            version,
+            name: name.into(),
+            checksum: checksum(sql.as_bytes()),
+            sql,
+        }
+    }
+}
+
+/// Applies [`SqlMigration`]s and records them in `_rustling_migrations`.
+///
+/// ```rust,ignore
+/// let applied = PostgresMigrator::from_dir("migrations")?.run(&pool).await?;
+/// ```
+///
+/// The run holds a session advisory lock, so concurrent runners wait for each other.
+#[derive(Debug, Clone)]
+pub struct PostgresMigrator {
+    migrations: Vec<SqlMigration>,
+    table: String,
+}
+
+impl PostgresMigrator {
+    pub fn new() -> Self {
+        Self {
+            migrations: Vec::new(),
+            table: MIGRATIONS_TABLE.to_string(),
+        }
+    }
+
+    /// Migrator with the `<version>_<name>.sql` files of `dir`.
+    pub fn from_dir(dir: impl AsRef<Path>) -> Result<Self, RepositoryError<sqlx::Error>> {
+        let files = read_dir(dir.as_ref(), "sql").map_err(RepositoryError::Other)?;
+        Ok(files
+            .into_iter()
+            .fold(Self::new(), |migrator, (version, name, sql)| {
+                migrator.migration(SqlMigration::new(version, name, sql))
+            }))
+    }
+
+    pub fn migration(mut self, migration: SqlMigration) -> Self {
+        self.migrations.push(migration);
+        self.migrations.sort_by_key(|m| m.version);
+        self
+    }
+
+    /// Table recording applied migrations, `_rustling_migrations` by default.
+    pub fn table(mut self, table: impl Into<String>) -> Self {
+        self.table = table.into();
+        self
+    }
+
+    pub fn migrations(&self) -> &[SqlMigration] {
+        &self.migrations
+    }
+
+    /// Applies the pending migrations in version order and returns them.
+    ///
+    /// Fails before applying anything if an applied migration was changed since.
+    pub async fn run(
+        &self,
+        pool: &PgPool,
+    ) -> Result<Vec<AppliedMigration>, RepositoryError<sqlx::Error>> {
+        let mut connection = pool
+            .acquire()
+            .await
+            .map_err(RepositoryError::ConnectionError)?;
+        sqlx::query("SELECT pg_advisory_lock(hashtext($1))")
+            .bind(&self.table)
+            .execute(&mut *connection)
+            .await
+            .map_err(RepositoryError::ConnectionError)?;
+
+        let result = self.run_locked(&mut connection).await;
+
+        let unlocked = sqlx::query("SELECT pg_advisory_unlock(hashtext($1))")
+            .bind(&self.table)
+            .execute(&mut *connection)
+            .await
+            .map_err(RepositoryError::ConnectionError);
+        let applied = result?;
+        unlocked?;
+        Ok(applied)
+    }
+
+    /// Migrations recorded as applied, by version.
+    pub async fn applied(
+        &self,
+        pool: &PgPool,
+    ) -> Result<Vec<AppliedMigration>, RepositoryError<sqlx::Error>> {
+        let mut connection = pool
+            .acquire()
+            .await
+            .map_err(RepositoryError::ConnectionError)?;
+        self.create_table(&mut connection).await?;
+        self.load_applied(&mut connection).await
+    }
+
+    async fn run_locked(
+        &self,
+        connection: &mut PgConnection,
+    ) -> Result<Vec<AppliedMigration>, RepositoryError<sqlx::Error>> {
+        self.create_table(connection).await?;
+        let applied = self.load_applied(connection).await?;
+        let known: Vec<_> = self
+            .migrations
+            .iter()
+            .map(|m| (m.version, m.name.as_str(), m.checksum.as_str()))
+            .collect();
+        let pending = pending(&known, &applied).map_err(RepositoryError::SchemaMismatch)?;
+
+        let mut newly_applied = Vec::new();
+        for migration in pending.into_iter().map(|i| &self.migrations[i]) {
+            let mut tx = connection
+                .begin()
+                .await
+                .map_err(RepositoryError::ConnectionError)?;
+            sqlx::raw_sql(&migration.sql)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| {
+                    RepositoryError::Other(format!(
+                        "migration {} ({}) failed: {}",
+                        migration.version, migration.name, e
+                    ))
+                })?;
+            sqlx::query(&format!(
+                "INSERT INTO {} (version, name, checksum) VALUES ($1, $2, $3)",
+                self.table
+            ))
+            .bind(migration.version)
+            .bind(&migration.name)
+            .bind(&migration.checksum)
+            .execute(&mut *tx)
+            .await
+            .map_err(RepositoryError::ConnectionError)?;
+            tx.commit()
+                .await
+                .map_err(RepositoryError::ConnectionError)?;
+            newly_applied.push(AppliedMigration {
+                version: migration.version,
+                name: migration.name.clone(),
+                checksum: migration.checksum.clone(),
+            });
+        }
+        Ok(newly_applied)
+    }
+
+    async fn create_table(
+        &self,
+        connection: &mut PgConnection,
+    ) -> Result<(), RepositoryError<sqlx::Error>> {
+        sqlx::query(&format!(
+            "CREATE TABLE IF NOT EXISTS {} (\
+             version BIGINT PRIMARY KEY, \
+             name TEXT NOT NULL, \
+             checksum TEXT NOT NULL, \
+             applied_at TIMESTAMPTZ NOT NULL DEFAULT now())",
+            self.table
+        ))
+        .execute(connection)
+        .await
+        .map_err(RepositoryError::ConnectionError)?;
+        Ok(())
+    }
+
+    async fn load_applied(
+        &self,
+        connection: &mut PgConnection,
+    ) -> Result<Vec<AppliedMigration>, RepositoryError<sqlx::Error>> {
+        sqlx::query_as(&format!(
+            "SELECT version, name, checksum FROM {} ORDER BY version",
+            self.table
+        ))
+        .fetch_all(connection)
+        .await
+        .map_err(RepositoryError::ConnectionError)
+    }
+}
+
+impl Default for PostgresMigrator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,263 @@
+//! Table and index descriptions generated by `#[derive(Entity)]`, and the Postgres DDL built
+//! from them.
+//!
+//! Column types come from the field types (`i32` is `INTEGER`, `Option<T>` is nullable,
+//! `OffsetDateTime` and chrono's `DateTime` are `TIMESTAMPTZ`, ...) unless overridden with
+//! `#[rustling(sql_type = "...")]`; `#[rustling(default = "now()")]` sets an SQL default.
+//!
+//! Indexes are declared on a field, or on the struct when they span several fields:
+//!
+//! - `#[rustling(index)]`, `#[rustling(unique)]`: single-field index
+//! - `#[rustling(index(unique, name = "...", predicate = "..."))]`: with options, see [`Index`]
+//! - `#[rustling(index(fields(last_name, first_name)))]` on the struct: composite index
+//!
+//! An integer `id` becomes `SERIAL`/`BIGSERIAL` so inserts can leave it out. Fields of other
+//! types (custom enums, ...) have no SQL type until one is set, and creating their table fails.
+
+use crate::api::FieldName;
+
+/// One column of a [`TableSchema`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Column {
+    pub name: &'static str,
+    /// Postgres type, e.g. `TEXT` or `INTEGER[]`; `None` if the field type has no known mapping.
+    pub sql_type: Option<&'static str>,
+    pub nullable: bool,
+    pub primary_key: bool,
+    pub default: Option<&'static str>,
+}
+
+impl Column {
+    /// Column of type `sql_type`, `NOT NULL` and without constraints.
+    pub const fn new(name: &'static str, sql_type: &'static str) -> Self {
+        Self {
+            name,
+            sql_type: Some(sql_type),
+            nullable: false,
+            primary_key: false,
+            default: None,
+        }
+    }
+
+    fn definition(&self) -> Result<String, String> {
+        let sql_type = self.sql_type.ok_or_else(|| {
+            format!(
+                "no SQL type for column {}, set #[rustling(sql_type = \"...\")]",
+                self.name
+            )
+        })?;
+        let serial = match sql_type {
+            "INTEGER" if self.primary_key && self.default.is_none() => Some("SERIAL"),
+            "BIGINT" if self.primary_key && self.default.is_none() => Some("BIGSERIAL"),
+            _ => None,
+        };
+        let mut definition = format!("{} {}", self.name, serial.unwrap_or(sql_type));
+        if self.primary_key {
+            definition.push_str(" PRIMARY KEY");
+        } else if !self.nullable {
+            definition.push_str(" NOT NULL");
+        }
+        if let Some(default) = self.default {
+            definition.push_str(" DEFAULT ");
+            definition.push_str(default);
+        }
+        Ok(definition)
+    }
+}
+
+/// Index on one or more fields of an entity.
+///
+/// `text`, `sparse`, `ttl` and `partial` only apply to Mongo, and `predicate` only to Postgres,
+/// which has no text indexes of this kind: [`Index::create_index_sql`] skips them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Index {
+    /// Defaults to `<table>_<columns>_idx` in Postgres and to the server's name in Mongo.
+    pub name: Option<&'static str>,
+    pub fields: &'static [FieldName],
+    pub unique: bool,
+    /// `WHERE` clause of a partial Postgres index, e.g. `deleted_at IS NULL`.
+    pub predicate: Option<&'static str>,
+    /// Mongo text index over the fields.
+    pub text: bool,
+    /// Mongo sparse index, skipping documents without the field.
+    pub sparse: bool,
+    /// Seconds after the indexed date at which Mongo deletes the document.
+    pub ttl: Option<u64>,
+    /// `partialFilterExpression` of a Mongo index, as (extended) JSON.
+    pub partial: Option<&'static str>,
+}
+
+impl Index {
+    /// Plain ascending index on `fields`.
+    pub const fn new(fields: &'static [FieldName]) -> Self {
+        Self {
+            name: None,
+            fields,
+            unique: false,
+            predicate: None,
+            text: false,
+            sparse: false,
+            ttl: None,
+            partial: None,
+        }
+    }
+
+    /// Name of the index in Postgres.
+    pub fn postgres_name(&self, table: &str) -> String {
+        match self.name {
+            Some(name) => name.to_string(),
+            None => {
+                let columns: Vec<_> = self.fields.iter().map(|f| f.column).collect();
+                format!("{}_{}_idx", table.replace('.', "_"), columns.join("_"))
+            }
+        }
+    }
+
+    /// `CREATE INDEX IF NOT EXISTS` statement; `None` for text indexes.
+    pub fn create_index_sql(&self, table: &str) -> Option<String> {
+        if self.text {
+            return None;
+        }
+        let columns: Vec<_> = self.fields.iter().map(|f| f.column).collect();
+        let mut sql = format!(
+            "CREATE {}INDEX IF NOT EXISTS {} ON {} ({})",
+            if self.unique { "UNIQUE " } else { "" },
+            self.postgres_name(table),
+            table,
+            columns.join(", ")
+        );
+        if let Some(predicate) = self.predicate {
+            sql.push_str(" WHERE ");
+            sql.push_str(predicate);
+        }
+        Some(sql)
+    }
+}
+
+/// Columns, in field order, and indexes of an entity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TableSchema {
+    /// Table name from `#[rustling(table = "...")]`, or the lowercased struct name plus `s`.
+    pub name: &'static str,
+    pub columns: &'static [Column],
+    pub indexes: &'static [Index],
+}
+
+impl TableSchema {
+    pub fn column(&self, name: &str) -> Option<&Column> {
+        self.columns.iter().find(|c| c.name == name)
+    }
+
+    /// `CREATE TABLE IF NOT EXISTS` followed by the statements creating the indexes.
+    ///
+    /// Fails if a column has no SQL type.
+    pub fn create_table_sql(&self, table: &str) -> Result<Vec<String>, String> {
+        let columns = self
+            .columns
+            .iter()
+            .map(Column::definition)
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut statements = vec![format!(
+            "CREATE TABLE IF NOT EXISTS {} ({})",
+            table,
+            columns.join(", ")
+        )];
+        statements.extend(self.indexes.iter().filter_map(|i| i.create_index_sql(table)));
+        Ok(statements)
+    }
+}
+
+/// Column as found in `information_schema.columns`, compared by [`TableSchema::differences`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "postgres", derive(sqlx::FromRow))]
+pub struct DbColumn {
+    pub name: String,
+    /// Postgres type name, e.g. `int4`, `varchar` or `_text` for `TEXT[]`.
+    pub udt_name: String,
+    pub nullable: bool,
+    pub primary_key: bool,
+}
+
+impl TableSchema {
+    /// Ways the existing columns differ from the schema, in column order; empty if they match.
+    ///
+    /// Only differences that break reading or writing entities are reported: a nullable
+    /// column for an `Option` field that is `NOT NULL` in the database is fine, and text and
+    /// JSON types are interchangeable. Extra database columns are ignored.
+    pub fn differences(&self, existing: &[DbColumn]) -> Vec<String> {
+        let mut differences = Vec::new();
+        for column in self.columns {
+            let Some(db) = existing.iter().find(|c| c.name == column.name) else {
+                differences.push(format!("missing column {}", column.name));
+                continue;
+            };
+            if let Some(sql_type) = column.sql_type {
+                let expected = udt_name(sql_type);
+                if type_family(&expected) != type_family(&db.udt_name) {
+                    differences.push(format!(
+                        "column {} has type {} but the entity declares {}",
+                        column.name, db.udt_name, sql_type
+                    ));
+                }
+            }
+            if column.primary_key && !db.primary_key {
+                differences.push(format!("column {} is not the primary key", column.name));
+            } else if !column.primary_key && !column.nullable && db.nullable {
+                differences.push(format!(
+                    "column {} is nullable but its field is not an Option",
+                    column.name
+                ));
+            }
+        }
+        differences
+    }
+}
+
+/// `udt_name` Postgres reports for a declared type, e.g. `INTEGER` is `int4`.
+fn udt_name(sql_type: &str) -> String {
+    let sql_type = sql_type.trim().to_lowercase();
+    if let Some(element) = sql_type.strip_suffix("[]") {
+        return format!("_{}", udt_name(element));
+    }
+    // Drop modifiers such as `VARCHAR(20)` or `NUMERIC(10, 2)`.
+    let base = match sql_type.split_once('(') {
+        Some((base, rest)) => format!(
+            "{}{}",
+            base.trim(),
+            rest.split_once(')').map_or("", |r| r.1)
+        ),
+        None => sql_type,
+    };
+    let name = match base.trim() {
+        "smallint" | "smallserial" | "serial2" => "int2",
+        "integer" | "int" | "serial" | "serial4" => "int4",
+        "bigint" | "bigserial" | "serial8" => "int8",
+        "real" => "float4",
+        "double precision" => "float8",
+        "boolean" => "bool",
+        "character varying" => "varchar",
+        "character" | "char" => "bpchar",
+        "\"char\"" => "char",
+        "decimal" => "numeric",
+        "timestamp" | "timestamp without time zone" => "timestamp",
+        "timestamp with time zone" => "timestamptz",
+        "time without time zone" => "time",
+        "time with time zone" => "timetz",
+        other => other,
+    };
+    name.to_string()
+}
+
+/// Types decoding into the same Rust types compare equal.
+fn type_family(udt_name: &str) -> &str {
+    let (array, element) = match udt_name.strip_prefix('_') {
+        Some(element) => (true, element),
+        None => (false, udt_name),
+    };
+    match (array, element) {
+        (false, "text" | "varchar" | "bpchar" | "name" | "citext") => "text",
+        (true, "text" | "varchar" | "bpchar" | "name" | "citext") => "_text",
+        (false, "json" | "jsonb") => "jsonb",
+        _ => udt_name,
+    }
+}
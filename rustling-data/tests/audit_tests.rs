@@ -0,0 +1,26 @@
+#![cfg(feature = "tokio")]
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use rustling_data::audit::{self, AuditStamp, Clock};
+
+struct FixedClock;
+
+impl Clock for FixedClock {
+    fn now(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(1_000_000)
+    }
+}
+
+#[tokio::test]
+async fn test_stamp_uses_clock_and_actor() {
+    audit::set_clock(FixedClock);
+
+    let stamp = audit::with_actor(42_i64, async { AuditStamp::current() }).await;
+    assert_eq!(stamp.now(), UNIX_EPOCH + Duration::from_secs(1_000_000));
+    assert_eq!(stamp.actor::<i64>(), Some(42));
+    // The actor is only handed out as the type it was set with.
+    assert_eq!(stamp.actor::<String>(), None);
+
+    assert_eq!(AuditStamp::current().actor::<i64>(), None);
+}
@@ -0,0 +1,27 @@
+#![cfg(feature = "tokio")]
+
+use rustling_data::events::{self, BroadcastSink, EntityDeleted, EntityInserted, EventSink};
+
+#[tokio::test]
+async fn test_subscription_receives_only_its_event_type() {
+    let mut deleted = events::subscribe::<EntityDeleted<i32>>();
+    assert!(events::is_active());
+
+    events::publish(EntityInserted { entity: "ignored".to_string() });
+    events::publish(EntityDeleted { id: "other id type".to_string() });
+    events::publish(EntityDeleted { id: 7_i32 });
+
+    let event = deleted.recv().await.expect("sink closed");
+    assert_eq!(event.id, 7);
+}
+
+#[tokio::test]
+async fn test_broadcast_sink_is_inactive_without_subscribers() {
+    let sink = BroadcastSink::new(8);
+    assert!(!sink.is_active());
+
+    let subscription = sink.subscribe::<EntityDeleted<i32>>();
+    assert!(sink.is_active());
+    drop(subscription);
+    assert!(!sink.is_active());
+}
@@ -0,0 +1,354 @@
+use rustling_data::InMemoryRepository;
+use rustling_data::api::{
+    BoxError, CrudRepository, DynRepository, Entity, ErasedRepository, FieldName, Lifecycle,
+    RepositoryError, SoftDelete, SwappableRepository,
+};
+use rustling_data::audit::AuditStamp;
+use rustling_data::memory::MemoryEntity;
+use rustling_data::validate::FieldError;
+use std::convert::Infallible;
+use std::sync::Arc;
+
+/// What `#[derive(Entity)]` generates for a versioned, soft-deletable entity.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Note {
+    id: i32,
+    title: String,
+    version: i64,
+    deleted: bool,
+}
+
+#[derive(Default)]
+struct NotePatch {
+    title: Option<String>,
+    version: Option<i64>,
+}
+
+impl Lifecycle for Note {}
+
+impl Entity for Note {
+    type Patch = NotePatch;
+
+    const VERSION_FIELD: Option<FieldName> = Some(FieldName {
+        column: "version",
+        key: "version",
+    });
+    const SOFT_DELETE: Option<SoftDelete> = Some(SoftDelete::Flag(FieldName {
+        column: "deleted",
+        key: "deleted",
+    }));
+
+    fn version(&self) -> Option<i64> {
+        Some(self.version)
+    }
+
+    fn copy_for_write(&self) -> Self {
+        self.clone()
+    }
+
+    fn validate(&self) -> Result<(), Vec<FieldError>> {
+        if self.title.is_empty() {
+            return Err(vec![FieldError::new("title", "must not be empty")]);
+        }
+        Ok(())
+    }
+
+    fn changes(&self, original: &Self) -> NotePatch {
+        NotePatch {
+            title: (self.title != original.title).then(|| self.title.clone()),
+            version: Some(original.version),
+        }
+    }
+}
+
+impl<ID> MemoryEntity<ID> for Note
+where
+    i32: From<ID>,
+{
+    fn set_id(&mut self, id: ID) {
+        self.id = From::from(id);
+    }
+
+    fn apply_update(&mut self, entity: &Self) {
+        self.title = entity.title.clone();
+        self.deleted = entity.deleted;
+    }
+
+    fn apply_patch(&mut self, patch: &NotePatch, _stamp: &AuditStamp) {
+        if let Some(title) = &patch.title {
+            self.title = title.clone();
+        }
+    }
+
+    fn is_empty_patch(patch: &NotePatch) -> bool {
+        patch.title.is_none()
+    }
+
+    fn expected_version(patch: &NotePatch) -> Option<i64> {
+        patch.version
+    }
+
+    fn validate_patch(_patch: &NotePatch) -> Result<(), Vec<FieldError>> {
+        Ok(())
+    }
+
+    fn bump_version(&mut self) {
+        self.version += 1;
+    }
+
+    fn is_deleted(&self) -> bool {
+        self.deleted
+    }
+
+    fn set_deleted(&mut self, deleted: bool) {
+        self.deleted = deleted;
+    }
+}
+
+/// Stands in for a `CrudRepository<Note, i32, sqlx::Error>`; the error type is irrelevant here.
+type Notes = InMemoryRepository<Note, i32>;
+type Error = RepositoryError<Infallible>;
+
+fn note(title: &str) -> Note {
+    Note {
+        title: title.to_string(),
+        ..Note::default()
+    }
+}
+
+#[tokio::test]
+async fn test_insert_and_find() {
+    let notes = Notes::new();
+
+    let first: Result<i32, Error> = notes.insert_one(&note("a")).await;
+    let second: Result<i32, Error> = notes.insert_one(&note("b")).await;
+    let (first, second) = (first.unwrap(), second.unwrap());
+    assert_eq!((first, second), (1, 2));
+
+    let found: Result<Option<Note>, Error> = notes.find_one(&first).await;
+    assert_eq!(
+        found.unwrap().map(|n| (n.id, n.title)),
+        Some((1, "a".to_string()))
+    );
+
+    let all: Result<Vec<Note>, Error> = notes.find_all().await;
+    assert_eq!(all.unwrap().len(), 2);
+
+    let invalid: Result<i32, Error> = notes.insert_one(&note("")).await;
+    assert!(matches!(invalid, Err(RepositoryError::Validation(_))));
+    assert_eq!(notes.len(), 2);
+}
+
+#[tokio::test]
+async fn test_update_checks_version() {
+    let notes = Notes::new();
+    let id: Result<i32, Error> = notes.insert_one(&note("draft")).await;
+    let id = id.unwrap();
+
+    let mut edited = notes.find_one_with_deleted(&id).unwrap();
+    edited.title = "final".to_string();
+    let updated: Result<Option<Note>, Error> = notes.update_one(&id, &edited).await;
+    let updated = updated.unwrap().expect("Note missing");
+    assert_eq!((updated.title.as_str(), updated.version), ("final", 1));
+
+    // `edited` still carries version 0.
+    let stale: Result<Option<Note>, Error> = notes.update_one(&id, &edited).await;
+    assert!(matches!(
+        stale,
+        Err(RepositoryError::OptimisticLockConflict)
+    ));
+
+    let missing: Result<Option<Note>, Error> = notes.update_one(&99, &edited).await;
+    assert!(missing.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_patch_writes_set_fields() {
+    let notes = Notes::new();
+    let id: Result<i32, Error> = notes.insert_one(&note("draft")).await;
+    let id = id.unwrap();
+
+    let empty: Result<Option<Note>, Error> = notes.patch_one(&id, &NotePatch::default()).await;
+    assert_eq!(empty.unwrap().map(|n| n.version), Some(0));
+
+    let patch = NotePatch {
+        title: Some("patched".to_string()),
+        version: Some(0),
+    };
+    let patched: Result<Option<Note>, Error> = notes.patch_one(&id, &patch).await;
+    let patched = patched.unwrap().expect("Note missing");
+    assert_eq!((patched.title.as_str(), patched.version), ("patched", 1));
+
+    let stale: Result<Option<Note>, Error> = notes.patch_one(&id, &patch).await;
+    assert!(matches!(
+        stale,
+        Err(RepositoryError::OptimisticLockConflict)
+    ));
+}
+
+#[tokio::test]
+async fn test_soft_delete_restore_and_purge() {
+    let notes = Notes::new();
+    let id: Result<i32, Error> = notes.insert_one(&note("old")).await;
+    let id = id.unwrap();
+
+    let deleted: Result<u64, Error> = notes.delete_one(&id).await;
+    assert_eq!(deleted.unwrap(), 1);
+    let found: Result<Option<Note>, Error> = notes.find_one(&id).await;
+    assert!(found.unwrap().is_none());
+    assert!(notes.find_one_with_deleted(&id).is_some_and(|n| n.deleted));
+
+    let again: Result<u64, Error> = notes.delete_one(&id).await;
+    assert_eq!(again.unwrap(), 0);
+
+    assert_eq!(notes.restore_one(&id), 1);
+    let found: Result<Option<Note>, Error> = notes.find_one(&id).await;
+    assert!(found.unwrap().is_some());
+
+    assert_eq!(notes.purge_one(&id), 1);
+    assert!(notes.is_empty());
+}
+
+#[tokio::test]
+async fn test_put_advances_sequence() {
+    let notes = InMemoryRepository::<Note, i32>::new();
+    notes.put(5, note("seeded"));
+    notes.put(2, note("older"));
+
+    let id: Result<i32, Error> = notes.insert_one(&note("next")).await;
+    assert_eq!(id.unwrap(), 6);
+    assert_eq!(notes.len(), 3);
+}
+
+#[tokio::test]
+async fn test_custom_id_generator() {
+    let notes = InMemoryRepository::<Note, i32>::with_id_generator(|sequence| sequence as i32 * 10);
+    notes.put(10, note("seeded"));
+
+    let duplicate: Result<i32, Error> = notes.insert_one(&note("first")).await;
+    assert!(matches!(
+        duplicate,
+        Err(RepositoryError::ConstraintViolation(_))
+    ));
+
+    let id: Result<i32, Error> = notes.insert_one(&note("second")).await;
+    assert_eq!(id.unwrap(), 20);
+}
+
+#[tokio::test]
+async fn test_erased_repository() {
+    let notes: Box<dyn rustling_data::api::Repository<Note, i32, Error = BoxError> + Send + Sync> =
+        Box::new(ErasedRepository(InMemoryRepository::<Note, i32>::new()));
+
+    let id = notes
+        .insert_one(&note("first"))
+        .await
+        .expect("Insert failed");
+    let found = notes.find_one(&id).await.expect("Find failed");
+    assert_eq!(found.map(|n| n.title), Some("first".to_string()));
+
+    let stale = Note {
+        version: 7,
+        ..note("second")
+    };
+    let conflict = notes.update_one(&id, &stale).await;
+    assert!(matches!(
+        conflict,
+        Err(RepositoryError::OptimisticLockConflict)
+    ));
+
+    assert_eq!(notes.delete_one(&id).await.expect("Delete failed"), 1);
+    assert!(notes.find_all().await.expect("Find all failed").is_empty());
+}
+
+/// Writes and reads back a note through `notes`, which must start empty.
+async fn round_trip<R: CrudRepository<Note, i32, Infallible>>(notes: &R) {
+    let id = notes
+        .insert_one(&note("first"))
+        .await
+        .expect("Insert failed");
+    let updated = notes
+        .update_one(&id, &note("second"))
+        .await
+        .expect("Update failed");
+    assert_eq!(updated.map(|n| n.title), Some("second".to_string()));
+    assert_eq!(notes.delete_one(&id).await.expect("Delete failed"), 1);
+    assert!(notes.find_all().await.expect("Find all failed").is_empty());
+}
+
+/// [`round_trip`] through the backend-independent trait.
+async fn dyn_round_trip<R: rustling_data::api::Repository<Note, i32>>(notes: &R)
+where
+    R::Error: std::fmt::Debug,
+{
+    let id = notes
+        .insert_one(&note("first"))
+        .await
+        .expect("Insert failed");
+    let updated = notes
+        .update_one(&id, &note("second"))
+        .await
+        .expect("Update failed");
+    assert_eq!(updated.map(|n| n.title), Some("second".to_string()));
+    assert_eq!(notes.delete_one(&id).await.expect("Delete failed"), 1);
+    assert!(notes.find_all().await.expect("Find all failed").is_empty());
+}
+
+#[tokio::test]
+async fn test_trait_objects_behind_pointers() {
+    use rustling_data::api::Repository;
+
+    let shared: Arc<dyn CrudRepository<Note, i32, Infallible> + Send + Sync> =
+        Arc::new(Notes::new());
+    round_trip(&shared).await;
+    let boxed: Box<dyn CrudRepository<Note, i32, Infallible> + Send + Sync> =
+        Box::new(Notes::new());
+    round_trip(&boxed).await;
+
+    dyn_round_trip(&Notes::new().into_dyn()).await;
+    let boxed: Box<dyn Repository<Note, i32, Error = BoxError>> =
+        Box::new(ErasedRepository(Notes::new()));
+    dyn_round_trip(&boxed).await;
+}
+
+#[tokio::test]
+async fn test_swappable_repository() {
+    use rustling_data::api::Repository;
+
+    let first = InMemoryRepository::<Note, i32>::new();
+    first.put(1, note("old"));
+    let second = InMemoryRepository::<Note, i32>::new();
+    second.put(1, note("new"));
+
+    let notes = Arc::new(SwappableRepository::new(first.into_dyn()));
+    let shared = Arc::clone(&notes);
+    let found = tokio::spawn(async move { shared.find_one(&1).await })
+        .await
+        .expect("Task failed");
+    assert_eq!(found.unwrap().map(|n| n.title), Some("old".to_string()));
+
+    let previous: DynRepository<Note, i32> = notes.swap(second.into_dyn());
+    let found = notes.find_one(&1).await.expect("Find failed");
+    assert_eq!(found.map(|n| n.title), Some("new".to_string()));
+
+    // Writes after the swap reach the new repository only.
+    let shared = Arc::clone(&notes);
+    let id = tokio::spawn(async move { shared.insert_one(&note("added")).await })
+        .await
+        .expect("Task failed")
+        .expect("Insert failed");
+    assert_eq!(id, 2);
+    assert_eq!(
+        notes
+            .current()
+            .find_all()
+            .await
+            .expect("Find all failed")
+            .len(),
+        2
+    );
+    assert_eq!(previous.find_all().await.expect("Find all failed").len(), 1);
+
+    // The swappable repository is a repository itself, so it can be shared as one.
+    dyn_round_trip(&SwappableRepository::new(Notes::new().into_dyn())).await;
+}
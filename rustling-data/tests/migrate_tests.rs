@@ -0,0 +1,23 @@
+#![cfg(any(feature = "postgres", feature = "mongo"))]
+
+use std::path::Path;
+
+use rustling_data::migrate::{checksum, parse_file_name};
+
+#[test]
+fn test_checksum() {
+    assert_eq!(
+        checksum(b"abc"),
+        "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+    );
+}
+
+#[test]
+fn test_parse_file_name() {
+    assert_eq!(
+        parse_file_name(Path::new("migrations/0002_add_email.sql")),
+        Some((2, "add_email".to_string()))
+    );
+    assert_eq!(parse_file_name(Path::new("add_email.sql")), None);
+    assert_eq!(parse_file_name(Path::new("v2_add_email.sql")), None);
+}
@@ -2,16 +2,25 @@
 
 use mongodb::{
     Client, Database,
-    bson::{doc, oid::ObjectId},
+    bson::{Document, doc, oid::ObjectId},
+    options::ValidationAction,
 };
+use futures_util::TryStreamExt;
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
 use std::time::Duration;
 use testcontainers_modules::{
     mongo,
-    testcontainers::{ContainerAsync, runners::AsyncRunner},
+    testcontainers::{ContainerAsync, Image, core::WaitFor, runners::AsyncRunner},
 };
 use tokio::time::sleep;
-use rustling_data::MongoDriver;
+use rustling_data::{
+    ChangeOperation, MongoDriver, MongoResumeTokenStore, ResumeTokenStore, ValidatorOptions,
+    WatchOptions,
+};
+use rustling_data::api::{FieldName, RepositoryError, SoftDelete};
+use rustling_data::migrate::mongo::{MongoMigration, MongoMigrator};
+use rustling_data::schema::Index;
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
 struct User {
@@ -22,25 +31,21 @@ struct User {
 }
 
 pub async fn setup_mongo() -> (Client, Database, ContainerAsync<mongo::Mongo>) {
-    let container = mongo::Mongo::default().start().await.unwrap();
+    let container = mongo::Mongo.start().await.unwrap();
     let port = container.get_host_port_ipv4(27017).await.unwrap();
     let uri = format!("mongodb://localhost:{port}/testdb");
 
     let mut retries = 10;
     loop {
-        match Client::with_uri_str(&uri).await {
-            Ok(client) => {
-                if client
-                    .database("admin")
-                    .run_command(doc! {"ping": 1})
-                    .await
-                    .is_ok()
-                {
-                    let db = client.database("testdb");
-                    return (client, db, container);
-                }
-            }
-            Err(_) => {}
+        if let Ok(client) = Client::with_uri_str(&uri).await
+            && client
+                .database("admin")
+                .run_command(doc! {"ping": 1})
+                .await
+                .is_ok()
+        {
+            let db = client.database("testdb");
+            return (client, db, container);
         }
 
         if retries == 0 {
@@ -166,6 +171,79 @@ async fn test_update_one() {
 }
 
 
+#[tokio::test]
+async fn test_patch_one() {
+    let (_client, _db, _container) = setup_mongo().await;
+    let mongo_repo = MongoDriver::new(_client.clone(), _db.name().to_string());
+
+    mongo_repo
+        .insert_one(
+            "users",
+            &User {
+                id: None,
+                name: "Frank".into(),
+                email: "frank@example.com".into(),
+            },
+        )
+        .await
+        .unwrap();
+
+    let patched: User = mongo_repo
+        .patch_one(
+            "users",
+            doc! { "email": "frank@example.com" },
+            doc! { "$set": { "name": "Frankie" } },
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(patched.name, "Frankie");
+    assert_eq!(patched.email, "frank@example.com");
+
+    drop(_container);
+}
+
+#[tokio::test]
+async fn test_patch_one_versioned() {
+    let (_client, _db, _container) = setup_mongo().await;
+    let mongo_repo = MongoDriver::new(_client.clone(), _db.name().to_string());
+
+    let id = mongo_repo
+        .insert_one("users", &doc! { "name": "Grace", "version": 0 })
+        .await
+        .unwrap();
+
+    let patched: Document = mongo_repo
+        .patch_one_versioned(
+            "users",
+            doc! { "_id": id },
+            doc! { "$set": { "name": "Gracie" } },
+            "version",
+            Some(0),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(patched.get_str("name").unwrap(), "Gracie");
+    assert_eq!(patched.get_i32("version").unwrap(), 1);
+
+    let stale = mongo_repo
+        .patch_one_versioned::<Document>(
+            "users",
+            doc! { "_id": id },
+            doc! { "$set": { "name": "Grace" } },
+            "version",
+            Some(0),
+        )
+        .await;
+
+    assert!(matches!(stale, Err(RepositoryError::OptimisticLockConflict)));
+
+    drop(_container);
+}
+
 #[tokio::test]
 async fn test_delete_one() {
     let (_client, _db, _container) = setup_mongo().await;
@@ -193,4 +271,343 @@ async fn test_delete_one() {
     assert!(users.is_empty());
 
     drop(_container);
-}
\ No newline at end of file
+}
+
+#[tokio::test]
+async fn test_soft_delete_and_restore() {
+    let (_client, _db, _container) = setup_mongo().await;
+    let mongo_repo = MongoDriver::new(_client.clone(), _db.name().to_string());
+    let soft_delete = SoftDelete::Flag(FieldName {
+        column: "deleted",
+        key: "deleted",
+    });
+
+    mongo_repo
+        .insert_one(
+            "users",
+            &User {
+                id: None,
+                name: "Judy".into(),
+                email: "judy@example.com".into(),
+            },
+        )
+        .await
+        .unwrap();
+
+    let deleted = mongo_repo
+        .soft_delete_one("users", doc! { "email": "judy@example.com" }, &soft_delete)
+        .await
+        .unwrap();
+    assert_eq!(deleted, 1);
+
+    let live: Vec<User> = mongo_repo
+        .find_where("users", MongoDriver::not_deleted(&soft_delete))
+        .await
+        .unwrap();
+    assert!(live.is_empty());
+
+    let all: Vec<User> = mongo_repo.find_all("users").await.unwrap();
+    assert_eq!(all.len(), 1);
+
+    let restored = mongo_repo
+        .restore_one("users", doc! { "email": "judy@example.com" }, &soft_delete)
+        .await
+        .unwrap();
+    assert_eq!(restored, 1);
+
+    let live: Vec<User> = mongo_repo
+        .find_where("users", MongoDriver::not_deleted(&soft_delete))
+        .await
+        .unwrap();
+    assert_eq!(live.len(), 1);
+
+    drop(_container);
+}
+
+/// Single-node replica set; change streams are not available on a standalone server.
+struct MongoReplicaSet;
+
+impl Image for MongoReplicaSet {
+    fn name(&self) -> &str {
+        "mongo"
+    }
+
+    fn tag(&self) -> &str {
+        "5.0.6"
+    }
+
+    fn ready_conditions(&self) -> Vec<WaitFor> {
+        vec![WaitFor::message_on_stdout("Waiting for connections")]
+    }
+
+    fn cmd(&self) -> impl IntoIterator<Item = impl Into<Cow<'_, str>>> {
+        ["--replSet", "rs0", "--bind_ip_all"]
+    }
+}
+
+async fn setup_mongo_replica_set() -> (Client, Database, ContainerAsync<MongoReplicaSet>) {
+    let container = MongoReplicaSet.start().await.unwrap();
+    let port = container.get_host_port_ipv4(27017).await.unwrap();
+    let uri = format!("mongodb://localhost:{port}/testdb?directConnection=true");
+    let client = Client::with_uri_str(&uri).await.unwrap();
+    let admin = client.database("admin");
+    admin
+        .run_command(doc! {
+            "replSetInitiate": {
+                "_id": "rs0",
+                "members": [{ "_id": 0, "host": "localhost:27017" }],
+            }
+        })
+        .await
+        .unwrap();
+
+    for _ in 0..30 {
+        let hello = admin.run_command(doc! { "hello": 1 }).await.unwrap();
+        if hello.get_bool("isWritablePrimary").unwrap_or(false) {
+            let db = client.database("testdb");
+            return (client, db, container);
+        }
+        sleep(Duration::from_secs(1)).await;
+    }
+    panic!("Replica set did not elect a primary in time");
+}
+
+#[tokio::test]
+async fn test_watch_and_resume() {
+    let (client, db, _container) = setup_mongo_replica_set().await;
+    let mongo_repo = MongoDriver::new(client.clone(), db.name().to_string());
+    let tokens = MongoResumeTokenStore::new(&mongo_repo, "resume_tokens", "test");
+    assert!(tokens.load().await.unwrap().is_none());
+
+    let options = WatchOptions::new()
+        .operations([ChangeOperation::Insert, ChangeOperation::Update])
+        .full_document(true);
+    let mut events = mongo_repo.watch::<User>("users", options).await.unwrap();
+
+    let id = mongo_repo
+        .insert_one(
+            "users",
+            &User {
+                id: None,
+                name: "Kim".into(),
+                email: "kim@example.com".into(),
+            },
+        )
+        .await
+        .unwrap();
+    mongo_repo
+        .patch_one::<User>("users", doc! { "_id": id }, doc! { "$set": { "name": "Kimberly" } })
+        .await
+        .unwrap();
+    mongo_repo.delete_one("users", doc! { "_id": id }).await.unwrap();
+
+    let inserted = events.try_next().await.unwrap().unwrap();
+    assert_eq!(inserted.operation, ChangeOperation::Insert);
+    assert_eq!(inserted.id, Some(id.into()));
+    assert_eq!(inserted.document.unwrap().name, "Kim");
+    tokens.save(&inserted.resume_token).await.unwrap();
+
+    let updated = events.try_next().await.unwrap().unwrap();
+    assert_eq!(updated.operation, ChangeOperation::Update);
+    // Looked up after the delete, so the document is gone.
+    assert!(updated.document.is_none());
+    drop(events);
+
+    // A restarted consumer continues after the saved event, with the default operations.
+    let options = WatchOptions::new().resume_after(tokens.load().await.unwrap());
+    let mut events = mongo_repo.watch::<User>("users", options).await.unwrap();
+    let next = events.try_next().await.unwrap().unwrap();
+    assert_eq!(next.operation, ChangeOperation::Update);
+    let next = events.try_next().await.unwrap().unwrap();
+    assert_eq!(next.operation, ChangeOperation::Delete);
+
+    drop(_container);
+}
+
+#[tokio::test]
+async fn test_migrator() {
+    let (client, db, _container) = setup_mongo().await;
+    let mongo_repo = MongoDriver::new(client.clone(), db.name().to_string());
+
+    let create_index = MongoMigration::from_json(
+        1,
+        "users_email_index",
+        r#"[{"createIndexes": "users", "indexes": [{"key": {"email": 1}, "name": "email_1", "unique": true}]}]"#,
+    )
+    .unwrap();
+    let backfill = MongoMigration::function(2, "backfill_users", |db: Database| async move {
+        db.collection::<Document>("users")
+            .insert_one(doc! { "name": "Seed", "email": "seed@example.com" })
+            .await
+            .map(|_| ())
+    });
+    let migrator = MongoMigrator::new()
+        .migration(backfill)
+        .migration(create_index);
+
+    let (first, second) = tokio::join!(migrator.run(&mongo_repo), migrator.run(&mongo_repo));
+    let mut applied: Vec<_> = first.unwrap().into_iter().chain(second.unwrap()).collect();
+    applied.sort_by_key(|m| m.version);
+    assert_eq!(
+        applied.iter().map(|m| m.version).collect::<Vec<_>>(),
+        vec![1, 2]
+    );
+    assert_eq!(migrator.applied(&mongo_repo).await.unwrap(), applied);
+    assert_eq!(
+        db.collection::<Document>("users")
+            .count_documents(doc! {})
+            .await
+            .unwrap(),
+        1
+    );
+
+    let changed = MongoMigrator::new().migration(MongoMigration::commands(
+        1,
+        "users_email_index",
+        vec![doc! { "ping": 1 }],
+    ));
+    match changed.run(&mongo_repo).await {
+        Err(RepositoryError::SchemaMismatch(msg)) => assert!(msg.contains("users_email_index")),
+        other => panic!("Unexpected result: {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_migrator_fails_after_losing_lock() {
+    let (client, db, _container) = setup_mongo().await;
+    let mongo_repo = MongoDriver::new(client.clone(), db.name().to_string());
+
+    // Stands in for a runner taking over the lock while the migration runs.
+    let take_over = MongoMigration::function(1, "take_over_lock", |db: Database| async move {
+        db.collection::<Document>("_rustling_migrations")
+            .update_one(
+                doc! { "_id": "lock" },
+                doc! { "$set": { "owner": ObjectId::new() } },
+            )
+            .await
+            .map(|_| ())
+    });
+    let migrator = MongoMigrator::new().migration(take_over);
+
+    match migrator.run(&mongo_repo).await {
+        Err(RepositoryError::Other(msg)) => assert!(msg.contains("lock"), "{}", msg),
+        other => panic!("Unexpected result: {:?}", other),
+    }
+    assert!(migrator.applied(&mongo_repo).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_ensure_indexes() {
+    let (client, db, _container) = setup_mongo().await;
+    let mongo_repo = MongoDriver::new(client.clone(), db.name().to_string());
+    const EMAIL: FieldName = FieldName {
+        column: "email",
+        key: "email",
+    };
+    const NAME: FieldName = FieldName {
+        column: "name",
+        key: "name",
+    };
+    const EXPIRES_AT: FieldName = FieldName {
+        column: "expires_at",
+        key: "expiresAt",
+    };
+    let indexes = [
+        Index {
+            unique: true,
+            sparse: true,
+            ..Index::new(&[EMAIL])
+        },
+        Index {
+            text: true,
+            ..Index::new(&[NAME])
+        },
+        Index {
+            name: Some("expiry"),
+            ttl: Some(3600),
+            partial: Some(r#"{"name": {"$exists": true}}"#),
+            ..Index::new(&[EXPIRES_AT])
+        },
+    ];
+
+    mongo_repo.ensure_indexes("users", &indexes).await.unwrap();
+    mongo_repo.ensure_indexes("users", &indexes).await.unwrap();
+
+    let mut created: Vec<_> = db
+        .collection::<Document>("users")
+        .list_indexes()
+        .await
+        .unwrap()
+        .try_collect::<Vec<_>>()
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|model| model.options.unwrap())
+        .collect();
+    created.sort_by_key(|options| options.name.clone());
+    let names: Vec<_> = created.iter().map(|o| o.name.clone().unwrap()).collect();
+    assert_eq!(names, vec!["_id_", "email_1", "expiry", "name_text"]);
+    assert_eq!(created[1].unique, Some(true));
+    assert_eq!(created[2].expire_after, Some(Duration::from_secs(3600)));
+
+    // The unique index is enforced.
+    for _ in 0..2 {
+        mongo_repo
+            .insert_one(
+                "users",
+                &User {
+                    id: None,
+                    name: "Kim".into(),
+                    email: "kim@example.com".into(),
+                },
+            )
+            .await
+            .ok();
+    }
+    assert_eq!(
+        db.collection::<Document>("users")
+            .count_documents(doc! {})
+            .await
+            .unwrap(),
+        1
+    );
+}
+
+#[tokio::test]
+async fn test_apply_json_schema() {
+    let (client, db, _container) = setup_mongo().await;
+    let mongo_repo = MongoDriver::new(client.clone(), db.name().to_string());
+    let schema = doc! {
+        "bsonType": "object",
+        "required": ["name", "email"],
+        "properties": {
+            "name": { "bsonType": "string" },
+            "email": { "bsonType": "string" },
+        },
+    };
+    let invalid = doc! { "name": "Kim", "email": 42 };
+
+    // Creates the collection with the validator.
+    mongo_repo
+        .apply_json_schema("users", schema.clone(), ValidatorOptions::new())
+        .await
+        .unwrap();
+    let users = db.collection::<Document>("users");
+    assert!(users.insert_one(invalid.clone()).await.is_err());
+    users
+        .insert_one(doc! { "name": "Kim", "email": "kim@example.com" })
+        .await
+        .unwrap();
+
+    // Updates the existing collection.
+    mongo_repo
+        .apply_json_schema(
+            "users",
+            schema,
+            ValidatorOptions::new().action(ValidationAction::Warn),
+        )
+        .await
+        .unwrap();
+    users.insert_one(invalid).await.unwrap();
+    assert_eq!(users.count_documents(doc! {}).await.unwrap(), 2);
+}
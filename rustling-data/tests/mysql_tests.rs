@@ -0,0 +1,251 @@
+#![cfg(feature = "mysql")]
+
+use rustling_data::api::{FieldName, RepositoryError, SoftDelete};
+use rustling_data::drivers::mysql::quote;
+use rustling_data::{MySqlDriver, MySqlValues};
+use sqlx::mysql::MySqlPoolOptions;
+use sqlx::{FromRow, MySqlPool};
+use testcontainers_modules::mysql;
+use testcontainers_modules::testcontainers::ContainerAsync;
+use testcontainers_modules::testcontainers::runners::AsyncRunner;
+
+#[derive(Debug, FromRow, PartialEq)]
+struct User {
+    id: i32,
+    name: String,
+    email: String,
+}
+
+const USER_COLUMNS: &[&str] = &["id", "name", "email"];
+
+/// Helper to start a MySQL container for a single test.
+async fn start_mysql_container() -> (MySqlPool, ContainerAsync<mysql::Mysql>) {
+    let container = mysql::Mysql::default()
+        .start()
+        .await
+        .expect("Failed to start MySQL container");
+
+    let host_port = container
+        .get_host_port_ipv4(3306)
+        .await
+        .expect("Failed to get mapped port");
+
+    let pool = MySqlPoolOptions::new()
+        .max_connections(5)
+        .acquire_timeout(std::time::Duration::from_secs(10))
+        .connect(&format!("mysql://root@127.0.0.1:{}/test", host_port))
+        .await
+        .expect("Failed to connect to MySQL");
+
+    // `order` is a reserved word, so queries only work if the driver quotes it.
+    sqlx::query(
+        r#"
+        CREATE TABLE users (
+            id INT AUTO_INCREMENT PRIMARY KEY,
+            name VARCHAR(255) NOT NULL,
+            email VARCHAR(255) NOT NULL UNIQUE,
+            `order` INT NOT NULL DEFAULT 0,
+            version INT NOT NULL DEFAULT 0,
+            deleted_at TIMESTAMP NULL
+        )
+        "#,
+    )
+    .execute(&pool)
+    .await
+    .expect("Failed to create table");
+
+    (pool, container)
+}
+
+#[test]
+fn test_quote() {
+    assert_eq!(quote("order"), "`order`");
+    assert_eq!(quote("shop.order"), "`shop`.`order`");
+    assert_eq!(quote("we`ird"), "`we``ird`");
+}
+
+#[tokio::test]
+async fn test_insert_and_find() {
+    let (pool, container) = start_mysql_container().await;
+
+    let mut values = MySqlValues::new();
+    values.push("Alice").push("alice@example.com").push(1);
+    let first = MySqlDriver::insert(&pool, "users", &["name", "email", "order"], values)
+        .await
+        .expect("Insert failed");
+    let second = MySqlDriver::insert(
+        &pool,
+        "users",
+        &["name", "email"],
+        vec![&"Bob", &"bob@example.com"],
+    )
+    .await
+    .expect("Insert failed");
+    assert_eq!(second, first + 1);
+
+    let user: Option<User> = MySqlDriver::find_one(&pool, "users", USER_COLUMNS, "id", first)
+        .await
+        .expect("Find one failed");
+    assert_eq!(user.map(|u| u.name), Some("Alice".to_string()));
+
+    let ordered: Vec<User> =
+        MySqlDriver::find_where(&pool, "users", USER_COLUMNS, "`order` = ?", vec![&1])
+            .await
+            .expect("Find where failed");
+    assert_eq!(ordered.len(), 1);
+
+    let duplicate = MySqlDriver::insert(
+        &pool,
+        "users",
+        &["name", "email"],
+        vec![&"Bobby", &"bob@example.com"],
+    )
+    .await;
+    assert!(matches!(
+        duplicate,
+        Err(RepositoryError::ConstraintViolation(_))
+    ));
+
+    container.rm().await.expect("Failed to remove container");
+}
+
+#[tokio::test]
+async fn test_upsert() {
+    let (pool, container) = start_mysql_container().await;
+
+    let inserted = MySqlDriver::upsert(
+        &pool,
+        "users",
+        &["name", "email"],
+        vec![&"Carol", &"carol@example.com"],
+        &["name"],
+    )
+    .await
+    .expect("Upsert failed");
+    assert_eq!(inserted, 1);
+
+    let updated = MySqlDriver::upsert(
+        &pool,
+        "users",
+        &["name", "email"],
+        vec![&"Caroline", &"carol@example.com"],
+        &["name"],
+    )
+    .await
+    .expect("Upsert failed");
+    assert_eq!(updated, 2);
+
+    // Found but unchanged counts like an insert.
+    let unchanged = MySqlDriver::upsert(
+        &pool,
+        "users",
+        &["name", "email"],
+        vec![&"Caroline", &"carol@example.com"],
+        &["name"],
+    )
+    .await
+    .expect("Upsert failed");
+    assert_eq!(unchanged, 1);
+
+    let users: Vec<User> = MySqlDriver::find_all(&pool, "users", USER_COLUMNS)
+        .await
+        .expect("Find all failed");
+    assert_eq!(users.len(), 1);
+    assert_eq!(users[0].name, "Caroline");
+
+    container.rm().await.expect("Failed to remove container");
+}
+
+#[tokio::test]
+async fn test_update_versioned_and_delete() {
+    let (pool, container) = start_mysql_container().await;
+
+    let id = MySqlDriver::insert(
+        &pool,
+        "users",
+        &["name", "email"],
+        vec![&"Grace", &"grace@example.com"],
+    )
+    .await
+    .expect("Insert failed");
+
+    let stale_rows = MySqlDriver::update_versioned(
+        &pool,
+        "users",
+        "id",
+        id,
+        &["name"],
+        vec![&"Gracie"],
+        "version",
+        Some(1),
+        None,
+    )
+    .await
+    .expect("Update failed");
+    assert_eq!(stale_rows, 0);
+
+    let updated_rows = MySqlDriver::update_versioned(
+        &pool,
+        "users",
+        "id",
+        id,
+        &["name"],
+        vec![&"Gracie"],
+        "version",
+        Some(0),
+        None,
+    )
+    .await
+    .expect("Update failed");
+    assert_eq!(updated_rows, 1);
+
+    let deleted_rows = MySqlDriver::delete(&pool, "users", "id", id)
+        .await
+        .expect("Delete failed");
+    assert_eq!(deleted_rows, 1);
+
+    container.rm().await.expect("Failed to remove container");
+}
+
+#[tokio::test]
+async fn test_soft_delete_and_restore() {
+    let (pool, container) = start_mysql_container().await;
+
+    let soft_delete = SoftDelete::Timestamp(FieldName {
+        column: "deleted_at",
+        key: "deleted_at",
+    });
+    let not_deleted = MySqlDriver::not_deleted(&soft_delete);
+
+    let id = MySqlDriver::insert(
+        &pool,
+        "users",
+        &["name", "email"],
+        vec![&"Judy", &"judy@example.com"],
+    )
+    .await
+    .expect("Insert failed");
+
+    let deleted_rows = MySqlDriver::soft_delete(&pool, "users", "id", id, &soft_delete)
+        .await
+        .expect("Soft delete failed");
+    assert_eq!(deleted_rows, 1);
+
+    let live: Vec<User> = MySqlDriver::find_where(
+        &pool,
+        "users",
+        USER_COLUMNS,
+        &not_deleted,
+        MySqlValues::new(),
+    )
+    .await
+    .expect("Find where failed");
+    assert!(live.is_empty());
+
+    let restored_rows = MySqlDriver::restore(&pool, "users", "id", id, &soft_delete)
+        .await
+        .expect("Restore failed");
+    assert_eq!(restored_rows, 1);
+
+    container.rm().await.expect("Failed to remove container");
+}
@@ -1,6 +1,16 @@
 #![cfg(feature = "postgres")]
 
-use rustling_data::PostgresDriver;
+use futures_util::TryStreamExt;
+use rustling_data::{
+    LockMode, Outbox, OutboxMessage, OutboxPublisher, OutboxRelay, PgValues, PostgresDriver,
+    RowChange, RowOperation,
+};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use rustling_data::api::{FieldName, RepositoryError, SoftDelete};
+use rustling_data::migrate::postgres::{PostgresMigrator, SqlMigration};
+use rustling_data::schema::{Column, Index, TableSchema};
 use sqlx::postgres::PgPoolOptions;
 use sqlx::{FromRow, PgPool, Postgres, Transaction};
 use testcontainers_modules::postgres;
@@ -14,6 +24,8 @@ struct User {
     email: String,
 }
 
+const USER_COLUMNS: &[&str] = &["id", "name", "email"];
+
 /// Helper to start a Postgres container for a single test.
 async fn start_postgres_container() -> (PgPool, ContainerAsync<postgres::Postgres>) {
     let container = postgres::Postgres::default()
@@ -97,7 +109,7 @@ async fn test_find_all() {
     .await
     .expect("Insert failed");
 
-    let users: Vec<User> = PostgresDriver::find_all(tx.as_mut(), "users")
+    let users: Vec<User> = PostgresDriver::find_all(tx.as_mut(), "users", USER_COLUMNS)
         .await
         .expect("Find all failed");
 
@@ -123,7 +135,7 @@ async fn test_find_one() {
     .await
     .expect("Insert failed");
 
-    let user: Option<User> = PostgresDriver::find_one(tx.as_mut(), "users", "id", id)
+    let user: Option<User> = PostgresDriver::find_one(tx.as_mut(), "users", USER_COLUMNS, "id", id)
         .await
         .expect("Find one failed");
 
@@ -158,13 +170,14 @@ async fn test_update() {
         id,
         &["name", "email"],
         vec![&"David", &"david@example.com"],
+        None,
     )
     .await
     .expect("Update failed");
 
     assert_eq!(updated_rows, 1);
 
-    let updated_user: User = PostgresDriver::find_one(tx.as_mut(), "users", "id", id)
+    let updated_user: User = PostgresDriver::find_one(tx.as_mut(), "users", USER_COLUMNS, "id", id)
         .await
         .expect("Find one failed")
         .expect("User not found");
@@ -176,6 +189,159 @@ async fn test_update() {
     container.rm().await.expect("Failed to remove container");
 }
 
+#[tokio::test]
+async fn test_update_subset_of_columns() {
+    let (pool, container) = start_postgres_container().await;
+
+    let mut tx = start_test_transaction(&pool).await;
+
+    let id = PostgresDriver::insert(
+        tx.as_mut(),
+        "users",
+        &["name", "email"],
+        vec![&"Frank", &"frank@example.com"],
+    )
+    .await
+    .expect("Insert failed");
+
+    let mut values = PgValues::new();
+    values.push("frankie@example.com");
+    let updated_rows =
+        PostgresDriver::update(tx.as_mut(), "users", "id", id, &["email"], values, None)
+            .await
+            .expect("Update failed");
+
+    assert_eq!(updated_rows, 1);
+
+    let updated_user: User = PostgresDriver::find_one(tx.as_mut(), "users", USER_COLUMNS, "id", id)
+        .await
+        .expect("Find one failed")
+        .expect("User not found");
+
+    assert_eq!(updated_user.name, "Frank");
+    assert_eq!(updated_user.email, "frankie@example.com");
+
+    tx.rollback().await.unwrap();
+    container.rm().await.expect("Failed to remove container");
+}
+
+#[tokio::test]
+async fn test_update_versioned() {
+    let (pool, container) = start_postgres_container().await;
+
+    let mut tx = start_test_transaction(&pool).await;
+
+    sqlx::query("ALTER TABLE users ADD COLUMN version INT NOT NULL DEFAULT 0")
+        .execute(tx.as_mut())
+        .await
+        .expect("Failed to add version column");
+
+    let id = PostgresDriver::insert(
+        tx.as_mut(),
+        "users",
+        &["name", "email"],
+        vec![&"Grace", &"grace@example.com"],
+    )
+    .await
+    .expect("Insert failed");
+
+    let stale_rows = PostgresDriver::update_versioned(
+        tx.as_mut(),
+        "users",
+        "id",
+        id,
+        &["name"],
+        vec![&"Gracie"],
+        "version",
+        Some(1),
+        None,
+    )
+    .await
+    .expect("Update failed");
+
+    assert_eq!(stale_rows, 0);
+
+    let updated_rows = PostgresDriver::update_versioned(
+        tx.as_mut(),
+        "users",
+        "id",
+        id,
+        &["name"],
+        vec![&"Gracie"],
+        "version",
+        Some(0),
+        None,
+    )
+    .await
+    .expect("Update failed");
+
+    assert_eq!(updated_rows, 1);
+
+    let version: i32 = sqlx::query_scalar("SELECT version FROM users WHERE id = $1")
+        .bind(id)
+        .fetch_one(tx.as_mut())
+        .await
+        .expect("Failed to read version");
+
+    assert_eq!(version, 1);
+
+    tx.rollback().await.unwrap();
+    container.rm().await.expect("Failed to remove container");
+}
+
+#[tokio::test]
+async fn test_find_for_update_locks() {
+    let (pool, container) = start_postgres_container().await;
+
+    let locked_id = PostgresDriver::insert(
+        &pool,
+        "users",
+        &["name", "email"],
+        vec![&"Heidi", &"heidi@example.com"],
+    )
+    .await
+    .expect("Insert failed");
+    PostgresDriver::insert(&pool, "users", &["name", "email"], vec![&"Ivan", &"ivan@example.com"])
+        .await
+        .expect("Insert failed");
+
+    let mut tx = start_test_transaction(&pool).await;
+    let locked: Option<User> =
+        PostgresDriver::find_one_for_update(tx.as_mut(), "users", USER_COLUMNS, "id", locked_id)
+            .await
+            .expect("Find one for update failed");
+    assert!(locked.is_some());
+
+    let mut other_tx = start_test_transaction(&pool).await;
+    let unlocked: Vec<User> = PostgresDriver::find_where_for_update(
+        other_tx.as_mut(),
+        "users",
+        USER_COLUMNS,
+        "email LIKE $1",
+        vec![&"%@example.com"],
+        LockMode::for_update().skip_locked(),
+    )
+    .await
+    .expect("Find where for update failed");
+    assert_eq!(unlocked.len(), 1);
+    assert_eq!(unlocked[0].name, "Ivan");
+
+    let nowait = PostgresDriver::find_where_for_update::<User, _>(
+        other_tx.as_mut(),
+        "users",
+        USER_COLUMNS,
+        "id = $1",
+        vec![&locked_id],
+        LockMode::for_share().nowait(),
+    )
+    .await;
+    assert!(nowait.is_err());
+
+    other_tx.rollback().await.unwrap();
+    tx.rollback().await.unwrap();
+    container.rm().await.expect("Failed to remove container");
+}
+
 #[tokio::test]
 async fn test_delete() {
     let (pool, container) = start_postgres_container().await;
@@ -197,7 +363,7 @@ async fn test_delete() {
 
     assert_eq!(deleted_rows, 1);
 
-    let remaining: Vec<User> = PostgresDriver::find_all(tx.as_mut(), "users")
+    let remaining: Vec<User> = PostgresDriver::find_all(tx.as_mut(), "users", USER_COLUMNS)
         .await
         .expect("Find all failed");
 
@@ -206,3 +372,404 @@ async fn test_delete() {
     tx.rollback().await.unwrap();
     container.rm().await.expect("Failed to remove container");
 }
+
+#[tokio::test]
+async fn test_verify_columns() {
+    let (pool, container) = start_postgres_container().await;
+
+    PostgresDriver::verify_columns(&pool, "users", USER_COLUMNS)
+        .await
+        .expect("Mapped columns should exist");
+
+    let err = PostgresDriver::verify_columns(&pool, "users", &["id", "name", "nickname"])
+        .await
+        .expect_err("Missing column should be reported");
+
+    match err {
+        RepositoryError::SchemaMismatch(msg) => assert!(msg.contains("nickname")),
+        other => panic!("Unexpected error: {:?}", other),
+    }
+
+    container.rm().await.expect("Failed to remove container");
+}
+
+#[tokio::test]
+async fn test_verify_schema() {
+    let (pool, container) = start_postgres_container().await;
+    const USERS: TableSchema = TableSchema {
+        name: "users",
+        columns: &[
+            Column {
+                primary_key: true,
+                ..Column::new("id", "INTEGER")
+            },
+            Column::new("name", "TEXT"),
+            Column::new("email", "VARCHAR(255)"),
+        ],
+        indexes: &[],
+    };
+
+    PostgresDriver::verify_schema(&pool, "users", &USERS)
+        .await
+        .expect("Schema should match");
+
+    sqlx::query("ALTER TABLE users ALTER COLUMN email DROP NOT NULL")
+        .execute(&pool)
+        .await
+        .unwrap();
+    match PostgresDriver::verify_schema(&pool, "users", &USERS).await {
+        Err(RepositoryError::SchemaMismatch(msg)) => {
+            assert!(msg.contains("column email is nullable"), "{}", msg)
+        }
+        other => panic!("Unexpected result: {:?}", other),
+    }
+    assert!(matches!(
+        PostgresDriver::verify_schema(&pool, "missing", &USERS).await,
+        Err(RepositoryError::SchemaMismatch(_))
+    ));
+
+    container.rm().await.expect("Failed to remove container");
+}
+
+#[tokio::test]
+async fn test_create_and_drop_table() {
+    let (pool, container) = start_postgres_container().await;
+    const PROFILES: TableSchema = TableSchema {
+        name: "profiles",
+        columns: &[
+            Column {
+                primary_key: true,
+                ..Column::new("id", "INTEGER")
+            },
+            Column::new("handle", "TEXT"),
+            Column {
+                nullable: true,
+                ..Column::new("bio", "TEXT")
+            },
+        ],
+        indexes: &[Index {
+            unique: true,
+            ..Index::new(&[FieldName {
+                column: "handle",
+                key: "handle",
+            }])
+        }],
+    };
+
+    PostgresDriver::create_table(&pool, "profiles", &PROFILES)
+        .await
+        .expect("Failed to create table");
+    // Creating it again is a no-op.
+    PostgresDriver::create_table(&pool, "profiles", &PROFILES)
+        .await
+        .expect("Failed to create table twice");
+    PostgresDriver::verify_columns(&pool, "profiles", &["id", "handle", "bio"])
+        .await
+        .expect("Columns should exist");
+
+    sqlx::query("INSERT INTO profiles (handle) VALUES ('bob')")
+        .execute(&pool)
+        .await
+        .unwrap();
+    let duplicate = sqlx::query("INSERT INTO profiles (handle) VALUES ('bob')")
+        .execute(&pool)
+        .await;
+    assert!(duplicate.is_err(), "handle should be unique");
+
+    PostgresDriver::drop_table(&pool, "profiles").await.unwrap();
+    assert!(
+        PostgresDriver::verify_columns(&pool, "profiles", &["id"])
+            .await
+            .is_err()
+    );
+
+    container.rm().await.expect("Failed to remove container");
+}
+
+#[tokio::test]
+async fn test_ensure_indexes() {
+    let (pool, container) = start_postgres_container().await;
+    const NAME: FieldName = FieldName {
+        column: "name",
+        key: "name",
+    };
+    const EMAIL: FieldName = FieldName {
+        column: "email",
+        key: "email",
+    };
+    let indexes = [
+        Index {
+            unique: true,
+            ..Index::new(&[EMAIL])
+        },
+        Index {
+            name: Some("users_by_name"),
+            predicate: Some("email <> ''"),
+            ..Index::new(&[NAME, EMAIL])
+        },
+        Index {
+            text: true,
+            ..Index::new(&[NAME])
+        },
+    ];
+
+    PostgresDriver::ensure_indexes(&pool, "users", &indexes)
+        .await
+        .expect("Failed to create indexes");
+    PostgresDriver::ensure_indexes(&pool, "users", &indexes)
+        .await
+        .expect("Creating indexes again should be a no-op");
+
+    let created: Vec<String> = sqlx::query_scalar(
+        "SELECT indexname::text FROM pg_indexes WHERE tablename = 'users' ORDER BY indexname",
+    )
+    .fetch_all(&pool)
+    .await
+    .unwrap();
+    assert_eq!(created, vec!["users_by_name", "users_email_idx", "users_pkey"]);
+
+    container.rm().await.expect("Failed to remove container");
+}
+
+#[tokio::test]
+async fn test_soft_delete_and_restore() {
+    let (pool, container) = start_postgres_container().await;
+
+    let mut tx = start_test_transaction(&pool).await;
+
+    sqlx::query("ALTER TABLE users ADD COLUMN deleted_at TIMESTAMPTZ")
+        .execute(tx.as_mut())
+        .await
+        .expect("Failed to add deleted_at column");
+    let soft_delete = SoftDelete::Timestamp(FieldName {
+        column: "deleted_at",
+        key: "deleted_at",
+    });
+    let not_deleted = PostgresDriver::not_deleted(&soft_delete);
+
+    let id = PostgresDriver::insert(
+        tx.as_mut(),
+        "users",
+        &["name", "email"],
+        vec![&"Judy", &"judy@example.com"],
+    )
+    .await
+    .expect("Insert failed");
+
+    let deleted_rows = PostgresDriver::soft_delete(tx.as_mut(), "users", "id", id, &soft_delete)
+        .await
+        .expect("Soft delete failed");
+    assert_eq!(deleted_rows, 1);
+
+    let live: Vec<User> =
+        PostgresDriver::find_where(tx.as_mut(), "users", USER_COLUMNS, &not_deleted, PgValues::new())
+            .await
+            .expect("Find where failed");
+    assert!(live.is_empty());
+
+    let all: Vec<User> = PostgresDriver::find_all(tx.as_mut(), "users", USER_COLUMNS)
+        .await
+        .expect("Find all failed");
+    assert_eq!(all.len(), 1);
+
+    let restored_rows = PostgresDriver::restore(tx.as_mut(), "users", "id", id, &soft_delete)
+        .await
+        .expect("Restore failed");
+    assert_eq!(restored_rows, 1);
+
+    let live: Vec<User> =
+        PostgresDriver::find_where(tx.as_mut(), "users", USER_COLUMNS, &not_deleted, PgValues::new())
+            .await
+            .expect("Find where failed");
+    assert_eq!(live.len(), 1);
+
+    tx.rollback().await.unwrap();
+    container.rm().await.expect("Failed to remove container");
+}
+
+/// Publisher recording topics, or failing every message when `fail` is set.
+struct RecordingPublisher {
+    topics: Arc<Mutex<Vec<String>>>,
+    fail: bool,
+}
+
+#[async_trait::async_trait]
+impl OutboxPublisher for RecordingPublisher {
+    async fn publish(&self, message: &OutboxMessage) -> Result<(), String> {
+        if self.fail {
+            return Err("broker unavailable".to_string());
+        }
+        self.topics.lock().unwrap().push(message.topic.clone());
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn test_outbox_relay() {
+    let (pool, container) = start_postgres_container().await;
+    let outbox = Outbox::default();
+    outbox.create_table(&pool).await.expect("Create outbox failed");
+
+    // Committed together with the entity change.
+    let mut tx = start_test_transaction(&pool).await;
+    let id = PostgresDriver::insert(tx.as_mut(), "users", &["name", "email"], vec![&"Kim", &"kim@example.com"])
+        .await
+        .expect("Insert failed");
+    outbox
+        .enqueue(tx.as_mut(), "user.created", &HashMap::from([("id", id)]))
+        .await
+        .expect("Enqueue failed");
+    tx.commit().await.unwrap();
+
+    // Rolled back together with the entity change.
+    let mut tx = start_test_transaction(&pool).await;
+    outbox
+        .enqueue(tx.as_mut(), "user.discarded", &HashMap::<String, i32>::new())
+        .await
+        .expect("Enqueue failed");
+    tx.rollback().await.unwrap();
+
+    let failing = OutboxRelay::new(
+        pool.clone(),
+        outbox.clone(),
+        RecordingPublisher { topics: Arc::default(), fail: true },
+    )
+    .retry_backoff(Duration::from_millis(200));
+    assert_eq!(failing.relay_once().await.expect("Relay failed"), 0);
+    let attempts: i32 = sqlx::query_scalar("SELECT attempts FROM rustling_outbox")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(attempts, 1);
+
+    let topics = Arc::default();
+    let relay = OutboxRelay::new(
+        pool.clone(),
+        outbox.clone(),
+        RecordingPublisher { topics: Arc::clone(&topics), fail: false },
+    );
+    // Not due again before the backoff has passed.
+    assert_eq!(relay.relay_once().await.expect("Relay failed"), 0);
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert_eq!(relay.relay_once().await.expect("Relay failed"), 1);
+    assert_eq!(relay.relay_once().await.expect("Relay failed"), 0);
+    assert_eq!(*topics.lock().unwrap(), vec!["user.created".to_string()]);
+
+    // Given up on after `max_attempts`.
+    outbox
+        .enqueue(&pool, "user.rejected", &HashMap::<String, i32>::new())
+        .await
+        .expect("Enqueue failed");
+    let failing = OutboxRelay::new(
+        pool.clone(),
+        outbox.clone(),
+        RecordingPublisher { topics: Arc::default(), fail: true },
+    )
+    .max_attempts(2)
+    .retry_backoff(Duration::ZERO);
+    assert_eq!(failing.relay_once().await.expect("Relay failed"), 0);
+    assert_eq!(failing.relay_once().await.expect("Relay failed"), 0);
+    let (attempts, status): (i32, String) =
+        sqlx::query_as("SELECT attempts, status FROM rustling_outbox WHERE topic = 'user.rejected'")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_eq!((attempts, status.as_str()), (2, "failed"));
+    assert_eq!(relay.relay_once().await.expect("Relay failed"), 0);
+
+    container.rm().await.expect("Failed to remove container");
+}
+
+#[tokio::test]
+async fn test_listen_and_notify() {
+    let (pool, container) = start_postgres_container().await;
+
+    let mut notifications = PostgresDriver::listen::<HashMap<String, String>>(&pool, "cache")
+        .await
+        .expect("Listen failed");
+    PostgresDriver::notify(&pool, "cache", &HashMap::from([("key", "user:1")]))
+        .await
+        .expect("Notify failed");
+    let notification = notifications.try_next().await.expect("Receive failed").unwrap();
+    assert_eq!(notification.channel, "cache");
+    assert_eq!(notification.payload["key"], "user:1");
+
+    PostgresDriver::create_notify_trigger(&pool, "users", "id", "user_changes")
+        .await
+        .expect("Create trigger failed");
+    let mut changes = PostgresDriver::listen::<RowChange<i32>>(&pool, "user_changes")
+        .await
+        .expect("Listen failed");
+    let id = PostgresDriver::insert(&pool, "users", &["name", "email"], vec![&"Lee", &"lee@example.com"])
+        .await
+        .expect("Insert failed");
+    PostgresDriver::delete(&pool, "users", "id", id).await.expect("Delete failed");
+
+    let change = changes.try_next().await.expect("Receive failed").unwrap();
+    assert_eq!(
+        change.payload,
+        RowChange { operation: RowOperation::Insert, table: "users".to_string(), id }
+    );
+    let change = changes.try_next().await.expect("Receive failed").unwrap();
+    assert_eq!(change.payload.operation, RowOperation::Delete);
+
+    container.rm().await.expect("Failed to remove container");
+}
+
+#[tokio::test]
+async fn test_migrator() {
+    let (pool, container) = start_postgres_container().await;
+    let migrator = PostgresMigrator::new()
+        .migration(SqlMigration::new(
+            2,
+            "add_nickname",
+            "ALTER TABLE users ADD COLUMN nickname TEXT",
+        ))
+        .migration(SqlMigration::new(
+            1,
+            "create_teams",
+            "CREATE TABLE teams (id SERIAL PRIMARY KEY); CREATE INDEX teams_id ON teams (id);",
+        ));
+
+    // Concurrent runners apply each migration once between them.
+    let (first, second) = tokio::join!(migrator.run(&pool), migrator.run(&pool));
+    let mut applied: Vec<_> = first.unwrap().into_iter().chain(second.unwrap()).collect();
+    applied.sort_by_key(|m| m.version);
+    assert_eq!(
+        applied.iter().map(|m| m.name.as_str()).collect::<Vec<_>>(),
+        vec!["create_teams", "add_nickname"]
+    );
+    assert_eq!(migrator.applied(&pool).await.unwrap(), applied);
+    PostgresDriver::verify_columns(&pool, "users", &["id", "nickname"])
+        .await
+        .expect("Migration should have added the column");
+
+    // A failing migration is rolled back and not recorded.
+    let failing = migrator.clone().migration(SqlMigration::new(
+        3,
+        "broken",
+        "ALTER TABLE users ADD COLUMN age INT; SELEC 1",
+    ));
+    assert!(matches!(
+        failing.run(&pool).await,
+        Err(RepositoryError::Other(_))
+    ));
+    assert!(
+        PostgresDriver::verify_columns(&pool, "users", &["age"])
+            .await
+            .is_err()
+    );
+    assert_eq!(migrator.applied(&pool).await.unwrap().len(), 2);
+
+    let changed = PostgresMigrator::new().migration(SqlMigration::new(
+        1,
+        "create_teams",
+        "CREATE TABLE teams (id BIGSERIAL PRIMARY KEY)",
+    ));
+    match changed.run(&pool).await {
+        Err(RepositoryError::SchemaMismatch(msg)) => assert!(msg.contains("create_teams")),
+        other => panic!("Unexpected result: {:?}", other),
+    }
+
+    container.rm().await.expect("Failed to remove container");
+}
@@ -0,0 +1,187 @@
+#![cfg(feature = "redis")]
+
+use rustling_data::api::{RedisError, RepositoryError};
+use rustling_data::redis::aio::MultiplexedConnection;
+use rustling_data::{Codec, KeySpace, RedisDriver};
+use serde::{Deserialize, Serialize};
+use testcontainers_modules::redis::{REDIS_PORT, Redis};
+use testcontainers_modules::testcontainers::ContainerAsync;
+use testcontainers_modules::testcontainers::runners::AsyncRunner;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Session {
+    id: i64,
+    user: String,
+    hits: u32,
+}
+
+const SESSIONS: KeySpace<'static> = KeySpace {
+    prefix: "session",
+    codec: Codec::MessagePack,
+    ttl: Some(60),
+    indexes: &["user"],
+};
+
+/// Helper to start a Redis container for a single test.
+async fn start_redis_container() -> (RedisDriver, ContainerAsync<Redis>) {
+    let container = Redis
+        .start()
+        .await
+        .expect("Failed to start Redis container");
+
+    let host_port = container
+        .get_host_port_ipv4(REDIS_PORT)
+        .await
+        .expect("Failed to get mapped port");
+
+    let client = rustling_data::redis::Client::open(format!("redis://127.0.0.1:{}", host_port))
+        .expect("Invalid Redis URL");
+    let connection: MultiplexedConnection = client
+        .get_multiplexed_async_connection()
+        .await
+        .expect("Failed to connect to Redis");
+
+    (RedisDriver::new(connection), container)
+}
+
+fn session(id: i64, user: &str) -> Session {
+    Session {
+        id,
+        user: user.to_string(),
+        hits: 0,
+    }
+}
+
+#[test]
+fn test_codecs_round_trip() {
+    let session = session(1, "alice");
+    for codec in [Codec::Json, Codec::MessagePack] {
+        let bytes = codec.encode(&session).expect("Encode failed");
+        let decoded: Session = codec.decode(&bytes).expect("Decode failed");
+        assert_eq!(decoded, session);
+    }
+    assert_eq!(SESSIONS.key(&7), "{session}:7");
+}
+
+#[tokio::test]
+async fn test_insert_and_find() {
+    let (driver, container) = start_redis_container().await;
+
+    let first: i64 = driver.next_id(&SESSIONS).await.expect("Next id failed");
+    let second: i64 = driver.next_id(&SESSIONS).await.expect("Next id failed");
+    assert_eq!((first, second), (1, 2));
+
+    driver
+        .insert(&SESSIONS, &first, &session(first, "alice"))
+        .await
+        .expect("Insert failed");
+    driver
+        .insert(&SESSIONS, &second, &session(second, "bob"))
+        .await
+        .expect("Insert failed");
+
+    let found: Option<Session> = driver
+        .find_one(&SESSIONS, &first)
+        .await
+        .expect("Find one failed");
+    assert_eq!(found, Some(session(first, "alice")));
+
+    let all: Vec<Session> = driver.find_all(&SESSIONS).await.expect("Find all failed");
+    assert_eq!(all.len(), 2);
+
+    let duplicate = driver
+        .insert(&SESSIONS, &first, &session(first, "mallory"))
+        .await;
+    assert!(matches!(
+        duplicate,
+        Err(RepositoryError::ConstraintViolation(_))
+    ));
+    let mallory: Vec<Session> = driver
+        .find_by_index(&SESSIONS, "user", &"mallory")
+        .await
+        .expect("Find by index failed");
+    assert!(mallory.is_empty());
+
+    container.rm().await.expect("Failed to remove container");
+}
+
+#[tokio::test]
+async fn test_modify_moves_index_entries() {
+    let (driver, container) = start_redis_container().await;
+
+    driver
+        .insert(&SESSIONS, &1, &session(1, "alice"))
+        .await
+        .expect("Insert failed");
+
+    let written = driver
+        .modify::<Session, _>(&SESSIONS, &1, |stored| {
+            stored.user = "bob".to_string();
+            stored.hits += 1;
+            Ok(true)
+        })
+        .await
+        .expect("Modify failed");
+    let (before, after) = written.expect("Session missing");
+    assert_eq!((before.user.as_str(), after.hits), ("alice", 1));
+
+    let alice: Vec<Session> = driver
+        .find_by_index(&SESSIONS, "user", &"alice")
+        .await
+        .expect("Find by index failed");
+    assert!(alice.is_empty());
+    let bob: Vec<Session> = driver
+        .find_by_index(&SESSIONS, "user", &"bob")
+        .await
+        .expect("Find by index failed");
+    assert_eq!(bob, vec![after]);
+
+    let unindexed: Result<Vec<Session>, RepositoryError<RedisError>> =
+        driver.find_by_index(&SESSIONS, "hits", &1).await;
+    assert!(matches!(unindexed, Err(RepositoryError::Other(_))));
+
+    let missing = driver
+        .modify::<Session, _>(&SESSIONS, &2, |_| Ok(true))
+        .await
+        .expect("Modify failed");
+    assert!(missing.is_none());
+
+    container.rm().await.expect("Failed to remove container");
+}
+
+#[tokio::test]
+async fn test_delete_and_ttl() {
+    let (driver, container) = start_redis_container().await;
+
+    driver
+        .insert(&SESSIONS, &1, &session(1, "alice"))
+        .await
+        .expect("Insert failed");
+
+    let ttl: i64 = rustling_data::redis::cmd("TTL")
+        .arg(SESSIONS.key(&1))
+        .query_async(&mut driver.connection())
+        .await
+        .expect("TTL failed");
+    assert!(ttl > 0 && ttl <= 60);
+
+    let deleted = driver
+        .delete::<Session>(&SESSIONS, &1)
+        .await
+        .expect("Delete failed");
+    assert_eq!(deleted, 1);
+
+    let again = driver
+        .delete::<Session>(&SESSIONS, &1)
+        .await
+        .expect("Delete failed");
+    assert_eq!(again, 0);
+
+    let alice: Vec<Session> = driver
+        .find_by_index(&SESSIONS, "user", &"alice")
+        .await
+        .expect("Find by index failed");
+    assert!(alice.is_empty());
+
+    container.rm().await.expect("Failed to remove container");
+}
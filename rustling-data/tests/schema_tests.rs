@@ -0,0 +1,142 @@
+use rustling_data::api::FieldName;
+use rustling_data::schema::{Column, DbColumn, Index, TableSchema};
+
+const EMAIL: FieldName = FieldName {
+    column: "email",
+    key: "email",
+};
+const NICKNAME: FieldName = FieldName {
+    column: "nickname",
+    key: "nick",
+};
+
+const ACCOUNTS: TableSchema = TableSchema {
+    name: "accounts",
+    columns: &[
+        Column {
+            primary_key: true,
+            ..Column::new("id", "BIGINT")
+        },
+        Column::new("email", "TEXT"),
+        Column {
+            nullable: true,
+            ..Column::new("nickname", "TEXT")
+        },
+        Column {
+            default: Some("now()"),
+            ..Column::new("created_at", "TIMESTAMPTZ")
+        },
+    ],
+    indexes: &[
+        Index {
+            unique: true,
+            ..Index::new(&[EMAIL])
+        },
+        Index {
+            predicate: Some("nickname IS NOT NULL"),
+            ..Index::new(&[NICKNAME, EMAIL])
+        },
+        Index {
+            text: true,
+            ..Index::new(&[NICKNAME])
+        },
+    ],
+};
+
+#[test]
+fn test_create_table_sql() {
+    assert_eq!(
+        ACCOUNTS.create_table_sql("app.accounts").unwrap(),
+        vec![
+            "CREATE TABLE IF NOT EXISTS app.accounts (id BIGSERIAL PRIMARY KEY, \
+             email TEXT NOT NULL, nickname TEXT, \
+             created_at TIMESTAMPTZ NOT NULL DEFAULT now())",
+            "CREATE UNIQUE INDEX IF NOT EXISTS app_accounts_email_idx ON app.accounts (email)",
+            "CREATE INDEX IF NOT EXISTS app_accounts_nickname_email_idx ON app.accounts \
+             (nickname, email) WHERE nickname IS NOT NULL",
+        ]
+    );
+}
+
+#[test]
+fn test_named_index_sql() {
+    let index = Index {
+        name: Some("accounts_by_email"),
+        ..Index::new(&[EMAIL])
+    };
+    assert_eq!(
+        index.create_index_sql("accounts").unwrap(),
+        "CREATE INDEX IF NOT EXISTS accounts_by_email ON accounts (email)"
+    );
+}
+
+#[test]
+fn test_create_table_sql_requires_types() {
+    const UNMAPPED: TableSchema = TableSchema {
+        name: "things",
+        columns: &[Column {
+            sql_type: None,
+            ..Column::new("status", "TEXT")
+        }],
+        indexes: &[],
+    };
+    let err = UNMAPPED.create_table_sql("things").unwrap_err();
+    assert!(err.contains("status"), "{}", err);
+}
+
+fn db_column(name: &str, udt_name: &str, nullable: bool, primary_key: bool) -> DbColumn {
+    DbColumn {
+        name: name.to_string(),
+        udt_name: udt_name.to_string(),
+        nullable,
+        primary_key,
+    }
+}
+
+#[test]
+fn test_differences_accept_compatible_columns() {
+    let existing = vec![
+        db_column("id", "int8", false, true),
+        db_column("email", "varchar", false, false),
+        db_column("nickname", "text", false, false),
+        db_column("created_at", "timestamptz", false, false),
+        db_column("extra", "text", true, false),
+    ];
+    assert!(ACCOUNTS.differences(&existing).is_empty());
+}
+
+#[test]
+fn test_differences_quoted_char() {
+    const FLAGS: TableSchema = TableSchema {
+        name: "flags",
+        columns: &[Column::new("level", "\"char\"")],
+        indexes: &[],
+    };
+    assert!(
+        FLAGS
+            .differences(&[db_column("level", "char", false, false)])
+            .is_empty()
+    );
+    assert_eq!(
+        FLAGS.differences(&[db_column("level", "int2", false, false)]),
+        vec!["column level has type int2 but the entity declares \"char\""]
+    );
+}
+
+#[test]
+fn test_differences_report_drift() {
+    let existing = vec![
+        db_column("id", "int8", false, false),
+        db_column("email", "text", true, false),
+        db_column("created_at", "timestamp", false, false),
+    ];
+    assert_eq!(
+        ACCOUNTS.differences(&existing),
+        vec![
+            "column id is not the primary key",
+            "column email is nullable but its field is not an Option",
+            "missing column nickname",
+            "column created_at has type timestamp but the entity declares TIMESTAMPTZ",
+        ]
+    );
+}
@@ -0,0 +1,283 @@
+#![cfg(feature = "sqlite")]
+
+use rustling_data::api::{FieldName, RepositoryError, SoftDelete};
+use rustling_data::audit::{self, Clock};
+use rustling_data::{SqliteDriver, SqliteValues};
+use sqlx::sqlite::SqlitePoolOptions;
+use sqlx::{FromRow, SqlitePool};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, FromRow, PartialEq)]
+struct User {
+    id: i32,
+    name: String,
+    email: String,
+}
+
+const USER_COLUMNS: &[&str] = &["id", "name", "email"];
+
+/// In-memory database with a `users` table; one connection, so every query sees it.
+async fn start_sqlite() -> SqlitePool {
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await
+        .expect("Failed to open SQLite database");
+
+    sqlx::query(
+        r#"
+        CREATE TABLE users (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            email TEXT NOT NULL UNIQUE,
+            version INTEGER NOT NULL DEFAULT 0,
+            deleted_at TIMESTAMP
+        )
+        "#,
+    )
+    .execute(&pool)
+    .await
+    .expect("Failed to create table");
+
+    pool
+}
+
+#[tokio::test]
+async fn test_insert_and_find() {
+    let pool = start_sqlite().await;
+
+    let id = SqliteDriver::insert(
+        &pool,
+        "users",
+        &["name", "email"],
+        vec![&"Alice", &"alice@example.com"],
+    )
+    .await
+    .expect("Insert failed");
+
+    let user: Option<User> = SqliteDriver::find_one(&pool, "users", USER_COLUMNS, "id", id)
+        .await
+        .expect("Find one failed");
+    assert_eq!(
+        user,
+        Some(User {
+            id,
+            name: "Alice".to_string(),
+            email: "alice@example.com".to_string(),
+        })
+    );
+
+    let users: Vec<User> = SqliteDriver::find_all(&pool, "users", USER_COLUMNS)
+        .await
+        .expect("Find all failed");
+    assert_eq!(users.len(), 1);
+
+    let missing: Option<User> = SqliteDriver::find_one(&pool, "users", USER_COLUMNS, "id", id + 1)
+        .await
+        .expect("Find one failed");
+    assert!(missing.is_none());
+}
+
+#[tokio::test]
+async fn test_insert_duplicate_is_constraint_violation() {
+    let pool = start_sqlite().await;
+
+    SqliteDriver::insert(
+        &pool,
+        "users",
+        &["name", "email"],
+        vec![&"Bob", &"bob@example.com"],
+    )
+    .await
+    .expect("Insert failed");
+
+    let duplicate = SqliteDriver::insert(
+        &pool,
+        "users",
+        &["name", "email"],
+        vec![&"Bobby", &"bob@example.com"],
+    )
+    .await;
+    assert!(matches!(
+        duplicate,
+        Err(RepositoryError::ConstraintViolation(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_update_and_delete() {
+    let pool = start_sqlite().await;
+
+    let id = SqliteDriver::insert(
+        &pool,
+        "users",
+        &["name", "email"],
+        vec![&"Carol", &"carol@example.com"],
+    )
+    .await
+    .expect("Insert failed");
+
+    let updated_rows =
+        SqliteDriver::update(&pool, "users", "id", id, &["name"], vec![&"Caroline"], None)
+            .await
+            .expect("Update failed");
+    assert_eq!(updated_rows, 1);
+
+    let filtered: Vec<User> =
+        SqliteDriver::find_where(&pool, "users", USER_COLUMNS, "name = ?1", vec![&"Caroline"])
+            .await
+            .expect("Find where failed");
+    assert_eq!(filtered.len(), 1);
+    assert_eq!(filtered[0].email, "carol@example.com");
+
+    let deleted_rows = SqliteDriver::delete(&pool, "users", "id", id)
+        .await
+        .expect("Delete failed");
+    assert_eq!(deleted_rows, 1);
+
+    let users: Vec<User> = SqliteDriver::find_all(&pool, "users", USER_COLUMNS)
+        .await
+        .expect("Find all failed");
+    assert!(users.is_empty());
+}
+
+#[tokio::test]
+async fn test_update_versioned() {
+    let pool = start_sqlite().await;
+
+    let id = SqliteDriver::insert(
+        &pool,
+        "users",
+        &["name", "email"],
+        vec![&"Grace", &"grace@example.com"],
+    )
+    .await
+    .expect("Insert failed");
+
+    let stale_rows = SqliteDriver::update_versioned(
+        &pool,
+        "users",
+        "id",
+        id,
+        &["name"],
+        vec![&"Gracie"],
+        "version",
+        Some(1),
+        None,
+    )
+    .await
+    .expect("Update failed");
+    assert_eq!(stale_rows, 0);
+
+    let updated_rows = SqliteDriver::update_versioned(
+        &pool,
+        "users",
+        "id",
+        id,
+        &["name"],
+        vec![&"Gracie"],
+        "version",
+        Some(0),
+        None,
+    )
+    .await
+    .expect("Update failed");
+    assert_eq!(updated_rows, 1);
+
+    let version: i64 = sqlx::query_scalar("SELECT version FROM users WHERE id = ?1")
+        .bind(id)
+        .fetch_one(&pool)
+        .await
+        .expect("Failed to read version");
+    assert_eq!(version, 1);
+}
+
+#[tokio::test]
+async fn test_soft_delete_and_restore() {
+    let pool = start_sqlite().await;
+
+    let soft_delete = SoftDelete::Timestamp(FieldName {
+        column: "deleted_at",
+        key: "deleted_at",
+    });
+    let not_deleted = SqliteDriver::not_deleted(&soft_delete);
+
+    let id = SqliteDriver::insert(
+        &pool,
+        "users",
+        &["name", "email"],
+        vec![&"Judy", &"judy@example.com"],
+    )
+    .await
+    .expect("Insert failed");
+
+    let deleted_rows = SqliteDriver::soft_delete(&pool, "users", "id", id, &soft_delete)
+        .await
+        .expect("Soft delete failed");
+    assert_eq!(deleted_rows, 1);
+
+    let live: Vec<User> = SqliteDriver::find_where(
+        &pool,
+        "users",
+        USER_COLUMNS,
+        &not_deleted,
+        SqliteValues::new(),
+    )
+    .await
+    .expect("Find where failed");
+    assert!(live.is_empty());
+
+    let restored_rows = SqliteDriver::restore(&pool, "users", "id", id, &soft_delete)
+        .await
+        .expect("Restore failed");
+    assert_eq!(restored_rows, 1);
+
+    let live: Vec<User> = SqliteDriver::find_where(
+        &pool,
+        "users",
+        USER_COLUMNS,
+        &not_deleted,
+        SqliteValues::new(),
+    )
+    .await
+    .expect("Find where failed");
+    assert_eq!(live.len(), 1);
+}
+
+struct FixedClock;
+
+impl Clock for FixedClock {
+    fn now(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_millis(1_700_000_000_250)
+    }
+}
+
+#[tokio::test]
+async fn test_soft_delete_uses_audit_clock() {
+    let pool = start_sqlite().await;
+    audit::set_clock(FixedClock);
+
+    let soft_delete = SoftDelete::Timestamp(FieldName {
+        column: "deleted_at",
+        key: "deleted_at",
+    });
+    let id = SqliteDriver::insert(
+        &pool,
+        "users",
+        &["name", "email"],
+        vec![&"Mallory", &"mallory@example.com"],
+    )
+    .await
+    .expect("Insert failed");
+
+    SqliteDriver::soft_delete(&pool, "users", "id", id, &soft_delete)
+        .await
+        .expect("Soft delete failed");
+
+    let deleted_at: String = sqlx::query_scalar("SELECT deleted_at FROM users WHERE id = ?1")
+        .bind(id)
+        .fetch_one(&pool)
+        .await
+        .expect("Select failed");
+    assert_eq!(deleted_at, "2023-11-14 22:13:20.250");
+}
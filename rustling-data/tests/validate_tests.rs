@@ -0,0 +1,32 @@
+use rustling_data::api::RepositoryError;
+use rustling_data::validate::{FieldError, Length, is_email};
+
+#[test]
+fn test_is_email() {
+    assert!(is_email("bob@example.com"));
+    assert!(is_email("first.last+tag@mail.example.org"));
+    assert!(!is_email("bob"));
+    assert!(!is_email("@example.com"));
+    assert!(!is_email("bob@localhost"));
+    assert!(!is_email("bob@example..com"));
+    assert!(!is_email("bob smith@example.com"));
+    assert!(!is_email("bob@x@example.com"));
+}
+
+#[test]
+fn test_length_counts_chars() {
+    assert_eq!("héllo".to_string().length(), 5);
+    assert_eq!(vec![1, 2, 3].length(), 3);
+}
+
+#[test]
+fn test_validation_error_message() {
+    let err: RepositoryError<std::io::Error> = RepositoryError::Validation(vec![
+        FieldError::new("name", "length must be at most 8"),
+        FieldError::new("age", "must be at least 0"),
+    ]);
+    assert_eq!(
+        err.to_string(),
+        "validation failed: name: length must be at most 8, age: must be at least 0"
+    );
+}
@@ -1,13 +1,26 @@
 use proc_macro2::TokenStream;
-use syn::DeriveInput;
 
+#[cfg(any(
+    feature = "postgres",
+    feature = "sqlite",
+    feature = "mysql",
+    feature = "mongo",
+    feature = "redis"
+))]
 pub struct RepositoryMeta {
     pub entity: TokenStream,
     pub id: TokenStream,
     pub storage_name: String,
 }
 
-pub fn parse_repository_meta(ast: &DeriveInput, storage_attr: &str) -> RepositoryMeta {
+#[cfg(any(
+    feature = "postgres",
+    feature = "sqlite",
+    feature = "mysql",
+    feature = "mongo",
+    feature = "redis"
+))]
+pub fn parse_repository_meta(ast: &syn::DeriveInput, storage_attr: &str) -> RepositoryMeta {
     let entity_attr = ast
         .attrs
         .iter()
@@ -45,3 +58,85 @@ pub fn parse_repository_meta(ast: &DeriveInput, storage_attr: &str) -> Repositor
         storage_name,
     }
 }
+
+/// `Some(value)` or `None` as an expression.
+pub fn option<T: quote::ToTokens>(value: &Option<T>) -> TokenStream {
+    match value {
+        Some(value) => quote::quote! { Some(#value) },
+        None => quote::quote! { None },
+    }
+}
+
+#[cfg(any(
+    feature = "postgres",
+    feature = "sqlite",
+    feature = "mysql",
+    feature = "mongo",
+    feature = "redis"
+))]
+/// Name given with `#[backend(name)]`, if any.
+pub fn parse_backend(ast: &syn::DeriveInput) -> Option<String> {
+    ast.attrs
+        .iter()
+        .find(|a| a.path().is_ident("backend"))
+        .map(|a| {
+            a.parse_args::<syn::Ident>()
+                .expect("Expected #[backend(name)], e.g. #[backend(sqlite)]")
+                .to_string()
+        })
+}
+
+#[cfg(any(
+    feature = "postgres",
+    feature = "sqlite",
+    feature = "mysql",
+    feature = "mongo",
+    feature = "redis"
+))]
+/// `Repository` implementation forwarding to the repository's `CrudRepository<E, ID, error>`.
+pub fn repository_impl(
+    name: &syn::Ident,
+    entity: &TokenStream,
+    id: &TokenStream,
+    error: &TokenStream,
+) -> TokenStream {
+    let crud = quote::quote! { ::rustling_data::api::CrudRepository::<#entity, #id, #error> };
+    let result = |ok: TokenStream| {
+        quote::quote! { Result<#ok, ::rustling_data::api::RepositoryError<#error>> }
+    };
+    let entities = result(quote::quote! { Vec<#entity> });
+    let found = result(quote::quote! { Option<#entity> });
+    let inserted = result(quote::quote! { #id });
+    let affected = result(quote::quote! { u64 });
+
+    quote::quote! {
+        #[async_trait::async_trait]
+        impl ::rustling_data::api::Repository<#entity, #id> for #name {
+            type Error = #error;
+
+            async fn find_all(&self) -> #entities {
+                #crud::find_all(self).await
+            }
+
+            async fn find_one(&self, id: &#id) -> #found {
+                #crud::find_one(self, id).await
+            }
+
+            async fn insert_one(&self, entity: &#entity) -> #inserted {
+                #crud::insert_one(self, entity).await
+            }
+
+            async fn update_one(&self, id: &#id, entity: &#entity) -> #found {
+                #crud::update_one(self, id, entity).await
+            }
+
+            async fn patch_one(&self, id: &#id, patch: &::rustling_data::api::Patch<#entity>) -> #found {
+                #crud::patch_one(self, id, patch).await
+            }
+
+            async fn delete_one(&self, id: &#id) -> #affected {
+                #crud::delete_one(self, id).await
+            }
+        }
+    }
+}
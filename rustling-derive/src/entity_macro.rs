@@ -0,0 +1,1186 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote, quote_spanned};
+use syn::spanned::Spanned;
+use syn::{Data, DeriveInput, Field, Fields, parse_macro_input};
+
+use crate::common::option;
+use crate::index::IndexAttrs;
+use crate::validation::{Validation, validate_body};
+
+/// Options set on a field with `#[rustling(...)]`.
+#[derive(Default)]
+struct FieldAttrs {
+    version: bool,
+    soft_delete: bool,
+    created_at: bool,
+    updated_at: bool,
+    created_by: bool,
+    updated_by: bool,
+    sql_type: Option<String>,
+    default: Option<String>,
+    index: Option<IndexAttrs>,
+    validations: Vec<Validation>,
+}
+
+fn parse_field_attrs(field: &Field) -> FieldAttrs {
+    // `entity_derive` reports invalid attributes before anything else parses them.
+    try_parse_field_attrs(field).expect("field attributes are checked by entity_derive")
+}
+
+fn try_parse_field_attrs(field: &Field) -> syn::Result<FieldAttrs> {
+    let mut attrs = FieldAttrs::default();
+    for attr in field.attrs.iter().filter(|a| a.path().is_ident("rustling")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("version") {
+                attrs.version = true;
+                Ok(())
+            } else if meta.path.is_ident("soft_delete") {
+                attrs.soft_delete = true;
+                Ok(())
+            } else if meta.path.is_ident("created_at") {
+                attrs.created_at = true;
+                Ok(())
+            } else if meta.path.is_ident("updated_at") {
+                attrs.updated_at = true;
+                Ok(())
+            } else if meta.path.is_ident("created_by") {
+                attrs.created_by = true;
+                Ok(())
+            } else if meta.path.is_ident("updated_by") {
+                attrs.updated_by = true;
+                Ok(())
+            } else if meta.path.is_ident("sql_type") {
+                attrs.sql_type = Some(meta.value()?.parse::<syn::LitStr>()?.value());
+                Ok(())
+            } else if meta.path.is_ident("default") {
+                attrs.default = Some(meta.value()?.parse::<syn::LitStr>()?.value());
+                Ok(())
+            } else if meta.path.is_ident("unique") {
+                attrs.index.get_or_insert_with(IndexAttrs::default).unique = true;
+                Ok(())
+            } else if meta.path.is_ident("index") {
+                let mut index = IndexAttrs::parse(&meta)?;
+                if !index.fields.is_empty() {
+                    return Err(meta.error("fields(...) is only supported on struct-level indexes"));
+                }
+                index.unique |= attrs.index.as_ref().is_some_and(|i| i.unique);
+                attrs.index = Some(index);
+                Ok(())
+            } else if let Some(validation) = Validation::parse(&meta)? {
+                attrs.validations.push(validation);
+                Ok(())
+            } else {
+                Err(meta.error("unsupported rustling attribute"))
+            }
+        })?;
+    }
+    Ok(attrs)
+}
+
+/// Options set on the struct with `#[rustling(...)]`.
+#[derive(Default)]
+struct StructAttrs {
+    /// `Lifecycle` is implemented by hand.
+    lifecycle: bool,
+    table: Option<String>,
+    indexes: Vec<IndexAttrs>,
+}
+
+fn parse_struct_attrs(ast: &DeriveInput) -> StructAttrs {
+    let mut attrs = StructAttrs::default();
+    for attr in ast.attrs.iter().filter(|a| a.path().is_ident("rustling")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("lifecycle") {
+                attrs.lifecycle = true;
+                Ok(())
+            } else if meta.path.is_ident("table") {
+                attrs.table = Some(meta.value()?.parse::<syn::LitStr>()?.value());
+                Ok(())
+            } else if meta.path.is_ident("index") {
+                let index = IndexAttrs::parse(&meta)?;
+                if index.fields.is_empty() {
+                    return Err(meta.error("struct-level indexes need fields(...)"));
+                }
+                attrs.indexes.push(index);
+                Ok(())
+            } else {
+                Err(meta.error("unsupported rustling attribute"))
+            }
+        })
+        .unwrap_or_else(|e| panic!("Invalid #[rustling(...)] attribute: {}", e));
+    }
+    attrs
+}
+
+pub fn entity_derive(input: TokenStream) -> TokenStream {
+    let ast = parse_macro_input!(input as DeriveInput);
+    let name = &ast.ident;
+    let vis = &ast.vis;
+    let struct_attrs = parse_struct_attrs(&ast);
+
+    let all_fields: Vec<_> = match &ast.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields_named) => fields_named.named.iter().collect(),
+            _ => panic!("Entity derive only supports named fields"),
+        },
+        _ => panic!("Entity derive only supports structs"),
+    };
+    if let Err(error) = all_fields
+        .iter()
+        .try_for_each(|f| try_parse_field_attrs(f).map(drop))
+    {
+        return error.to_compile_error().into();
+    }
+    let fields: Vec<_> = all_fields
+        .iter()
+        .copied()
+        .filter(|f| f.ident.as_ref().unwrap() != "id")
+        .collect();
+
+    let version = single_field(&fields, "version", |a| a.version);
+    if version.is_some_and(|f| is_option(&f.ty)) {
+        panic!("#[rustling(version)] field must be an integer, not an Option");
+    }
+    let soft_delete = single_field(&fields, "soft_delete", |a| a.soft_delete);
+    let audit = AuditFields {
+        created_at: single_field(&fields, "created_at", |a| a.created_at),
+        updated_at: single_field(&fields, "updated_at", |a| a.updated_at),
+        created_by: single_field(&fields, "created_by", |a| a.created_by),
+        updated_by: single_field(&fields, "updated_by", |a| a.updated_by),
+    };
+
+    // `created_*` fields are written once on insert and are not part of updates or patches.
+    let patch_fields: Vec<_> = fields
+        .iter()
+        .copied()
+        .filter(|f| !audit.is_created(f))
+        .collect();
+
+    // The version is bumped by the driver on update, never written from the entity.
+    let update_fields: Vec<_> = patch_fields
+        .iter()
+        .copied()
+        .filter(|f| !is_field(f, version))
+        .collect();
+
+    let column_names: Vec<_> = fields
+        .iter()
+        .map(|f| f.ident.as_ref().unwrap().to_string())
+        .collect();
+    let all_column_names: Vec<_> = all_fields
+        .iter()
+        .map(|f| f.ident.as_ref().unwrap().to_string())
+        .collect();
+    let update_column_names: Vec<_> = update_fields
+        .iter()
+        .map(|f| f.ident.as_ref().unwrap().to_string())
+        .collect();
+    let field_idents: Vec<_> = fields.iter().map(|f| f.ident.as_ref().unwrap()).collect();
+    let update_idents: Vec<_> = update_fields
+        .iter()
+        .map(|f| f.ident.as_ref().unwrap())
+        .collect();
+
+    let postgres_methods = if cfg!(feature = "postgres") {
+        let encode_bounds = deferred_bounds(&fields, encode_bound());
+        let table = struct_attrs
+            .table
+            .clone()
+            .unwrap_or_else(|| name.to_string().to_lowercase() + "s");
+        let schema_columns = all_fields.iter().map(|f| schema_column(f));
+        quote! {
+            /// Columns, types and constraints of the entity's table.
+            pub fn table_schema() -> &'static ::rustling_data::schema::TableSchema {
+                static SCHEMA: ::rustling_data::schema::TableSchema = ::rustling_data::schema::TableSchema {
+                    name: #table,
+                    columns: &[#(#schema_columns),*],
+                    indexes: <#name as ::rustling_data::api::Entity>::INDEXES,
+                };
+                &SCHEMA
+            }
+
+            pub fn values(&self) -> ::rustling_data::PgValues #encode_bounds {
+                let mut values = ::rustling_data::PgValues::new();
+                #(values.push(&self.#field_idents);)*
+                values
+            }
+
+            pub fn update_values(&self) -> ::rustling_data::PgValues #encode_bounds {
+                let mut values = ::rustling_data::PgValues::new();
+                #(values.push(&self.#update_idents);)*
+                values
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    let sqlite_methods = if cfg!(feature = "sqlite") {
+        let encode_bounds = deferred_bounds(&fields, sqlite_encode_bound());
+        quote! {
+            pub fn sqlite_values(&self) -> ::rustling_data::SqliteValues<'_> #encode_bounds {
+                let mut values = ::rustling_data::SqliteValues::new();
+                #(values.push(&self.#field_idents);)*
+                values
+            }
+
+            pub fn sqlite_update_values(&self) -> ::rustling_data::SqliteValues<'_> #encode_bounds {
+                let mut values = ::rustling_data::SqliteValues::new();
+                #(values.push(&self.#update_idents);)*
+                values
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    let mysql_methods = if cfg!(feature = "mysql") {
+        let encode_bounds = deferred_bounds(&fields, mysql_encode_bound());
+        quote! {
+            pub fn mysql_values(&self) -> ::rustling_data::MySqlValues #encode_bounds {
+                let mut values = ::rustling_data::MySqlValues::new();
+                #(values.push(&self.#field_idents);)*
+                values
+            }
+
+            pub fn mysql_update_values(&self) -> ::rustling_data::MySqlValues #encode_bounds {
+                let mut values = ::rustling_data::MySqlValues::new();
+                #(values.push(&self.#update_idents);)*
+                values
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    let mongo_methods = if cfg!(feature = "mongo") {
+        let mut skipped_keys = vec!["_id".to_string()];
+        skipped_keys.extend(
+            all_fields
+                .iter()
+                .copied()
+                .filter(|f| f.ident.as_ref().unwrap() == "id")
+                .map(serde_name),
+        );
+        skipped_keys.extend(
+            fields
+                .iter()
+                .copied()
+                .filter(|f| !update_fields.iter().any(|u| std::ptr::eq(*u, *f)))
+                .map(serde_name),
+        );
+        skipped_keys.dedup();
+        let mut required = Vec::new();
+        let mut properties = Vec::new();
+        for field in &all_fields {
+            let serde = serde_attrs(field);
+            if serde.skipped {
+                continue;
+            }
+            if !serde.optional && !is_option(&field.ty) {
+                required.push(serde.name.clone());
+            }
+            let key = serde.name;
+            let schema = match bson_schema(&field.ty) {
+                Some(schema) => schema.to_document(),
+                None => quote! { ::rustling_data::bson::Document::new() },
+            };
+            properties.push(quote! { properties.insert(#key, #schema); });
+        }
+        let required = (!required.is_empty()).then(|| {
+            quote! { schema.insert("required", vec![#(#required),*]); }
+        });
+        quote! {
+            /// `$jsonSchema` of the entity's documents, see `MongoDriver::apply_json_schema`.
+            pub fn json_schema() -> ::rustling_data::bson::Document {
+                let mut properties = ::rustling_data::bson::Document::new();
+                #(#properties)*
+                let mut schema = ::rustling_data::bson::doc! { "bsonType": "object" };
+                #required
+                schema.insert("properties", properties);
+                schema
+            }
+
+            /// `$set` document writing the fields `update_one` may change.
+            pub fn update_document(&self) -> Result<::rustling_data::bson::Document, ::rustling_data::bson::ser::Error>
+            where
+                for<'__r> Self: ::rustling_data::serde::Serialize,
+            {
+                let mut set = ::rustling_data::bson::to_document(self)?;
+                #(set.remove(#skipped_keys);)*
+                Ok(::rustling_data::bson::doc! { "$set": set })
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    let patch_name = format_ident!("{}Patch", name);
+    let patch = implement_patch(&patch_name, vis, &patch_fields, version, &audit);
+
+    let version_items = match version {
+        Some(f) => {
+            let ident = f.ident.as_ref().unwrap();
+            let field_name = field_name(f);
+            quote! {
+                const VERSION_FIELD: Option<::rustling_data::api::FieldName> = Some(#field_name);
+
+                fn version(&self) -> Option<i64> {
+                    Some(i64::from(self.#ident))
+                }
+            }
+        }
+        None => quote! {},
+    };
+    let soft_delete_item = match soft_delete {
+        Some(f) => {
+            let field_name = field_name(f);
+            let kind = if is_option(&f.ty) {
+                quote! { Timestamp }
+            } else if is_bool(&f.ty) {
+                quote! { Flag }
+            } else {
+                panic!("#[rustling(soft_delete)] field must be a bool or an Option timestamp");
+            };
+            quote! {
+                const SOFT_DELETE: Option<::rustling_data::api::SoftDelete> =
+                    Some(::rustling_data::api::SoftDelete::#kind(#field_name));
+            }
+        }
+        None => quote! {},
+    };
+    let insert_stamps = audit.entity_stamps(
+        &[audit.created_at, audit.updated_at],
+        &[audit.created_by, audit.updated_by],
+    );
+    let update_stamps = audit.entity_stamps(&[audit.updated_at], &[audit.updated_by]);
+    let copies = all_fields.iter().map(|f| {
+        let ident = f.ident.as_ref().unwrap();
+        quote_spanned! {f.ty.span()=> #ident: ::core::clone::Clone::clone(&self.#ident)}
+    });
+    let mut write_items = quote! {
+        fn copy_for_write(&self) -> Self {
+            Self { #(#copies),* }
+        }
+    };
+    if !insert_stamps.is_empty() {
+        write_items.extend(quote! {
+            fn stamp_insert(&mut self, stamp: &::rustling_data::audit::AuditStamp) {
+                #insert_stamps
+            }
+        });
+    }
+    if !update_stamps.is_empty() {
+        write_items.extend(quote! {
+            fn stamp_update(&mut self, stamp: &::rustling_data::audit::AuditStamp) {
+                #update_stamps
+            }
+        });
+    }
+    let checks = fields.iter().filter_map(|f| {
+        let ident = f.ident.as_ref().unwrap();
+        let checks = field_checks(f)?;
+        Some(if is_option(&f.ty) {
+            quote! { if let Some(value) = &self.#ident { #checks } }
+        } else {
+            quote! {{ let value = &self.#ident; #checks }}
+        })
+    });
+    let validate_body = validate_body(checks);
+    write_items.extend(quote! {
+        fn validate(&self) -> Result<(), Vec<::rustling_data::validate::FieldError>> {
+            #validate_body
+        }
+    });
+    let (lifecycle_const, lifecycle_impl) = if struct_attrs.lifecycle {
+        (quote! { const LIFECYCLE: bool = true; }, quote! {})
+    } else {
+        (
+            quote! {},
+            quote! { impl ::rustling_data::api::Lifecycle for #name {} },
+        )
+    };
+    let field_indexes = all_fields.iter().filter_map(|f| {
+        let index = parse_field_attrs(f).index?;
+        Some(index.to_index(&[field_name(f)]))
+    });
+    let struct_indexes = struct_attrs.indexes.iter().map(|index| {
+        let fields: Vec<_> = index
+            .fields
+            .iter()
+            .map(|ident| {
+                let field = all_fields
+                    .iter()
+                    .find(|f| f.ident.as_ref() == Some(ident))
+                    .unwrap_or_else(|| {
+                        panic!("Index field `{}` is not a field of {}", ident, name)
+                    });
+                field_name(field)
+            })
+            .collect();
+        index.to_index(&fields)
+    });
+    let indexes: Vec<_> = field_indexes.chain(struct_indexes).collect();
+    let indexes_item = if indexes.is_empty() {
+        quote! {}
+    } else {
+        quote! {
+            const INDEXES: &'static [::rustling_data::schema::Index] = &[#(#indexes),*];
+        }
+    };
+    // A tracked entity always carries the version it was loaded with, so `save` is checked.
+    let changes = patch_fields.iter().map(|f| {
+        let ident = f.ident.as_ref().unwrap();
+        if is_field(f, version) {
+            quote! { #ident: Some(original.#ident) }
+        } else {
+            quote_spanned! {f.ty.span()=>
+                #ident: if ::core::cmp::PartialEq::ne(&self.#ident, &original.#ident) {
+                    Some(::core::clone::Clone::clone(&self.#ident))
+                } else {
+                    None
+                }
+            }
+        }
+    });
+
+    let field_bounds = field_bounds(&all_fields, &update_fields);
+
+    let memory_impl = match all_fields
+        .iter()
+        .find(|f| f.ident.as_ref().unwrap() == "id")
+    {
+        Some(id) => implement_memory_entity(
+            name,
+            id,
+            &update_idents,
+            &patch_fields,
+            version,
+            soft_delete,
+        ),
+        None => quote! {},
+    };
+
+    let gene = quote! {
+        impl #name {
+            pub fn columns() -> &'static [&'static str] {
+                &[#(#column_names),*]
+            }
+
+            pub fn all_columns() -> &'static [&'static str] {
+                &[#(#all_column_names),*]
+            }
+
+            pub fn update_columns() -> &'static [&'static str] {
+                &[#(#update_column_names),*]
+            }
+
+            #postgres_methods
+            #sqlite_methods
+            #mysql_methods
+            #mongo_methods
+        }
+
+        #patch
+
+        #lifecycle_impl
+
+        impl ::rustling_data::api::Entity for #name {
+            type Patch = #patch_name;
+
+            #version_items
+            #soft_delete_item
+            #indexes_item
+            #lifecycle_const
+            #write_items
+
+            fn changes(&self, original: &Self) -> #patch_name {
+                #patch_name {
+                    #(#changes),*
+                }
+            }
+        }
+
+        #memory_impl
+
+        #field_bounds
+    };
+
+    gene.into()
+}
+
+/// Checks that every field is `Clone` (for `copy_for_write`) and every updated field is
+/// `PartialEq` (for `changes`), reporting a missing impl on the field rather than on the derive.
+fn field_bounds(all_fields: &[&Field], update_fields: &[&Field]) -> TokenStream2 {
+    let clone = all_fields.iter().map(|f| {
+        let ty = &f.ty;
+        quote_spanned! {ty.span()=> entity_field_clone::<#ty>();}
+    });
+    let partial_eq = update_fields.iter().map(|f| {
+        let ty = &f.ty;
+        quote_spanned! {ty.span()=> entity_field_partial_eq::<#ty>();}
+    });
+    quote! {
+        const _: () = {
+            #[diagnostic::on_unimplemented(
+                message = "`#[derive(Entity)]` requires every field to implement `Clone`",
+                label = "`{Self}` is not `Clone`"
+            )]
+            trait EntityFieldClone {}
+            impl<T: ::core::clone::Clone> EntityFieldClone for T {}
+
+            #[diagnostic::on_unimplemented(
+                message = "`#[derive(Entity)]` requires updated fields to implement `PartialEq`",
+                label = "`{Self}` is not `PartialEq`, so changes to it cannot be detected"
+            )]
+            trait EntityFieldPartialEq {}
+            impl<T: ::core::cmp::PartialEq> EntityFieldPartialEq for T {}
+
+            fn entity_field_clone<T: EntityFieldClone>() {}
+            fn entity_field_partial_eq<T: EntityFieldPartialEq>() {}
+
+            #[allow(dead_code)]
+            fn entity_fields() {
+                #(#clone)*
+                #(#partial_eq)*
+            }
+        };
+    }
+}
+
+/// Generates `<Entity>Patch`, the all-`Option` companion struct used by `patch_one`.
+fn implement_patch(
+    patch_name: &syn::Ident,
+    vis: &syn::Visibility,
+    fields: &[&Field],
+    version: Option<&Field>,
+    audit: &AuditFields,
+) -> TokenStream2 {
+    let patch_fields = fields.iter().map(|f| {
+        let ident = &f.ident;
+        let field_vis = &f.vis;
+        let ty = &f.ty;
+        quote! { #field_vis #ident: Option<#ty> }
+    });
+
+    // The version field is the expected version, not a value to write.
+    let written: Vec<_> = fields
+        .iter()
+        .copied()
+        .filter(|f| !is_field(f, version))
+        .collect();
+    let idents: Vec<_> = written.iter().map(|f| f.ident.as_ref().unwrap()).collect();
+
+    let expected_version = match version {
+        Some(f) => {
+            let ident = f.ident.as_ref().unwrap();
+            quote! { self.#ident.map(i64::from) }
+        }
+        None => quote! { None },
+    };
+
+    let stamped = if audit.updated_at.is_none() && audit.updated_by.is_none() {
+        quote! { None }
+    } else {
+        let assigns = fields.iter().map(|f| {
+            let ident = f.ident.as_ref().unwrap();
+            let now = quote! { ::core::convert::From::from(stamp.now()) };
+            if is_field(f, audit.updated_at) {
+                if is_option(&f.ty) {
+                    quote! { #ident: Some(Some(#now)) }
+                } else {
+                    quote! { #ident: Some(#now) }
+                }
+            } else if is_field(f, audit.updated_by) {
+                if is_option(&f.ty) {
+                    quote! { #ident: stamp.actor().map(Some).or_else(|| self.#ident.clone()) }
+                } else {
+                    quote! { #ident: stamp.actor().or_else(|| self.#ident.clone()) }
+                }
+            } else {
+                quote! { #ident: ::core::clone::Clone::clone(&self.#ident) }
+            }
+        });
+        quote! { Some(Self { #(#assigns),* }) }
+    };
+
+    let checks = written.iter().filter_map(|f| {
+        let ident = f.ident.as_ref().unwrap();
+        let checks = field_checks(f)?;
+        Some(if is_option(&f.ty) {
+            quote! { if let Some(Some(value)) = &self.#ident { #checks } }
+        } else {
+            quote! { if let Some(value) = &self.#ident { #checks } }
+        })
+    });
+    let validate_body = validate_body(checks);
+
+    let is_empty = if idents.is_empty() {
+        quote! { true }
+    } else {
+        quote! { #(self.#idents.is_none())&&* }
+    };
+
+    let columns_method = if cfg!(any(
+        feature = "postgres",
+        feature = "sqlite",
+        feature = "mysql"
+    )) {
+        let column_names: Vec<_> = idents.iter().map(|i| i.to_string()).collect();
+        quote! {
+            /// Columns of the fields set to `Some`, in declaration order.
+            pub fn columns(&self) -> Vec<&'static str> {
+                let mut columns = Vec::new();
+                #(if self.#idents.is_some() { columns.push(#column_names); })*
+                columns
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    let postgres_methods = if cfg!(feature = "postgres") {
+        let encode_bounds = deferred_bounds(&written, encode_bound());
+        quote! {
+            /// Values of the fields set to `Some`, matching [`Self::columns`].
+            pub fn values(&self) -> ::rustling_data::PgValues #encode_bounds {
+                let mut values = ::rustling_data::PgValues::new();
+                #(if let Some(v) = &self.#idents { values.push(v); })*
+                values
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    let sqlite_methods = if cfg!(feature = "sqlite") {
+        let encode_bounds = deferred_bounds(&written, sqlite_encode_bound());
+        quote! {
+            /// Values of the fields set to `Some`, matching [`Self::columns`].
+            pub fn sqlite_values(&self) -> ::rustling_data::SqliteValues<'_> #encode_bounds {
+                let mut values = ::rustling_data::SqliteValues::new();
+                #(if let Some(v) = &self.#idents { values.push(v); })*
+                values
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    let mysql_methods = if cfg!(feature = "mysql") {
+        let encode_bounds = deferred_bounds(&written, mysql_encode_bound());
+        quote! {
+            /// Values of the fields set to `Some`, matching [`Self::columns`].
+            pub fn mysql_values(&self) -> ::rustling_data::MySqlValues #encode_bounds {
+                let mut values = ::rustling_data::MySqlValues::new();
+                #(if let Some(v) = &self.#idents { values.push(v); })*
+                values
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    let mongo_methods = if cfg!(feature = "mongo") {
+        let updates = written.iter().map(|f| {
+            let ident = &f.ident;
+            let key = serde_name(f);
+            if is_option(&f.ty) {
+                quote! {
+                    match &self.#ident {
+                        Some(v) if v.is_some() => { set.insert(#key, ::rustling_data::bson::to_bson(v)?); }
+                        Some(_) => { unset.insert(#key, ""); }
+                        None => {}
+                    }
+                }
+            } else {
+                quote! {
+                    if let Some(v) = &self.#ident {
+                        set.insert(#key, ::rustling_data::bson::to_bson(v)?);
+                    }
+                }
+            }
+        });
+        let serialize_bounds =
+            deferred_bounds(&written, quote! { ::rustling_data::serde::Serialize });
+        quote! {
+            /// Builds the `$set`/`$unset` update document; empty when no field is set.
+            pub fn update_document(&self) -> Result<::rustling_data::bson::Document, ::rustling_data::bson::ser::Error> #serialize_bounds {
+                let mut set = ::rustling_data::bson::Document::new();
+                let mut unset = ::rustling_data::bson::Document::new();
+                #(#updates)*
+
+                let mut update = ::rustling_data::bson::Document::new();
+                if !set.is_empty() {
+                    update.insert("$set", set);
+                }
+                if !unset.is_empty() {
+                    update.insert("$unset", unset);
+                }
+                Ok(update)
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    quote! {
+        #[derive(Default)]
+        #vis struct #patch_name {
+            #(#patch_fields),*
+        }
+
+        impl #patch_name {
+            /// Version the stored entity must have for the patch to apply, if versioned.
+            pub fn expected_version(&self) -> Option<i64> {
+                #expected_version
+            }
+
+            /// Whether no field is set, so the patch would not write anything.
+            pub fn is_empty(&self) -> bool {
+                #is_empty
+            }
+
+            /// Runs the entity's validation attributes on the fields set to `Some`.
+            pub fn validate(&self) -> Result<(), Vec<::rustling_data::validate::FieldError>> {
+                #validate_body
+            }
+
+            /// Copy of the patch with the `updated_*` audit fields set, if the entity has any.
+            pub fn stamped(&self, stamp: &::rustling_data::audit::AuditStamp) -> Option<Self> {
+                #stamped
+            }
+
+            #columns_method
+            #postgres_methods
+            #sqlite_methods
+            #mysql_methods
+            #mongo_methods
+        }
+    }
+}
+
+/// Implements `MemoryEntity<ID>` for every `ID` the `id` field can be built from.
+fn implement_memory_entity(
+    name: &syn::Ident,
+    id: &Field,
+    update_idents: &[&syn::Ident],
+    patch_fields: &[&Field],
+    version: Option<&Field>,
+    soft_delete: Option<&Field>,
+) -> TokenStream2 {
+    let id_type = &id.ty;
+    let patch_idents: Vec<_> = patch_fields
+        .iter()
+        .filter(|f| !is_field(f, version))
+        .map(|f| f.ident.as_ref().unwrap())
+        .collect();
+    let mut bounds = vec![quote! { #id_type: ::core::convert::From<__ID> }];
+
+    let bump_version = match version {
+        Some(f) => {
+            let ident = f.ident.as_ref().unwrap();
+            quote! {
+                fn bump_version(&mut self) {
+                    self.#ident += 1;
+                }
+            }
+        }
+        None => quote! {},
+    };
+    let soft_delete_items = match soft_delete {
+        Some(f) => {
+            let ident = f.ident.as_ref().unwrap();
+            let (is_deleted, set_deleted) = match option_type(&f.ty) {
+                Some(inner) => {
+                    bounds.push(
+                        quote! { for<'__r> #inner: ::core::convert::From<::std::time::SystemTime> },
+                    );
+                    (
+                        quote! { self.#ident.is_some() },
+                        quote! {
+                            if deleted {
+                                Some(::core::convert::From::from(::rustling_data::audit::now()))
+                            } else {
+                                None
+                            }
+                        },
+                    )
+                }
+                None => (quote! { self.#ident }, quote! { deleted }),
+            };
+            quote! {
+                fn is_deleted(&self) -> bool {
+                    #is_deleted
+                }
+
+                fn set_deleted(&mut self, deleted: bool) {
+                    self.#ident = #set_deleted;
+                }
+            }
+        }
+        None => quote! {},
+    };
+
+    quote! {
+        impl<__ID> ::rustling_data::memory::MemoryEntity<__ID> for #name
+        where
+            #(#bounds),*
+        {
+            fn set_id(&mut self, id: __ID) {
+                self.id = ::core::convert::From::from(id);
+            }
+
+            fn apply_update(&mut self, entity: &Self) {
+                #(self.#update_idents = ::core::clone::Clone::clone(&entity.#update_idents);)*
+            }
+
+            fn apply_patch(&mut self, patch: &Self::Patch, stamp: &::rustling_data::audit::AuditStamp) {
+                let stamped = patch.stamped(stamp);
+                let patch = stamped.as_ref().unwrap_or(patch);
+                #(if let Some(value) = &patch.#patch_idents { self.#patch_idents = ::core::clone::Clone::clone(value); })*
+            }
+
+            fn is_empty_patch(patch: &Self::Patch) -> bool {
+                patch.is_empty()
+            }
+
+            fn expected_version(patch: &Self::Patch) -> Option<i64> {
+                patch.expected_version()
+            }
+
+            fn validate_patch(patch: &Self::Patch) -> Result<(), Vec<::rustling_data::validate::FieldError>> {
+                patch.validate()
+            }
+
+            #bump_version
+            #soft_delete_items
+        }
+    }
+}
+
+/// The `#[rustling(created_at)]`, `#[rustling(updated_at)]`, `#[rustling(created_by)]` and
+/// `#[rustling(updated_by)]` fields of an entity.
+struct AuditFields<'a> {
+    created_at: Option<&'a Field>,
+    updated_at: Option<&'a Field>,
+    created_by: Option<&'a Field>,
+    updated_by: Option<&'a Field>,
+}
+
+impl AuditFields<'_> {
+    fn is_created(&self, field: &Field) -> bool {
+        is_field(field, self.created_at) || is_field(field, self.created_by)
+    }
+
+    /// Statements setting the given timestamp and actor fields of `self` from `stamp`.
+    fn entity_stamps(
+        &self,
+        timestamps: &[Option<&Field>],
+        actors: &[Option<&Field>],
+    ) -> TokenStream2 {
+        let timestamps = timestamps.iter().flatten().map(|f| {
+            let ident = f.ident.as_ref().unwrap();
+            if is_option(&f.ty) {
+                quote! { self.#ident = Some(::core::convert::From::from(stamp.now())); }
+            } else {
+                quote! { self.#ident = ::core::convert::From::from(stamp.now()); }
+            }
+        });
+        let actors = actors.iter().flatten().map(|f| {
+            let ident = f.ident.as_ref().unwrap();
+            let value = if is_option(&f.ty) {
+                quote! { Some(actor) }
+            } else {
+                quote! { actor }
+            };
+            quote! {
+                if let Some(actor) = stamp.actor() {
+                    self.#ident = #value;
+                }
+            }
+        });
+        quote! { #(#timestamps)* #(#actors)* }
+    }
+}
+
+/// Checks of the validation attributes on `field`, run against a `value` reference.
+fn field_checks(field: &Field) -> Option<TokenStream2> {
+    let validations = parse_field_attrs(field).validations;
+    if validations.is_empty() {
+        return None;
+    }
+    let name = field.ident.as_ref().unwrap().to_string();
+    let checks = validations.iter().map(|v| v.check(&name));
+    Some(quote! { #({ #checks })* })
+}
+
+/// `Column` literal for a field of the entity's `TableSchema`.
+fn schema_column(field: &Field) -> TokenStream2 {
+    let attrs = parse_field_attrs(field);
+    let name = field.ident.as_ref().unwrap().to_string();
+    let primary_key = name == "id";
+    let nullable = is_option(&field.ty) && !primary_key;
+    let sql_type = attrs.sql_type.or_else(|| sql_type(&field.ty));
+    let default = attrs.default.or_else(|| {
+        (primary_key && sql_type.as_deref() == Some("UUID")).then(|| "gen_random_uuid()".into())
+    });
+    let sql_type = option(&sql_type);
+    let default = option(&default);
+    quote! {
+        ::rustling_data::schema::Column {
+            name: #name,
+            sql_type: #sql_type,
+            nullable: #nullable,
+            primary_key: #primary_key,
+            default: #default,
+        }
+    }
+}
+
+/// `$jsonSchema` of a field type: the BSON types serde writes it as.
+struct BsonSchema {
+    types: Vec<&'static str>,
+    items: Option<Box<BsonSchema>>,
+}
+
+impl BsonSchema {
+    fn to_document(&self) -> TokenStream2 {
+        let types = &self.types;
+        let bson_type = match types.as_slice() {
+            [single] => quote! { #single },
+            _ => quote! { [#(#types),*] },
+        };
+        match &self.items {
+            Some(items) => {
+                let items = items.to_document();
+                quote! { ::rustling_data::bson::doc! { "bsonType": #bson_type, "items": #items } }
+            }
+            None => quote! { ::rustling_data::bson::doc! { "bsonType": #bson_type } },
+        }
+    }
+}
+
+/// BSON schema of a field type, looking into `Option` and `Vec`; `None` for types it does not
+/// know, which are left unchecked.
+fn bson_schema(ty: &syn::Type) -> Option<BsonSchema> {
+    let syn::Type::Path(p) = ty else {
+        return None;
+    };
+    if p.qself.is_some() {
+        return None;
+    }
+    let segment = p.path.segments.last()?;
+    let argument = match &segment.arguments {
+        syn::PathArguments::AngleBracketed(args) => args.args.iter().find_map(|a| match a {
+            syn::GenericArgument::Type(ty) => Some(ty),
+            _ => None,
+        }),
+        _ => None,
+    };
+    let types: &[&'static str] = match segment.ident.to_string().as_str() {
+        "Option" => {
+            let mut schema = bson_schema(argument?)?;
+            schema.types.push("null");
+            return Some(schema);
+        }
+        "Vec" => {
+            return Some(BsonSchema {
+                types: vec!["array"],
+                items: argument.and_then(bson_schema).map(Box::new),
+            });
+        }
+        "String" => &["string"],
+        "bool" => &["bool"],
+        "i8" | "i16" | "i32" | "u8" | "u16" => &["int"],
+        "i64" | "u32" | "u64" => &["int", "long"],
+        "f32" | "f64" => &["double"],
+        "ObjectId" => &["objectId"],
+        // bson's `DateTime`; chrono's is generic and written as a string.
+        "DateTime" if argument.is_none() => &["date"],
+        "Document" | "HashMap" | "BTreeMap" => &["object"],
+        "Decimal128" => &["decimal"],
+        "Binary" => &["binData"],
+        "Timestamp" => &["timestamp"],
+        _ => return None,
+    };
+    Some(BsonSchema {
+        types: types.to_vec(),
+        items: None,
+    })
+}
+
+/// Postgres type of a field type, looking through `Option`; `None` for unknown types.
+fn sql_type(ty: &syn::Type) -> Option<String> {
+    let syn::Type::Path(p) = ty else {
+        return None;
+    };
+    if p.qself.is_some() {
+        return None;
+    }
+    let segment = p.path.segments.last()?;
+    let argument = match &segment.arguments {
+        syn::PathArguments::AngleBracketed(args) => args.args.iter().find_map(|a| match a {
+            syn::GenericArgument::Type(ty) => Some(ty),
+            _ => None,
+        }),
+        _ => None,
+    };
+    let sql_type = match segment.ident.to_string().as_str() {
+        "Option" => return sql_type(argument?),
+        "Vec"
+            if argument
+                .is_some_and(|a| matches!(a, syn::Type::Path(p) if p.path.is_ident("u8"))) =>
+        {
+            "BYTEA"
+        }
+        "Vec" => return sql_type(argument?).map(|t| t + "[]"),
+        "bool" => "BOOLEAN",
+        // sqlx encodes `i8` as the one-byte `"char"`, not as an integer type.
+        "i8" => "\"char\"",
+        "i16" => "SMALLINT",
+        "i32" => "INTEGER",
+        "i64" => "BIGINT",
+        "f32" => "REAL",
+        "f64" => "DOUBLE PRECISION",
+        "String" => "TEXT",
+        "Uuid" => "UUID",
+        "Decimal" | "BigDecimal" => "NUMERIC",
+        "NaiveDate" | "Date" => "DATE",
+        "NaiveTime" | "Time" => "TIME",
+        "NaiveDateTime" | "PrimitiveDateTime" => "TIMESTAMP",
+        "DateTime" | "OffsetDateTime" => "TIMESTAMPTZ",
+        "Value" | "JsonValue" | "Json" => "JSONB",
+        _ => return None,
+    };
+    Some(sql_type.to_string())
+}
+
+/// The field marked `#[rustling(<attr>)]`, panicking if there are several.
+fn single_field<'a>(
+    fields: &[&'a Field],
+    attr: &str,
+    marked: impl Fn(&FieldAttrs) -> bool,
+) -> Option<&'a Field> {
+    let mut marked_fields = fields
+        .iter()
+        .copied()
+        .filter(|f| marked(&parse_field_attrs(f)));
+    let field = marked_fields.next();
+    if marked_fields.next().is_some() {
+        panic!("Only one field can be marked #[rustling({})]", attr);
+    }
+    field
+}
+
+/// `where` clause requiring `bound` on every field type.
+///
+/// The bounds are higher-ranked so the compiler only checks them where the method is used:
+/// a Postgres-only entity still derives `Entity` when the Mongo backend is enabled too.
+fn deferred_bounds(fields: &[&Field], bound: TokenStream2) -> TokenStream2 {
+    let types = fields.iter().map(|f| &f.ty);
+    quote! { where #(for<'__r> #types: #bound),* }
+}
+
+fn encode_bound() -> TokenStream2 {
+    quote! {
+        ::rustling_data::sqlx::Encode<'__r, ::rustling_data::sqlx::Postgres>
+            + ::rustling_data::sqlx::Type<::rustling_data::sqlx::Postgres>
+    }
+}
+
+fn sqlite_encode_bound() -> TokenStream2 {
+    quote! {
+        ::rustling_data::sqlx::Encode<'__r, ::rustling_data::sqlx::Sqlite>
+            + ::rustling_data::sqlx::Type<::rustling_data::sqlx::Sqlite>
+    }
+}
+
+fn mysql_encode_bound() -> TokenStream2 {
+    quote! {
+        ::rustling_data::sqlx::Encode<'__r, ::rustling_data::sqlx::MySql>
+            + ::rustling_data::sqlx::Type<::rustling_data::sqlx::MySql>
+    }
+}
+
+fn is_field(field: &Field, other: Option<&Field>) -> bool {
+    other.is_some_and(|o| std::ptr::eq(field, o))
+}
+
+fn is_bool(ty: &syn::Type) -> bool {
+    matches!(ty, syn::Type::Path(p) if p.qself.is_none() && p.path.is_ident("bool"))
+}
+
+/// `T` for an `Option<T>` field type.
+fn option_type(ty: &syn::Type) -> Option<&syn::Type> {
+    let syn::Type::Path(p) = ty else {
+        return None;
+    };
+    let segment = p.path.segments.last().filter(|s| s.ident == "Option")?;
+    match &segment.arguments {
+        syn::PathArguments::AngleBracketed(args) => match args.args.first()? {
+            syn::GenericArgument::Type(inner) => Some(inner),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn is_option(ty: &syn::Type) -> bool {
+    match ty {
+        syn::Type::Path(p) if p.qself.is_none() => {
+            p.path.segments.last().is_some_and(|s| s.ident == "Option")
+        }
+        _ => false,
+    }
+}
+
+/// `FieldName` literal for a field: its name as column, its serde name as document key.
+fn field_name(field: &Field) -> TokenStream2 {
+    let column = field.ident.as_ref().unwrap().to_string();
+    let key = serde_name(field);
+    quote! { ::rustling_data::api::FieldName { column: #column, key: #key } }
+}
+
+/// Document key of a field: its `#[serde(rename = "...")]` if present, otherwise its name.
+fn serde_name(field: &Field) -> String {
+    serde_attrs(field).name
+}
+
+/// How serde writes a field, as far as its `#[serde(...)]` attributes tell.
+struct SerdeAttrs {
+    name: String,
+    /// Never written (`skip`, `skip_serializing`) or merged into the parent (`flatten`).
+    skipped: bool,
+    /// May be left out of the document (`skip_serializing_if`) or read as a default (`default`).
+    optional: bool,
+}
+
+fn serde_attrs(field: &Field) -> SerdeAttrs {
+    let mut attrs = SerdeAttrs {
+        name: field.ident.as_ref().unwrap().to_string(),
+        skipped: false,
+        optional: false,
+    };
+    for attr in field.attrs.iter().filter(|a| a.path().is_ident("serde")) {
+        let _ = attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("rename") {
+                let lit: syn::LitStr = meta.value()?.parse()?;
+                attrs.name = lit.value();
+            } else if meta.path.is_ident("skip")
+                || meta.path.is_ident("skip_serializing")
+                || meta.path.is_ident("flatten")
+            {
+                attrs.skipped = true;
+            } else if meta.path.is_ident("skip_serializing_if") || meta.path.is_ident("default") {
+                attrs.optional = true;
+            }
+            if meta.input.peek(syn::Token![=]) {
+                let _: syn::Expr = meta.value()?.parse()?;
+            }
+            Ok(())
+        });
+    }
+    attrs
+}
@@ -0,0 +1,89 @@
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::meta::ParseNestedMeta;
+
+use crate::common::option;
+
+/// An index attribute: `index`, `index(...)` or `unique` on a field, `index(fields(...), ...)`
+/// on the struct.
+#[derive(Default)]
+pub struct IndexAttrs {
+    /// Fields of a struct-level index.
+    pub fields: Vec<syn::Ident>,
+    pub unique: bool,
+    name: Option<syn::LitStr>,
+    predicate: Option<syn::LitStr>,
+    text: bool,
+    sparse: bool,
+    ttl: Option<syn::LitInt>,
+    partial: Option<syn::LitStr>,
+}
+
+impl IndexAttrs {
+    /// Parses the options of an `index` attribute, if it has any.
+    pub fn parse(meta: &ParseNestedMeta) -> syn::Result<Self> {
+        let mut attrs = IndexAttrs::default();
+        if !meta.input.peek(syn::token::Paren) {
+            return Ok(attrs);
+        }
+        meta.parse_nested_meta(|option| {
+            if option.path.is_ident("fields") {
+                option.parse_nested_meta(|field| {
+                    let ident = field
+                        .path
+                        .get_ident()
+                        .ok_or_else(|| field.error("expected a field name"))?;
+                    attrs.fields.push(ident.clone());
+                    Ok(())
+                })
+            } else if option.path.is_ident("unique") {
+                attrs.unique = true;
+                Ok(())
+            } else if option.path.is_ident("name") {
+                attrs.name = Some(option.value()?.parse()?);
+                Ok(())
+            } else if option.path.is_ident("predicate") {
+                attrs.predicate = Some(option.value()?.parse()?);
+                Ok(())
+            } else if option.path.is_ident("text") {
+                attrs.text = true;
+                Ok(())
+            } else if option.path.is_ident("sparse") {
+                attrs.sparse = true;
+                Ok(())
+            } else if option.path.is_ident("ttl") {
+                attrs.ttl = Some(option.value()?.parse()?);
+                Ok(())
+            } else if option.path.is_ident("partial") {
+                attrs.partial = Some(option.value()?.parse()?);
+                Ok(())
+            } else {
+                Err(option.error("unsupported index option"))
+            }
+        })?;
+        Ok(attrs)
+    }
+
+    /// `Index` literal over the given `FieldName` literals.
+    pub fn to_index(&self, fields: &[TokenStream2]) -> TokenStream2 {
+        let unique = self.unique;
+        let text = self.text;
+        let sparse = self.sparse;
+        let name = option(&self.name);
+        let predicate = option(&self.predicate);
+        let ttl = option(&self.ttl);
+        let partial = option(&self.partial);
+        quote! {
+            ::rustling_data::schema::Index {
+                name: #name,
+                fields: &[#(#fields),*],
+                unique: #unique,
+                predicate: #predicate,
+                text: #text,
+                sparse: #sparse,
+                ttl: #ttl,
+                partial: #partial,
+            }
+        }
+    }
+}
@@ -7,9 +7,13 @@
 //!
 //! ## ✨ Available Macros
 //!
-//! - `#[derive(Repository)]` — derive a PostgreSQL repository implementation
+//! - `#[derive(Repository)]` — derive a PostgreSQL repository implementation, or a SQLite or
+//!   MySQL one with `#[backend(sqlite)]` / `#[backend(mysql)]`
 //! - `#[derive(MongoRepository)]` — derive a MongoDB repository implementation
-//! - `#[derive(Entity)]` — derive helper methods for SQL entities (columns & values)
+//! - `#[derive(RedisRepository)]` — derive a Redis repository storing serialized entities
+//! - `#[backend(memory)]` on any of them — an `InMemoryRepository` with the same API, typically
+//!   applied as `#[cfg_attr(test, backend(memory))]` for unit tests
+//! - `#[derive(Entity)]` — derive helper methods for entities (columns & values) and a `<Entity>Patch` struct
 //!
 //! ## 💡 Example
 //! ```rust,no_run
@@ -34,62 +38,56 @@
 extern crate proc_macro;
 
 use proc_macro::TokenStream;
-use quote::quote;
-use syn::{Data, DeriveInput, Fields, parse_macro_input};
 
 mod common;
+mod entity_macro;
+mod index;
+mod validation;
 
+#[cfg(any(
+    feature = "postgres",
+    feature = "sqlite",
+    feature = "mysql",
+    feature = "mongo",
+    feature = "redis"
+))]
+mod memory_macro;
 #[cfg(feature = "mongo")]
 mod mongo_macro;
+#[cfg(feature = "mysql")]
+mod mysql_macro;
 #[cfg(feature = "postgres")]
 mod postgres_macro;
+#[cfg(feature = "redis")]
+mod redis_macro;
+#[cfg(any(feature = "postgres", feature = "sqlite", feature = "mysql"))]
+mod sql_macro;
+#[cfg(feature = "sqlite")]
+mod sqlite_macro;
 
-#[cfg(feature = "postgres")]
-#[proc_macro_derive(Repository, attributes(entity, id, table))]
+#[cfg(any(feature = "postgres", feature = "sqlite", feature = "mysql"))]
+#[proc_macro_derive(Repository, attributes(entity, id, table, backend))]
 pub fn repository_derive(input: TokenStream) -> TokenStream {
-    postgres_macro::repository_derive(input)
+    sql_macro::repository_derive(input)
 }
 
 #[cfg(feature = "mongo")]
-#[proc_macro_derive(MongoRepository, attributes(entity, id, collection))]
+#[proc_macro_derive(MongoRepository, attributes(entity, id, collection, backend))]
 pub fn mongo_repository_derive(input: TokenStream) -> TokenStream {
     mongo_macro::mongo_repository_derive(input)
 }
 
-#[proc_macro_derive(Entity)]
-pub fn derive_entity(input: TokenStream) -> TokenStream {
-    let ast = parse_macro_input!(input as DeriveInput);
-    let name = &ast.ident;
-
-    let fields: Vec<_> = match &ast.data {
-        Data::Struct(data) => match &data.fields {
-            Fields::Named(fields_named) => fields_named
-                .named
-                .iter()
-                .filter(|f| f.ident.as_ref().unwrap() != "id")
-                .collect(),
-            _ => panic!("Entity derive only supports named fields"),
-        },
-        _ => panic!("Entity derive only supports structs"),
-    };
-
-    let column_names: Vec<_> = fields
-        .iter()
-        .map(|f| f.ident.as_ref().unwrap().to_string())
-        .collect();
-    let field_idents: Vec<_> = fields.iter().map(|f| f.ident.as_ref().unwrap()).collect();
-
-    let gene = quote! {
-        impl #name {
-            pub fn columns() -> &'static [&'static str] {
-                &[#(#column_names),*]
-            }
-
-            pub fn values<'e>(&'e self) -> Vec<&'e (impl sqlx::Encode<'e, sqlx::Postgres> + sqlx::Type<sqlx::Postgres>)> {
-                vec![#(&self.#field_idents),*]
-            }
-        }
-    };
+#[cfg(feature = "redis")]
+#[proc_macro_derive(
+    RedisRepository,
+    attributes(entity, id, prefix, codec, ttl, indexed, backend)
+)]
+pub fn redis_repository_derive(input: TokenStream) -> TokenStream {
+    redis_macro::redis_repository_derive(input)
+}
 
-    gene.into()
+/// Every field must be `Clone`, and every field written by updates must be `PartialEq`.
+#[proc_macro_derive(Entity, attributes(rustling))]
+pub fn derive_entity(input: TokenStream) -> TokenStream {
+    entity_macro::entity_derive(input)
 }
@@ -0,0 +1,86 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+
+use crate::common::{parse_repository_meta, repository_impl};
+
+/// `#[backend(memory)]`: a repository delegating to an `InMemoryRepository` in its `store`
+/// field, with the error type and inherent soft-delete methods of the backend it replaces.
+pub fn implement_repository_trait(
+    ast: &syn::DeriveInput,
+    storage_attr: &str,
+    db_error: TokenStream2,
+) -> TokenStream {
+    let name = &ast.ident;
+    let meta = parse_repository_meta(ast, storage_attr);
+
+    let entity_type = meta.entity;
+    let id_type = meta.id;
+    let result = |ok: TokenStream2| {
+        quote! { Result<#ok, ::rustling_data::api::RepositoryError<#db_error>> }
+    };
+    let entities = result(quote! { Vec<#entity_type> });
+    let entity = result(quote! { Option<#entity_type> });
+    let id = result(quote! { #id_type });
+    let affected = result(quote! { u64 });
+
+    let repository = repository_impl(name, &entity_type, &id_type, &db_error);
+
+    let gene = quote! {
+        impl #name {
+            /// Loads all entities, including soft-deleted ones.
+            pub async fn find_with_deleted(&self) -> #entities {
+                Ok(self.store.find_with_deleted())
+            }
+
+            /// Loads an entity even if it is soft-deleted.
+            pub async fn find_one_with_deleted(&self, id: &#id_type) -> #entity {
+                Ok(self.store.find_one_with_deleted(id))
+            }
+
+            /// Undoes a soft delete; returns the number of restored entities.
+            pub async fn restore_one(&self, id: &#id_type) -> #affected {
+                match <#entity_type as ::rustling_data::api::Entity>::SOFT_DELETE {
+                    Some(_) => Ok(self.store.restore_one(id)),
+                    None => Err(::rustling_data::api::RepositoryError::Other(format!("{} has no #[rustling(soft_delete)] field", stringify!(#entity_type)))),
+                }
+            }
+
+            /// Permanently deletes an entity, whether or not it is soft-deleted.
+            pub async fn purge_one(&self, id: &#id_type) -> #affected {
+                Ok(self.store.purge_one(id))
+            }
+        }
+
+        #[async_trait::async_trait]
+        impl ::rustling_data::api::CrudRepository<#entity_type, #id_type, #db_error> for #name {
+            async fn find_all(&self) -> #entities {
+                ::rustling_data::api::CrudRepository::<#entity_type, #id_type, #db_error>::find_all(&self.store).await
+            }
+
+            async fn find_one(&self, id: &#id_type) -> #entity {
+                ::rustling_data::api::CrudRepository::<#entity_type, #id_type, #db_error>::find_one(&self.store, id).await
+            }
+
+            async fn insert_one(&self, entity: &#entity_type) -> #id {
+                ::rustling_data::api::CrudRepository::<#entity_type, #id_type, #db_error>::insert_one(&self.store, entity).await
+            }
+
+            async fn update_one(&self, id: &#id_type, entity: &#entity_type) -> #entity {
+                ::rustling_data::api::CrudRepository::<#entity_type, #id_type, #db_error>::update_one(&self.store, id, entity).await
+            }
+
+            async fn patch_one(&self, id: &#id_type, patch: &::rustling_data::api::Patch<#entity_type>) -> #entity {
+                ::rustling_data::api::CrudRepository::<#entity_type, #id_type, #db_error>::patch_one(&self.store, id, patch).await
+            }
+
+            async fn delete_one(&self, id: &#id_type) -> #affected {
+                ::rustling_data::api::CrudRepository::<#entity_type, #id_type, #db_error>::delete_one(&self.store, id).await
+            }
+        }
+
+        #repository
+    };
+
+    gene.into()
+}
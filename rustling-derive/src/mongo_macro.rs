@@ -1,11 +1,19 @@
-use crate::common::parse_repository_meta;
+use crate::common::{parse_backend, parse_repository_meta, repository_impl};
 use proc_macro::TokenStream;
 use quote::quote;
-use syn;
 
+/// `#[derive(MongoRepository)]`, or an in-memory repository with `#[backend(memory)]`.
 pub fn mongo_repository_derive(input: TokenStream) -> TokenStream {
     let syntax_tree = syn::parse(input).unwrap();
-    implement_mongo_repository_trait(&syntax_tree)
+    match parse_backend(&syntax_tree).as_deref() {
+        None | Some("mongo") => implement_mongo_repository_trait(&syntax_tree),
+        Some("memory") => crate::memory_macro::implement_repository_trait(
+            &syntax_tree,
+            "collection",
+            quote! { ::rustling_data::api::MongoError },
+        ),
+        Some(other) => panic!("Unsupported backend `{}`, expected mongo or memory", other),
+    }
 }
 
 fn implement_mongo_repository_trait(syntax_tree: &syn::DeriveInput) -> TokenStream {
@@ -16,37 +24,194 @@ fn implement_mongo_repository_trait(syntax_tree: &syn::DeriveInput) -> TokenStre
     let id = meta.id;
     let storage_name = syn::LitStr::new(&meta.storage_name, proc_macro2::Span::call_site());
 
+    let repository = repository_impl(
+        name,
+        &entity,
+        &id,
+        &quote! { ::rustling_data::api::MongoError },
+    );
+
     let generated = quote! {
         #[async_trait::async_trait]
         impl ::rustling_data::api::CrudRepository<#entity, #id, ::rustling_data::api::MongoError> for #name {
             async fn find_all(&self) -> Result<Vec<#entity>, ::rustling_data::api::RepositoryError<::rustling_data::api::MongoError>> {
                 let mongo_repo = ::rustling_data::MongoDriver::new(self.client.clone(), self.db_name.clone());
-                mongo_repo.find_all::<#entity>(#storage_name).await
+                let found = match <#entity as ::rustling_data::api::Entity>::SOFT_DELETE {
+                    Some(soft_delete) => {
+                        let filter = ::rustling_data::MongoDriver::not_deleted(&soft_delete);
+                        mongo_repo.find_where::<#entity>(#storage_name, filter).await?
+                    }
+                    None => mongo_repo.find_all::<#entity>(#storage_name).await?,
+                };
+                Ok(found.into_iter().map(<#entity as ::rustling_data::api::Lifecycle>::loaded).collect())
             }
 
             async fn find_one(&self, id: &#id) -> Result<Option<#entity>, ::rustling_data::api::RepositoryError<::rustling_data::api::MongoError>> {
                 let mongo_repo = ::rustling_data::MongoDriver::new(self.client.clone(), self.db_name.clone());
-                let filter = ::rustling_data::bson::doc! { "_id": id };
-                mongo_repo.find_one::<#entity>(#storage_name, filter).await
+                let mut filter = ::rustling_data::bson::doc! { "_id": id };
+                if let Some(soft_delete) = <#entity as ::rustling_data::api::Entity>::SOFT_DELETE {
+                    filter.extend(::rustling_data::MongoDriver::not_deleted(&soft_delete));
+                }
+                Ok(mongo_repo.find_one::<#entity>(#storage_name, filter).await?.map(<#entity as ::rustling_data::api::Lifecycle>::loaded))
             }
 
             async fn insert_one(&self, doc: &#entity) -> Result<::rustling_data::bson::oid::ObjectId, ::rustling_data::api::RepositoryError<::rustling_data::api::MongoError>> {
                 let mongo_repo = ::rustling_data::MongoDriver::new(self.client.clone(), self.db_name.clone());
-                mongo_repo.insert_one(#storage_name, doc).await
+                let mut doc = ::rustling_data::api::Entity::copy_for_write(doc);
+                ::rustling_data::api::Entity::stamp_insert(&mut doc, &::rustling_data::audit::AuditStamp::current());
+                <#entity as ::rustling_data::api::Lifecycle>::before_insert(&mut doc).map_err(::rustling_data::api::RepositoryError::Other)?;
+                ::rustling_data::api::Entity::validate(&doc).map_err(::rustling_data::api::RepositoryError::Validation)?;
+                let id = mongo_repo.insert_one(#storage_name, &doc).await?;
+                <#entity as ::rustling_data::api::Lifecycle>::after_insert(&doc);
+                if ::rustling_data::events::is_active() {
+                    // The insert already succeeded, so a failed reload only skips the event.
+                    if let Ok(Some(entity)) = self.find_one(&id).await {
+                        ::rustling_data::events::publish(::rustling_data::events::EntityInserted { entity });
+                    }
+                }
+                Ok(id)
             }
 
             async fn update_one(&self, id: &#id, doc: &#entity) -> Result<Option<#entity>, ::rustling_data::api::RepositoryError<::rustling_data::api::MongoError>> {
                 let mongo_repo = ::rustling_data::MongoDriver::new(self.client.clone(), self.db_name.clone());
-                let filter = ::rustling_data::bson::doc! { "_id": id };
-                mongo_repo.update_one(#storage_name, filter, doc).await
+                let mut filter = ::rustling_data::bson::doc! { "_id": id };
+                if let Some(soft_delete) = <#entity as ::rustling_data::api::Entity>::SOFT_DELETE {
+                    filter.extend(::rustling_data::MongoDriver::not_deleted(&soft_delete));
+                }
+                let mut doc = ::rustling_data::api::Entity::copy_for_write(doc);
+                ::rustling_data::api::Entity::stamp_update(&mut doc, &::rustling_data::audit::AuditStamp::current());
+                <#entity as ::rustling_data::api::Lifecycle>::before_update(&mut doc).map_err(::rustling_data::api::RepositoryError::Other)?;
+                ::rustling_data::api::Entity::validate(&doc).map_err(::rustling_data::api::RepositoryError::Validation)?;
+                let before = if ::rustling_data::events::is_active() { self.find_one(id).await? } else { None };
+                // `$set` of the updatable fields only, so `created_*` fields keep their stored values.
+                let update = doc
+                    .update_document()
+                    .map_err(|e| ::rustling_data::api::RepositoryError::Other(e.to_string()))?;
+                let updated = match <#entity as ::rustling_data::api::Entity>::VERSION_FIELD {
+                    Some(version) => {
+                        let expected = ::rustling_data::api::Entity::version(&doc);
+                        mongo_repo.patch_one_versioned::<#entity>(#storage_name, filter, update, version.key, expected).await?
+                    }
+                    None => mongo_repo.patch_one::<#entity>(#storage_name, filter, update).await?,
+                };
+                let updated = updated.map(<#entity as ::rustling_data::api::Lifecycle>::loaded);
+                if let Some(updated) = &updated {
+                    <#entity as ::rustling_data::api::Lifecycle>::after_update(updated);
+                    if let Some(before) = before {
+                        ::rustling_data::events::publish(::rustling_data::events::EntityUpdated { before, after: ::rustling_data::api::Entity::copy_for_write(updated) });
+                    }
+                }
+                Ok(updated)
+            }
+
+            async fn patch_one(&self, id: &#id, patch: &::rustling_data::api::Patch<#entity>) -> Result<Option<#entity>, ::rustling_data::api::RepositoryError<::rustling_data::api::MongoError>> {
+                let mongo_repo = ::rustling_data::MongoDriver::new(self.client.clone(), self.db_name.clone());
+                let mut filter = ::rustling_data::bson::doc! { "_id": id };
+                if let Some(soft_delete) = <#entity as ::rustling_data::api::Entity>::SOFT_DELETE {
+                    filter.extend(::rustling_data::MongoDriver::not_deleted(&soft_delete));
+                }
+                let update = patch
+                    .update_document()
+                    .map_err(|e| ::rustling_data::api::RepositoryError::Other(e.to_string()))?;
+                if update.is_empty() {
+                    return self.find_one(id).await;
+                }
+                patch.validate().map_err(::rustling_data::api::RepositoryError::Validation)?;
+                let before = if ::rustling_data::events::is_active() { self.find_one(id).await? } else { None };
+                let update = match patch.stamped(&::rustling_data::audit::AuditStamp::current()) {
+                    Some(stamped) => stamped
+                        .update_document()
+                        .map_err(|e| ::rustling_data::api::RepositoryError::Other(e.to_string()))?,
+                    None => update,
+                };
+                let updated = match <#entity as ::rustling_data::api::Entity>::VERSION_FIELD {
+                    Some(version) => {
+                        let expected = patch.expected_version();
+                        mongo_repo.patch_one_versioned::<#entity>(#storage_name, filter, update, version.key, expected).await?
+                    }
+                    None => mongo_repo.patch_one::<#entity>(#storage_name, filter, update).await?,
+                };
+                let updated = updated.map(<#entity as ::rustling_data::api::Lifecycle>::loaded);
+                if let Some(updated) = &updated {
+                    <#entity as ::rustling_data::api::Lifecycle>::after_update(updated);
+                    if let Some(before) = before {
+                        ::rustling_data::events::publish(::rustling_data::events::EntityUpdated { before, after: ::rustling_data::api::Entity::copy_for_write(updated) });
+                    }
+                }
+                Ok(updated)
             }
 
             async fn delete_one(&self, id: &#id) -> Result<u64, ::rustling_data::api::RepositoryError<::rustling_data::api::MongoError>> {
+                if <#entity as ::rustling_data::api::Entity>::LIFECYCLE {
+                    if let Some(entity) = self.find_one(id).await? {
+                        <#entity as ::rustling_data::api::Lifecycle>::before_delete(&entity).map_err(::rustling_data::api::RepositoryError::Other)?;
+                    }
+                }
+                let mongo_repo = ::rustling_data::MongoDriver::new(self.client.clone(), self.db_name.clone());
+                let filter = ::rustling_data::bson::doc! { "_id": id };
+                let affected = match <#entity as ::rustling_data::api::Entity>::SOFT_DELETE {
+                    Some(soft_delete) => mongo_repo.soft_delete_one(#storage_name, filter, &soft_delete).await?,
+                    None => mongo_repo.delete_one(#storage_name, filter).await?,
+                };
+                if affected > 0 {
+                    ::rustling_data::events::publish(::rustling_data::events::EntityDeleted { id: ::core::clone::Clone::clone(id) });
+                }
+                Ok(affected)
+            }
+        }
+
+        impl #name {
+            /// Loads all entities, including soft-deleted ones.
+            pub async fn find_with_deleted(&self) -> Result<Vec<#entity>, ::rustling_data::api::RepositoryError<::rustling_data::api::MongoError>> {
+                let mongo_repo = ::rustling_data::MongoDriver::new(self.client.clone(), self.db_name.clone());
+                let found = mongo_repo.find_all::<#entity>(#storage_name).await?;
+                Ok(found.into_iter().map(<#entity as ::rustling_data::api::Lifecycle>::loaded).collect())
+            }
+
+            /// Loads an entity even if it is soft-deleted.
+            pub async fn find_one_with_deleted(&self, id: &#id) -> Result<Option<#entity>, ::rustling_data::api::RepositoryError<::rustling_data::api::MongoError>> {
+                let mongo_repo = ::rustling_data::MongoDriver::new(self.client.clone(), self.db_name.clone());
+                let filter = ::rustling_data::bson::doc! { "_id": id };
+                Ok(mongo_repo.find_one::<#entity>(#storage_name, filter).await?.map(<#entity as ::rustling_data::api::Lifecycle>::loaded))
+            }
+
+            /// Undoes a soft delete; returns the number of restored documents.
+            pub async fn restore_one(&self, id: &#id) -> Result<u64, ::rustling_data::api::RepositoryError<::rustling_data::api::MongoError>> {
+                let mongo_repo = ::rustling_data::MongoDriver::new(self.client.clone(), self.db_name.clone());
+                let filter = ::rustling_data::bson::doc! { "_id": id };
+                match <#entity as ::rustling_data::api::Entity>::SOFT_DELETE {
+                    Some(soft_delete) => mongo_repo.restore_one(#storage_name, filter, &soft_delete).await,
+                    None => Err(::rustling_data::api::RepositoryError::Other(format!("{} has no #[rustling(soft_delete)] field", stringify!(#entity)))),
+                }
+            }
+
+            /// Creates the entity's `#[rustling(index)]` indexes on the collection.
+            pub async fn ensure_indexes(&self) -> Result<(), ::rustling_data::api::RepositoryError<::rustling_data::api::MongoError>> {
+                let mongo_repo = ::rustling_data::MongoDriver::new(self.client.clone(), self.db_name.clone());
+                mongo_repo.ensure_indexes(#storage_name, <#entity as ::rustling_data::api::Entity>::INDEXES).await
+            }
+
+            /// Makes the server validate writes against the entity's `json_schema()`.
+            pub async fn apply_json_schema(&self, options: ::rustling_data::ValidatorOptions) -> Result<(), ::rustling_data::api::RepositoryError<::rustling_data::api::MongoError>> {
+                let mongo_repo = ::rustling_data::MongoDriver::new(self.client.clone(), self.db_name.clone());
+                mongo_repo.apply_json_schema(#storage_name, #entity::json_schema(), options).await
+            }
+
+            /// Streams changes to the collection; see [`::rustling_data::MongoDriver::watch`].
+            pub async fn watch(&self, options: ::rustling_data::WatchOptions) -> Result<::rustling_data::ChangeEvents<#entity>, ::rustling_data::api::RepositoryError<::rustling_data::api::MongoError>> {
+                let mongo_repo = ::rustling_data::MongoDriver::new(self.client.clone(), self.db_name.clone());
+                mongo_repo.watch::<#entity>(#storage_name, options).await
+            }
+
+            /// Permanently deletes an entity, whether or not it is soft-deleted.
+            pub async fn purge_one(&self, id: &#id) -> Result<u64, ::rustling_data::api::RepositoryError<::rustling_data::api::MongoError>> {
                 let mongo_repo = ::rustling_data::MongoDriver::new(self.client.clone(), self.db_name.clone());
                 let filter = ::rustling_data::bson::doc! { "_id": id };
                 mongo_repo.delete_one(#storage_name, filter).await
             }
         }
+
+        #repository
     };
 
     generated.into()
@@ -0,0 +1,34 @@
+use proc_macro::TokenStream;
+use quote::quote;
+
+use crate::common::parse_repository_meta;
+use crate::sql_macro::{SqlBackend, crud_impl, soft_delete_methods};
+
+pub fn implement_repository_trait(ast: &syn::DeriveInput) -> TokenStream {
+    let name = &ast.ident;
+    let meta = parse_repository_meta(ast, "table");
+
+    let entity_type = meta.entity;
+    let id_type = meta.id;
+    let table_name = syn::LitStr::new(&meta.storage_name, proc_macro2::Span::call_site());
+    let backend = SqlBackend {
+        driver: quote! { rustling_data::MySqlDriver },
+        values: quote! { rustling_data::MySqlValues },
+        entity_values: syn::parse_quote!(mysql_values),
+        entity_update_values: syn::parse_quote!(mysql_update_values),
+        patch_values: syn::parse_quote!(mysql_values),
+        first_param: "?",
+    };
+    let soft_delete_methods = soft_delete_methods(&backend, &entity_type, &id_type, &table_name);
+    let crud_impl = crud_impl(&backend, name, &entity_type, &id_type, &table_name);
+
+    let gene = quote! {
+        impl #name {
+            #soft_delete_methods
+        }
+
+        #crud_impl
+    };
+
+    gene.into()
+}
@@ -1,56 +1,105 @@
 use proc_macro::TokenStream;
 use quote::quote;
-use syn::DeriveInput;
 
 use crate::common::parse_repository_meta;
+use crate::sql_macro::{SqlBackend, crud_impl, soft_delete_methods};
 
-pub fn repository_derive(input: TokenStream) -> TokenStream {
-    let ast: DeriveInput = syn::parse(input).unwrap();
-    implement_repository_trait(&ast)
-}
-
-fn implement_repository_trait(ast: &syn::DeriveInput) -> TokenStream {
+pub fn implement_repository_trait(ast: &syn::DeriveInput) -> TokenStream {
     let name = &ast.ident;
     let meta = parse_repository_meta(ast, "table");
 
     let entity_type = meta.entity;
     let id_type = meta.id;
     let table_name = syn::LitStr::new(&meta.storage_name, proc_macro2::Span::call_site());
+    let backend = SqlBackend {
+        driver: quote! { rustling_data::PostgresDriver },
+        values: quote! { rustling_data::PgValues },
+        entity_values: syn::parse_quote!(values),
+        entity_update_values: syn::parse_quote!(update_values),
+        patch_values: syn::parse_quote!(values),
+        first_param: "$1",
+    };
+    let soft_delete_methods = soft_delete_methods(&backend, &entity_type, &id_type, &table_name);
+    let crud_impl = crud_impl(&backend, name, &entity_type, &id_type, &table_name);
 
     let gene = quote! {
-        #[async_trait::async_trait]
-        impl rustling_data::api::CrudRepository<#entity_type, #id_type, sqlx::Error> for #name {
-            async fn find_all(&self) -> Result<Vec<#entity_type>, rustling_data::api::RepositoryError<sqlx::Error>> {
-                rustling_data::PostgresDriver::find_all(&self.pool, #table_name).await
+        impl #name {
+            pub async fn verify_columns(&self) -> Result<(), rustling_data::api::RepositoryError<sqlx::Error>> {
+                rustling_data::PostgresDriver::verify_columns(&self.pool, #table_name, #entity_type::all_columns()).await
+            }
+
+            /// Checks the table's columns, types, nullability and primary key against the entity.
+            pub async fn verify_schema(&self) -> Result<(), rustling_data::api::RepositoryError<sqlx::Error>> {
+                rustling_data::PostgresDriver::verify_schema(&self.pool, #table_name, #entity_type::table_schema()).await
             }
 
-            async fn find_one(&self, id: &#id_type) -> Result<Option<#entity_type>, rustling_data::api::RepositoryError<sqlx::Error>> {
-                rustling_data::PostgresDriver::find_one(&self.pool, #table_name, "id", *id).await
+            /// Loads the entity and locks its row `FOR UPDATE`; pass a transaction as `executor`.
+            pub async fn find_one_for_update<'e, E>(&self, executor: E, id: &#id_type) -> Result<Option<#entity_type>, rustling_data::api::RepositoryError<sqlx::Error>>
+            where
+                E: sqlx::Executor<'e, Database = sqlx::Postgres>,
+            {
+                match <#entity_type as rustling_data::api::Entity>::SOFT_DELETE {
+                    Some(soft_delete) => {
+                        let filter = format!("id = $1 AND {}", rustling_data::PostgresDriver::not_deleted(&soft_delete));
+                        let lock = rustling_data::LockMode::for_update();
+                        let mut found = rustling_data::PostgresDriver::find_where_for_update(executor, #table_name, #entity_type::all_columns(), &filter, vec![id], lock).await?;
+                        Ok(found.pop().map(<#entity_type as rustling_data::api::Lifecycle>::loaded))
+                    }
+                    None => Ok(rustling_data::PostgresDriver::find_one_for_update(executor, #table_name, #entity_type::all_columns(), "id", *id).await?.map(<#entity_type as rustling_data::api::Lifecycle>::loaded)),
+                }
             }
 
-            async fn insert_one(&self, entity: &#entity_type) -> Result<#id_type, rustling_data::api::RepositoryError<sqlx::Error>> {
-                let columns = #entity_type::columns();
-                let values = entity.values();
-                rustling_data::PostgresDriver::insert(&self.pool, #table_name, columns, values).await
+            /// Loads and locks the entities matching `filter`; pass a transaction as `executor`.
+            pub async fn find_where_for_update<'e, E>(
+                &self,
+                executor: E,
+                filter: &str,
+                values: impl Into<rustling_data::PgValues>,
+                lock: rustling_data::LockMode,
+            ) -> Result<Vec<#entity_type>, rustling_data::api::RepositoryError<sqlx::Error>>
+            where
+                E: sqlx::Executor<'e, Database = sqlx::Postgres>,
+            {
+                let filter = match <#entity_type as rustling_data::api::Entity>::SOFT_DELETE {
+                    Some(soft_delete) => format!("({}) AND {}", filter, rustling_data::PostgresDriver::not_deleted(&soft_delete)),
+                    None => filter.to_string(),
+                };
+                let found = rustling_data::PostgresDriver::find_where_for_update(executor, #table_name, #entity_type::all_columns(), &filter, values, lock).await?;
+                Ok(found.into_iter().map(<#entity_type as rustling_data::api::Lifecycle>::loaded).collect())
             }
 
-            async fn update_one(&self, id: &#id_type, entity: &#entity_type) -> Result<Option<#entity_type>, rustling_data::api::RepositoryError<sqlx::Error>> {
-                let columns = #entity_type::columns();
-                let values = entity.values();
-                let affected = rustling_data::PostgresDriver::update(&self.pool, #table_name, "id", *id, columns, values).await?;
-                if affected > 0 {
-                    self.find_one(id).await
-                } else {
-                    Ok(None)
-                }
+            #soft_delete_methods
+
+            /// Creates the table from the entity's `table_schema()`, unless it already exists.
+            pub async fn create_table(&self) -> Result<(), rustling_data::api::RepositoryError<sqlx::Error>> {
+                rustling_data::PostgresDriver::create_table(&self.pool, #table_name, #entity_type::table_schema()).await
             }
 
-            async fn delete_one(&self, id: &#id_type) -> Result<u64, rustling_data::api::RepositoryError<sqlx::Error>> {
-                rustling_data::PostgresDriver::delete(&self.pool, #table_name, "id", *id).await
+            /// Creates the entity's `#[rustling(index)]` indexes that are missing on the table.
+            pub async fn ensure_indexes(&self) -> Result<(), rustling_data::api::RepositoryError<sqlx::Error>> {
+                rustling_data::PostgresDriver::ensure_indexes(&self.pool, #table_name, <#entity_type as rustling_data::api::Entity>::INDEXES).await
+            }
+
+            pub async fn drop_table(&self) -> Result<(), rustling_data::api::RepositoryError<sqlx::Error>> {
+                rustling_data::PostgresDriver::drop_table(&self.pool, #table_name).await
+            }
+
+            /// Installs a trigger notifying `channel` of every row change in the table.
+            pub async fn create_notify_trigger(&self, channel: &str) -> Result<(), rustling_data::api::RepositoryError<sqlx::Error>> {
+                rustling_data::PostgresDriver::create_notify_trigger(&self.pool, #table_name, "id", channel).await
+            }
+
+            /// Row changes sent on `channel` by the trigger from [`Self::create_notify_trigger`].
+            pub async fn listen_changes(&self, channel: &str) -> Result<rustling_data::Notifications<rustling_data::RowChange<#id_type>>, rustling_data::api::RepositoryError<sqlx::Error>>
+            where
+                for<'__r> #id_type: rustling_data::serde::de::DeserializeOwned + Send + 'static,
+            {
+                rustling_data::PostgresDriver::listen(&self.pool, channel).await
             }
         }
+
+        #crud_impl
     };
 
     gene.into()
 }
-
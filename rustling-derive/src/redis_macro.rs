@@ -0,0 +1,267 @@
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::ext::IdentExt;
+
+use crate::common::{parse_backend, parse_repository_meta, repository_impl};
+
+/// `#[derive(RedisRepository)]`, or an in-memory repository with `#[backend(memory)]`.
+pub fn redis_repository_derive(input: TokenStream) -> TokenStream {
+    let ast: syn::DeriveInput = syn::parse(input).unwrap();
+    match parse_backend(&ast).as_deref() {
+        None | Some("redis") => implement_redis_repository_trait(&ast),
+        Some("memory") => crate::memory_macro::implement_repository_trait(
+            &ast,
+            "prefix",
+            quote! { ::rustling_data::api::RedisError },
+        ),
+        Some(other) => panic!("Unsupported backend `{}`, expected redis or memory", other),
+    }
+}
+
+fn implement_redis_repository_trait(ast: &syn::DeriveInput) -> TokenStream {
+    let name = &ast.ident;
+    let meta = parse_repository_meta(ast, "prefix");
+
+    let entity = meta.entity;
+    let id = meta.id;
+    let prefix = syn::LitStr::new(&meta.storage_name, proc_macro2::Span::call_site());
+
+    let codec = match ast.attrs.iter().find(|a| a.path().is_ident("codec")) {
+        Some(attr) => match attr
+            .parse_args::<syn::Ident>()
+            .expect("Expected #[codec(json)] or #[codec(message_pack)]")
+            .to_string()
+            .as_str()
+        {
+            "json" => quote! { ::rustling_data::Codec::Json },
+            "message_pack" => quote! { ::rustling_data::Codec::MessagePack },
+            other => panic!(
+                "Unsupported codec `{}`, expected json or message_pack",
+                other
+            ),
+        },
+        None => quote! { ::rustling_data::Codec::Json },
+    };
+    let ttl = match ast.attrs.iter().find(|a| a.path().is_ident("ttl")) {
+        Some(attr) => {
+            let seconds: syn::LitInt = attr.parse_args().expect("Expected #[ttl(seconds)]");
+            quote! { Some(#seconds) }
+        }
+        None => quote! { None },
+    };
+    // Serialized field names: the derive cannot see the entity's `#[serde(rename)]`, so
+    // renamed fields are given as string literals.
+    let indexes: Vec<String> = match ast.attrs.iter().find(|a| a.path().is_ident("indexed")) {
+        Some(attr) => attr
+            .parse_args_with(|input: syn::parse::ParseStream| {
+                syn::punctuated::Punctuated::<String, syn::Token![,]>::parse_terminated_with(
+                    input,
+                    |input| {
+                        if input.peek(syn::LitStr) {
+                            Ok(input.parse::<syn::LitStr>()?.value())
+                        } else {
+                            Ok(input.call(syn::Ident::parse_any)?.unraw().to_string())
+                        }
+                    },
+                )
+            })
+            .expect("Expected #[indexed(field, \"serialized_name\", ...)]")
+            .into_iter()
+            .collect(),
+        None => Vec::new(),
+    };
+
+    let repository = repository_impl(
+        name,
+        &entity,
+        &id,
+        &quote! { ::rustling_data::api::RedisError },
+    );
+
+    let generated = quote! {
+        impl #name {
+            /// Key layout and encoding of the repository's entities.
+            pub const KEY_SPACE: ::rustling_data::KeySpace<'static> = ::rustling_data::KeySpace {
+                prefix: #prefix,
+                codec: #codec,
+                ttl: #ttl,
+                indexes: &[#(#indexes),*],
+            };
+
+            fn driver(&self) -> ::rustling_data::RedisDriver {
+                ::rustling_data::RedisDriver::new(self.connection.clone())
+            }
+
+            /// Loads the live entities whose `#[indexed(...)]` field serializes to `value`.
+            pub async fn find_by(&self, field: &str, value: &(impl ::rustling_data::serde::Serialize + Sync)) -> Result<Vec<#entity>, ::rustling_data::api::RepositoryError<::rustling_data::api::RedisError>> {
+                let found = self.driver().find_by_index::<#entity>(&Self::KEY_SPACE, field, value).await?;
+                Ok(found
+                    .into_iter()
+                    .filter(|entity| !::rustling_data::memory::MemoryEntity::<#id>::is_deleted(entity))
+                    .map(<#entity as ::rustling_data::api::Lifecycle>::loaded)
+                    .collect())
+            }
+
+            /// Loads all entities, including soft-deleted ones.
+            pub async fn find_with_deleted(&self) -> Result<Vec<#entity>, ::rustling_data::api::RepositoryError<::rustling_data::api::RedisError>> {
+                let found = self.driver().find_all::<#entity>(&Self::KEY_SPACE).await?;
+                Ok(found.into_iter().map(<#entity as ::rustling_data::api::Lifecycle>::loaded).collect())
+            }
+
+            /// Loads an entity even if it is soft-deleted.
+            pub async fn find_one_with_deleted(&self, id: &#id) -> Result<Option<#entity>, ::rustling_data::api::RepositoryError<::rustling_data::api::RedisError>> {
+                let found = self.driver().find_one::<#entity>(&Self::KEY_SPACE, id).await?;
+                Ok(found.map(<#entity as ::rustling_data::api::Lifecycle>::loaded))
+            }
+
+            /// Undoes a soft delete; returns the number of restored entities.
+            pub async fn restore_one(&self, id: &#id) -> Result<u64, ::rustling_data::api::RepositoryError<::rustling_data::api::RedisError>> {
+                if <#entity as ::rustling_data::api::Entity>::SOFT_DELETE.is_none() {
+                    return Err(::rustling_data::api::RepositoryError::Other(format!("{} has no #[rustling(soft_delete)] field", stringify!(#entity))));
+                }
+                let restored = self.driver().modify::<#entity, _>(&Self::KEY_SPACE, id, |stored| {
+                    let deleted = ::rustling_data::memory::MemoryEntity::<#id>::is_deleted(stored);
+                    if deleted {
+                        ::rustling_data::memory::MemoryEntity::<#id>::set_deleted(stored, false);
+                    }
+                    Ok(deleted)
+                }).await?;
+                Ok(u64::from(restored.is_some()))
+            }
+
+            /// Permanently deletes an entity, whether or not it is soft-deleted.
+            pub async fn purge_one(&self, id: &#id) -> Result<u64, ::rustling_data::api::RepositoryError<::rustling_data::api::RedisError>> {
+                self.driver().delete::<#entity>(&Self::KEY_SPACE, id).await
+            }
+        }
+
+        #[async_trait::async_trait]
+        impl ::rustling_data::api::CrudRepository<#entity, #id, ::rustling_data::api::RedisError> for #name {
+            async fn find_all(&self) -> Result<Vec<#entity>, ::rustling_data::api::RepositoryError<::rustling_data::api::RedisError>> {
+                let found = self.driver().find_all::<#entity>(&Self::KEY_SPACE).await?;
+                Ok(found
+                    .into_iter()
+                    .filter(|entity| !::rustling_data::memory::MemoryEntity::<#id>::is_deleted(entity))
+                    .map(<#entity as ::rustling_data::api::Lifecycle>::loaded)
+                    .collect())
+            }
+
+            async fn find_one(&self, id: &#id) -> Result<Option<#entity>, ::rustling_data::api::RepositoryError<::rustling_data::api::RedisError>> {
+                let found = self.driver().find_one::<#entity>(&Self::KEY_SPACE, id).await?;
+                Ok(found
+                    .filter(|entity| !::rustling_data::memory::MemoryEntity::<#id>::is_deleted(entity))
+                    .map(<#entity as ::rustling_data::api::Lifecycle>::loaded))
+            }
+
+            async fn insert_one(&self, entity: &#entity) -> Result<#id, ::rustling_data::api::RepositoryError<::rustling_data::api::RedisError>> {
+                let mut entity = ::rustling_data::api::Entity::copy_for_write(entity);
+                ::rustling_data::api::Entity::stamp_insert(&mut entity, &::rustling_data::audit::AuditStamp::current());
+                <#entity as ::rustling_data::api::Lifecycle>::before_insert(&mut entity).map_err(::rustling_data::api::RepositoryError::Other)?;
+                ::rustling_data::api::Entity::validate(&entity).map_err(::rustling_data::api::RepositoryError::Validation)?;
+                let driver = self.driver();
+                let id: #id = driver.next_id(&Self::KEY_SPACE).await?;
+                ::rustling_data::memory::MemoryEntity::<#id>::set_id(&mut entity, ::core::clone::Clone::clone(&id));
+                driver.insert(&Self::KEY_SPACE, &id, &entity).await?;
+                <#entity as ::rustling_data::api::Lifecycle>::after_insert(&entity);
+                if ::rustling_data::events::is_active() {
+                    // The insert already succeeded, so a failed reload only skips the event.
+                    if let Ok(Some(entity)) = self.find_one(&id).await {
+                        ::rustling_data::events::publish(::rustling_data::events::EntityInserted { entity });
+                    }
+                }
+                Ok(id)
+            }
+
+            async fn update_one(&self, id: &#id, entity: &#entity) -> Result<Option<#entity>, ::rustling_data::api::RepositoryError<::rustling_data::api::RedisError>> {
+                let mut entity = ::rustling_data::api::Entity::copy_for_write(entity);
+                ::rustling_data::api::Entity::stamp_update(&mut entity, &::rustling_data::audit::AuditStamp::current());
+                <#entity as ::rustling_data::api::Lifecycle>::before_update(&mut entity).map_err(::rustling_data::api::RepositoryError::Other)?;
+                ::rustling_data::api::Entity::validate(&entity).map_err(::rustling_data::api::RepositoryError::Validation)?;
+                let expected = ::rustling_data::api::Entity::version(&entity);
+                let written = self.driver().modify::<#entity, _>(&Self::KEY_SPACE, id, |stored| {
+                    if ::rustling_data::memory::MemoryEntity::<#id>::is_deleted(stored) {
+                        return Ok(false);
+                    }
+                    if <#entity as ::rustling_data::api::Entity>::VERSION_FIELD.is_some() && ::rustling_data::api::Entity::version(stored) != expected {
+                        return Err(::rustling_data::api::RepositoryError::OptimisticLockConflict);
+                    }
+                    ::rustling_data::memory::MemoryEntity::<#id>::apply_update(stored, &entity);
+                    ::rustling_data::memory::MemoryEntity::<#id>::bump_version(stored);
+                    Ok(true)
+                }).await?;
+                Ok(written.map(|(before, after)| {
+                    let after = <#entity as ::rustling_data::api::Lifecycle>::loaded(after);
+                    <#entity as ::rustling_data::api::Lifecycle>::after_update(&after);
+                    if ::rustling_data::events::is_active() {
+                        ::rustling_data::events::publish(::rustling_data::events::EntityUpdated {
+                            before: <#entity as ::rustling_data::api::Lifecycle>::loaded(before),
+                            after: ::rustling_data::api::Entity::copy_for_write(&after),
+                        });
+                    }
+                    after
+                }))
+            }
+
+            async fn patch_one(&self, id: &#id, patch: &::rustling_data::api::Patch<#entity>) -> Result<Option<#entity>, ::rustling_data::api::RepositoryError<::rustling_data::api::RedisError>> {
+                if <#entity as ::rustling_data::memory::MemoryEntity<#id>>::is_empty_patch(patch) {
+                    return self.find_one(id).await;
+                }
+                <#entity as ::rustling_data::memory::MemoryEntity<#id>>::validate_patch(patch).map_err(::rustling_data::api::RepositoryError::Validation)?;
+                let stamp = ::rustling_data::audit::AuditStamp::current();
+                let expected = <#entity as ::rustling_data::memory::MemoryEntity<#id>>::expected_version(patch);
+                let written = self.driver().modify::<#entity, _>(&Self::KEY_SPACE, id, |stored| {
+                    if ::rustling_data::memory::MemoryEntity::<#id>::is_deleted(stored) {
+                        return Ok(false);
+                    }
+                    if expected.is_some() && ::rustling_data::api::Entity::version(stored) != expected {
+                        return Err(::rustling_data::api::RepositoryError::OptimisticLockConflict);
+                    }
+                    ::rustling_data::memory::MemoryEntity::<#id>::apply_patch(stored, patch, &stamp);
+                    ::rustling_data::memory::MemoryEntity::<#id>::bump_version(stored);
+                    Ok(true)
+                }).await?;
+                Ok(written.map(|(before, after)| {
+                    let after = <#entity as ::rustling_data::api::Lifecycle>::loaded(after);
+                    <#entity as ::rustling_data::api::Lifecycle>::after_update(&after);
+                    if ::rustling_data::events::is_active() {
+                        ::rustling_data::events::publish(::rustling_data::events::EntityUpdated {
+                            before: <#entity as ::rustling_data::api::Lifecycle>::loaded(before),
+                            after: ::rustling_data::api::Entity::copy_for_write(&after),
+                        });
+                    }
+                    after
+                }))
+            }
+
+            async fn delete_one(&self, id: &#id) -> Result<u64, ::rustling_data::api::RepositoryError<::rustling_data::api::RedisError>> {
+                if <#entity as ::rustling_data::api::Entity>::LIFECYCLE {
+                    if let Some(entity) = self.find_one(id).await? {
+                        <#entity as ::rustling_data::api::Lifecycle>::before_delete(&entity).map_err(::rustling_data::api::RepositoryError::Other)?;
+                    }
+                }
+                let driver = self.driver();
+                let affected = match <#entity as ::rustling_data::api::Entity>::SOFT_DELETE {
+                    Some(_) => {
+                        let written = driver.modify::<#entity, _>(&Self::KEY_SPACE, id, |stored| {
+                            if ::rustling_data::memory::MemoryEntity::<#id>::is_deleted(stored) {
+                                return Ok(false);
+                            }
+                            ::rustling_data::memory::MemoryEntity::<#id>::set_deleted(stored, true);
+                            Ok(true)
+                        }).await?;
+                        u64::from(written.is_some())
+                    }
+                    None => driver.delete::<#entity>(&Self::KEY_SPACE, id).await?,
+                };
+                if affected > 0 {
+                    ::rustling_data::events::publish(::rustling_data::events::EntityDeleted { id: ::core::clone::Clone::clone(id) });
+                }
+                Ok(affected)
+            }
+        }
+
+        #repository
+    };
+
+    generated.into()
+}
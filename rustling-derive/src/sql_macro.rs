@@ -0,0 +1,245 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::DeriveInput;
+
+use crate::common::{parse_backend, repository_impl};
+
+/// `#[derive(Repository)]`: dispatches on `#[backend(postgres | sqlite | mysql | memory)]`,
+/// PostgreSQL by default.
+pub fn repository_derive(input: TokenStream) -> TokenStream {
+    let ast: DeriveInput = syn::parse(input).unwrap();
+    let backend = parse_backend(&ast).unwrap_or_else(|| "postgres".to_string());
+
+    match backend.as_str() {
+        #[cfg(feature = "postgres")]
+        "postgres" => crate::postgres_macro::implement_repository_trait(&ast),
+        #[cfg(not(feature = "postgres"))]
+        "postgres" => missing_feature("postgres"),
+        #[cfg(feature = "sqlite")]
+        "sqlite" => crate::sqlite_macro::implement_repository_trait(&ast),
+        #[cfg(not(feature = "sqlite"))]
+        "sqlite" => missing_feature("sqlite"),
+        #[cfg(feature = "mysql")]
+        "mysql" => crate::mysql_macro::implement_repository_trait(&ast),
+        #[cfg(not(feature = "mysql"))]
+        "mysql" => missing_feature("mysql"),
+        "memory" => crate::memory_macro::implement_repository_trait(
+            &ast,
+            "table",
+            quote! { ::rustling_data::sqlx::Error },
+        ),
+        other => panic!(
+            "Unsupported backend `{}`, expected postgres, sqlite, mysql or memory",
+            other
+        ),
+    }
+}
+
+#[cfg(not(all(feature = "postgres", feature = "sqlite", feature = "mysql")))]
+fn missing_feature(backend: &str) -> TokenStream {
+    panic!(
+        "#[backend({0})] requires the `{0}` feature of rustling-derive",
+        backend
+    )
+}
+
+/// Driver and value-binding names used by the SQL repository code of one backend.
+pub struct SqlBackend {
+    /// Driver type, e.g. `rustling_data::PostgresDriver`.
+    pub driver: TokenStream2,
+    /// Values type passed to the driver, e.g. `rustling_data::PgValues`.
+    pub values: TokenStream2,
+    /// Entity method returning the insert values.
+    pub entity_values: syn::Ident,
+    /// Entity method returning the update values.
+    pub entity_update_values: syn::Ident,
+    /// Patch method returning the values of its set fields.
+    pub patch_values: syn::Ident,
+    /// Placeholder of the first statement parameter, e.g. `$1`.
+    pub first_param: &'static str,
+}
+
+/// Inherent methods for soft-deleted rows shared by the SQL repositories.
+pub fn soft_delete_methods(
+    backend: &SqlBackend,
+    entity_type: &TokenStream2,
+    id_type: &TokenStream2,
+    table_name: &syn::LitStr,
+) -> TokenStream2 {
+    let driver = &backend.driver;
+    quote! {
+        /// Loads all entities, including soft-deleted ones.
+        pub async fn find_with_deleted(&self) -> Result<Vec<#entity_type>, rustling_data::api::RepositoryError<sqlx::Error>> {
+            let found = #driver::find_all(&self.pool, #table_name, #entity_type::all_columns()).await?;
+            Ok(found.into_iter().map(<#entity_type as rustling_data::api::Lifecycle>::loaded).collect())
+        }
+
+        /// Loads an entity even if it is soft-deleted.
+        pub async fn find_one_with_deleted(&self, id: &#id_type) -> Result<Option<#entity_type>, rustling_data::api::RepositoryError<sqlx::Error>> {
+            let found = #driver::find_one(&self.pool, #table_name, #entity_type::all_columns(), "id", *id).await?;
+            Ok(found.map(<#entity_type as rustling_data::api::Lifecycle>::loaded))
+        }
+
+        /// Undoes a soft delete; returns the number of restored rows.
+        pub async fn restore_one(&self, id: &#id_type) -> Result<u64, rustling_data::api::RepositoryError<sqlx::Error>> {
+            match <#entity_type as rustling_data::api::Entity>::SOFT_DELETE {
+                Some(soft_delete) => #driver::restore(&self.pool, #table_name, "id", *id, &soft_delete).await,
+                None => Err(rustling_data::api::RepositoryError::Other(format!("{} has no #[rustling(soft_delete)] field", stringify!(#entity_type)))),
+            }
+        }
+
+        /// Permanently deletes an entity, whether or not it is soft-deleted.
+        pub async fn purge_one(&self, id: &#id_type) -> Result<u64, rustling_data::api::RepositoryError<sqlx::Error>> {
+            #driver::delete(&self.pool, #table_name, "id", *id).await
+        }
+    }
+}
+
+/// `CrudRepository` implementation over the backend's driver.
+pub fn crud_impl(
+    backend: &SqlBackend,
+    name: &syn::Ident,
+    entity_type: &TokenStream2,
+    id_type: &TokenStream2,
+    table_name: &syn::LitStr,
+) -> TokenStream2 {
+    let SqlBackend {
+        driver,
+        values: values_type,
+        entity_values,
+        entity_update_values,
+        patch_values,
+        first_param,
+    } = backend;
+    let repository = repository_impl(name, entity_type, id_type, &quote! { sqlx::Error });
+    let id_filter = format!("id = {} AND {{}}", first_param);
+
+    quote! {
+        #[async_trait::async_trait]
+        impl rustling_data::api::CrudRepository<#entity_type, #id_type, sqlx::Error> for #name {
+            async fn find_all(&self) -> Result<Vec<#entity_type>, rustling_data::api::RepositoryError<sqlx::Error>> {
+                let found = match <#entity_type as rustling_data::api::Entity>::SOFT_DELETE {
+                    Some(soft_delete) => {
+                        let filter = #driver::not_deleted(&soft_delete);
+                        #driver::find_where(&self.pool, #table_name, #entity_type::all_columns(), &filter, #values_type::new()).await?
+                    }
+                    None => #driver::find_all(&self.pool, #table_name, #entity_type::all_columns()).await?,
+                };
+                Ok(found.into_iter().map(<#entity_type as rustling_data::api::Lifecycle>::loaded).collect())
+            }
+
+            async fn find_one(&self, id: &#id_type) -> Result<Option<#entity_type>, rustling_data::api::RepositoryError<sqlx::Error>> {
+                match <#entity_type as rustling_data::api::Entity>::SOFT_DELETE {
+                    Some(soft_delete) => {
+                        let filter = format!(#id_filter, #driver::not_deleted(&soft_delete));
+                        let mut found = #driver::find_where(&self.pool, #table_name, #entity_type::all_columns(), &filter, vec![id]).await?;
+                        Ok(found.pop().map(<#entity_type as rustling_data::api::Lifecycle>::loaded))
+                    }
+                    None => Ok(#driver::find_one(&self.pool, #table_name, #entity_type::all_columns(), "id", *id).await?.map(<#entity_type as rustling_data::api::Lifecycle>::loaded)),
+                }
+            }
+
+            async fn insert_one(&self, entity: &#entity_type) -> Result<#id_type, rustling_data::api::RepositoryError<sqlx::Error>> {
+                let mut entity = rustling_data::api::Entity::copy_for_write(entity);
+                rustling_data::api::Entity::stamp_insert(&mut entity, &rustling_data::audit::AuditStamp::current());
+                <#entity_type as rustling_data::api::Lifecycle>::before_insert(&mut entity).map_err(rustling_data::api::RepositoryError::Other)?;
+                rustling_data::api::Entity::validate(&entity).map_err(rustling_data::api::RepositoryError::Validation)?;
+                let columns = #entity_type::columns();
+                let values = entity.#entity_values();
+                let id = #driver::insert(&self.pool, #table_name, columns, values).await?;
+                <#entity_type as rustling_data::api::Lifecycle>::after_insert(&entity);
+                if rustling_data::events::is_active() {
+                    // The insert already succeeded, so a failed reload only skips the event.
+                    if let Ok(Some(entity)) = self.find_one(&id).await {
+                        rustling_data::events::publish(rustling_data::events::EntityInserted { entity });
+                    }
+                }
+                Ok(id)
+            }
+
+            async fn update_one(&self, id: &#id_type, entity: &#entity_type) -> Result<Option<#entity_type>, rustling_data::api::RepositoryError<sqlx::Error>> {
+                let mut entity = rustling_data::api::Entity::copy_for_write(entity);
+                rustling_data::api::Entity::stamp_update(&mut entity, &rustling_data::audit::AuditStamp::current());
+                <#entity_type as rustling_data::api::Lifecycle>::before_update(&mut entity).map_err(rustling_data::api::RepositoryError::Other)?;
+                rustling_data::api::Entity::validate(&entity).map_err(rustling_data::api::RepositoryError::Validation)?;
+                let before = if rustling_data::events::is_active() { self.find_one(id).await? } else { None };
+                let columns = #entity_type::update_columns();
+                let values = entity.#entity_update_values();
+                let soft_delete = <#entity_type as rustling_data::api::Entity>::SOFT_DELETE;
+                let affected = match <#entity_type as rustling_data::api::Entity>::VERSION_FIELD {
+                    Some(version) => {
+                        let expected = rustling_data::api::Entity::version(&entity);
+                        #driver::update_versioned(&self.pool, #table_name, "id", *id, columns, values, version.column, expected, soft_delete.as_ref()).await?
+                    }
+                    None => #driver::update(&self.pool, #table_name, "id", *id, columns, values, soft_delete.as_ref()).await?,
+                };
+                if affected > 0 {
+                    let updated = self.find_one(id).await?;
+                    if let Some(updated) = &updated {
+                        <#entity_type as rustling_data::api::Lifecycle>::after_update(updated);
+                        if let Some(before) = before {
+                            rustling_data::events::publish(rustling_data::events::EntityUpdated { before, after: rustling_data::api::Entity::copy_for_write(updated) });
+                        }
+                    }
+                    Ok(updated)
+                } else if <#entity_type as rustling_data::api::Entity>::VERSION_FIELD.is_some() && self.find_one(id).await?.is_some() {
+                    Err(rustling_data::api::RepositoryError::OptimisticLockConflict)
+                } else {
+                    Ok(None)
+                }
+            }
+
+            async fn patch_one(&self, id: &#id_type, patch: &rustling_data::api::Patch<#entity_type>) -> Result<Option<#entity_type>, rustling_data::api::RepositoryError<sqlx::Error>> {
+                if patch.columns().is_empty() {
+                    return self.find_one(id).await;
+                }
+                patch.validate().map_err(rustling_data::api::RepositoryError::Validation)?;
+                let before = if rustling_data::events::is_active() { self.find_one(id).await? } else { None };
+                let stamped = patch.stamped(&rustling_data::audit::AuditStamp::current());
+                let patch = stamped.as_ref().unwrap_or(patch);
+                let columns = patch.columns();
+                let soft_delete = <#entity_type as rustling_data::api::Entity>::SOFT_DELETE;
+                let affected = match <#entity_type as rustling_data::api::Entity>::VERSION_FIELD {
+                    Some(version) => {
+                        let expected = patch.expected_version();
+                        #driver::update_versioned(&self.pool, #table_name, "id", *id, &columns, patch.#patch_values(), version.column, expected, soft_delete.as_ref()).await?
+                    }
+                    None => #driver::update(&self.pool, #table_name, "id", *id, &columns, patch.#patch_values(), soft_delete.as_ref()).await?,
+                };
+                if affected > 0 {
+                    let updated = self.find_one(id).await?;
+                    if let Some(updated) = &updated {
+                        <#entity_type as rustling_data::api::Lifecycle>::after_update(updated);
+                        if let Some(before) = before {
+                            rustling_data::events::publish(rustling_data::events::EntityUpdated { before, after: rustling_data::api::Entity::copy_for_write(updated) });
+                        }
+                    }
+                    Ok(updated)
+                } else if <#entity_type as rustling_data::api::Entity>::VERSION_FIELD.is_some() && self.find_one(id).await?.is_some() {
+                    Err(rustling_data::api::RepositoryError::OptimisticLockConflict)
+                } else {
+                    Ok(None)
+                }
+            }
+
+            async fn delete_one(&self, id: &#id_type) -> Result<u64, rustling_data::api::RepositoryError<sqlx::Error>> {
+                if <#entity_type as rustling_data::api::Entity>::LIFECYCLE {
+                    if let Some(entity) = self.find_one(id).await? {
+                        <#entity_type as rustling_data::api::Lifecycle>::before_delete(&entity).map_err(rustling_data::api::RepositoryError::Other)?;
+                    }
+                }
+                let affected = match <#entity_type as rustling_data::api::Entity>::SOFT_DELETE {
+                    Some(soft_delete) => #driver::soft_delete(&self.pool, #table_name, "id", *id, &soft_delete).await?,
+                    None => #driver::delete(&self.pool, #table_name, "id", *id).await?,
+                };
+                if affected > 0 {
+                    rustling_data::events::publish(rustling_data::events::EntityDeleted { id: *id });
+                }
+                Ok(affected)
+            }
+        }
+
+        #repository
+    }
+}
@@ -0,0 +1,142 @@
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{ToTokens, quote};
+use syn::meta::ParseNestedMeta;
+
+/// A validation attribute on an entity field, e.g. `#[rustling(length(max = 64))]`.
+pub enum Validation {
+    Length {
+        min: Option<syn::Expr>,
+        max: Option<syn::Expr>,
+    },
+    Range {
+        min: Option<syn::Expr>,
+        max: Option<syn::Expr>,
+    },
+    Regex(syn::LitStr),
+    Email,
+    Custom(syn::Path),
+}
+
+impl Validation {
+    /// Parses `meta` if it is a validation attribute, `None` for other attributes.
+    pub fn parse(meta: &ParseNestedMeta) -> syn::Result<Option<Self>> {
+        if meta.path.is_ident("length") {
+            let (min, max) = parse_bounds(meta)?;
+            Ok(Some(Validation::Length { min, max }))
+        } else if meta.path.is_ident("range") {
+            let (min, max) = parse_bounds(meta)?;
+            Ok(Some(Validation::Range { min, max }))
+        } else if meta.path.is_ident("regex") {
+            let pattern: syn::LitStr = meta.value()?.parse()?;
+            if let Err(error) = regex::Regex::new(&pattern.value()) {
+                return Err(syn::Error::new(
+                    pattern.span(),
+                    format!("invalid regex: {}", error),
+                ));
+            }
+            Ok(Some(Validation::Regex(pattern)))
+        } else if meta.path.is_ident("email") {
+            Ok(Some(Validation::Email))
+        } else if meta.path.is_ident("custom") {
+            let path: syn::LitStr = meta.value()?.parse()?;
+            Ok(Some(Validation::Custom(path.parse()?)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Statements checking `value` (a reference to the field value) and pushing to `errors`.
+    pub fn check(&self, field: &str) -> TokenStream2 {
+        let error = |message: TokenStream2| {
+            quote! { errors.push(::rustling_data::validate::FieldError::new(#field, #message)); }
+        };
+        match self {
+            Validation::Length { min, max } => {
+                let min_check = min.as_ref().map(|min| {
+                    let error = error(bound_message("length must be at least", min));
+                    quote! { if length < #min { #error } }
+                });
+                let max_check = max.as_ref().map(|max| {
+                    let error = error(bound_message("length must be at most", max));
+                    quote! { if length > #max { #error } }
+                });
+                quote! {
+                    let length = ::rustling_data::validate::Length::length(value);
+                    #min_check
+                    #max_check
+                }
+            }
+            Validation::Range { min, max } => {
+                let min_check = min.as_ref().map(|min| {
+                    let error = error(bound_message("must be at least", min));
+                    quote! { if *value < #min { #error } }
+                });
+                let max_check = max.as_ref().map(|max| {
+                    let error = error(bound_message("must be at most", max));
+                    quote! { if *value > #max { #error } }
+                });
+                quote! { #min_check #max_check }
+            }
+            Validation::Regex(pattern) => {
+                let error = error(quote! { concat!("must match ", #pattern) });
+                quote! {
+                    static PATTERN: ::std::sync::OnceLock<::rustling_data::regex::Regex> = ::std::sync::OnceLock::new();
+                    let pattern = PATTERN.get_or_init(|| {
+                        // Checked when the entity was derived.
+                        ::rustling_data::regex::Regex::new(#pattern).unwrap()
+                    });
+                    if !pattern.is_match(value) { #error }
+                }
+            }
+            Validation::Email => {
+                let error = error(quote! { "must be a valid email address" });
+                quote! {
+                    if !::rustling_data::validate::is_email(value) { #error }
+                }
+            }
+            Validation::Custom(path) => {
+                let error = error(quote! { message });
+                quote! {
+                    if let Err(message) = #path(value) { #error }
+                }
+            }
+        }
+    }
+}
+
+fn parse_bounds(meta: &ParseNestedMeta) -> syn::Result<(Option<syn::Expr>, Option<syn::Expr>)> {
+    let (mut min, mut max) = (None, None);
+    meta.parse_nested_meta(|bound| {
+        if bound.path.is_ident("min") {
+            min = Some(bound.value()?.parse()?);
+            Ok(())
+        } else if bound.path.is_ident("max") {
+            max = Some(bound.value()?.parse()?);
+            Ok(())
+        } else {
+            Err(bound.error("expected `min` or `max`"))
+        }
+    })?;
+    if min.is_none() && max.is_none() {
+        return Err(meta.error("expected `min` and/or `max`"));
+    }
+    Ok((min, max))
+}
+
+fn bound_message(prefix: &str, bound: &syn::Expr) -> TokenStream2 {
+    let message = format!("{} {}", prefix, bound.to_token_stream());
+    quote! { #message }
+}
+
+/// Body of a `validate` method running `checks`, each expanding to statements on `errors`.
+pub fn validate_body(checks: impl Iterator<Item = TokenStream2>) -> TokenStream2 {
+    let checks: Vec<_> = checks.collect();
+    if checks.is_empty() {
+        return quote! { Ok(()) };
+    }
+    quote! {
+        let mut errors = Vec::new();
+        #(#checks)*
+        if errors.is_empty() { Ok(()) } else { Err(errors) }
+    }
+}